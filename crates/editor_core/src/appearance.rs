@@ -0,0 +1,416 @@
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+/// How often the preference is read on desktops that announce no changes
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// The color scheme the desktop prefers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SystemAppearance {
+    Light,
+    Dark,
+}
+
+/// Source of the desktop's color scheme preference
+pub trait AppearanceProvider: Send + Sync {
+    /// The current preference, `None` when the desktop states none
+    fn appearance(&self) -> Option<SystemAppearance>;
+
+    /// Send on `changed` whenever the preference may have changed, for as
+    /// long as the receiver lives; a provider that never changes sends nothing
+    fn watch(&self, changed: Sender<()>) -> Result<()> {
+        drop(changed);
+        Ok(())
+    }
+}
+
+/// The preference of the running desktop
+///
+/// Read from the freedesktop settings portal on Linux, whose `SettingChanged`
+/// signal announces changes, and polled from the global `AppleInterfaceStyle`
+/// default on macOS and the personalization registry key on Windows.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DesktopAppearance;
+
+impl AppearanceProvider for DesktopAppearance {
+    fn appearance(&self) -> Option<SystemAppearance> {
+        desktop_appearance()
+    }
+
+    fn watch(&self, changed: Sender<()>) -> Result<()> {
+        watch_desktop_appearance(changed)
+    }
+}
+
+/// A preference set by hand, for tests and desktops without one
+#[derive(Debug, Default)]
+pub struct FixedAppearance {
+    appearance: Mutex<Option<SystemAppearance>>,
+    watchers: Mutex<Vec<Sender<()>>>,
+}
+
+impl FixedAppearance {
+    pub fn new(appearance: Option<SystemAppearance>) -> Self {
+        Self {
+            appearance: Mutex::new(appearance),
+            watchers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Change the preference, announcing it to watchers
+    pub fn set(&self, appearance: Option<SystemAppearance>) {
+        *self.appearance.lock() = appearance;
+        self.watchers.lock().retain(|watcher| watcher.send(()).is_ok());
+    }
+}
+
+impl AppearanceProvider for FixedAppearance {
+    fn appearance(&self) -> Option<SystemAppearance> {
+        *self.appearance.lock()
+    }
+
+    fn watch(&self, changed: Sender<()>) -> Result<()> {
+        self.watchers.lock().push(changed);
+        Ok(())
+    }
+}
+
+/// Follows the desktop's preference, notifying subscribers when it changes
+pub struct AppearanceMonitor {
+    provider: Arc<dyn AppearanceProvider>,
+    current: Mutex<Option<SystemAppearance>>,
+    /// Whether a theme follows the preference; the provider is not asked otherwise
+    active: AtomicBool,
+    subscribers: Mutex<Vec<Sender<Option<SystemAppearance>>>>,
+}
+
+impl AppearanceMonitor {
+    pub fn new(provider: Arc<dyn AppearanceProvider>) -> Arc<Self> {
+        let current = provider.appearance();
+        Arc::new(Self {
+            provider,
+            current: Mutex::new(current),
+            active: AtomicBool::new(true),
+            subscribers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// The preference as last read
+    pub fn current(&self) -> Option<SystemAppearance> {
+        *self.current.lock()
+    }
+
+    /// Receive the new preference every time it changes
+    pub fn subscribe(&self) -> Receiver<Option<SystemAppearance>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    /// Start or stop following the preference; it is read again on starting
+    pub fn set_active(&self, active: bool) {
+        let was_active = self.active.swap(active, Ordering::Relaxed);
+        if active && !was_active {
+            self.refresh();
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Read the preference again and notify subscribers if it changed
+    pub fn refresh(&self) -> bool {
+        let appearance = self.provider.appearance();
+        let changed = {
+            let mut current = self.current.lock();
+            let changed = *current != appearance;
+            *current = appearance;
+            changed
+        };
+        if changed {
+            self.subscribers
+                .lock()
+                .retain(|subscriber| subscriber.send(appearance).is_ok());
+        }
+        changed
+    }
+
+    /// Refresh, while active, whenever the provider announces a change
+    ///
+    /// Stops once the monitor, and with it the provider, is dropped.
+    pub fn watch(self: &Arc<Self>) -> Result<()> {
+        let (tx, rx) = mpsc::channel();
+        self.provider.watch(tx)?;
+        let monitor = Arc::downgrade(self);
+        std::thread::Builder::new()
+            .name("appearance-monitor".to_string())
+            .spawn(move || {
+                while rx.recv().is_ok() {
+                    let Some(monitor) = monitor.upgrade() else {
+                        break;
+                    };
+                    if monitor.is_active() {
+                        monitor.refresh();
+                    }
+                }
+            })?;
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+#[cfg(target_os = "linux")]
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+#[cfg(target_os = "linux")]
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+#[cfg(target_os = "linux")]
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+/// Read `org.freedesktop.appearance color-scheme` from the settings portal,
+/// with `gdbus` or, where GLib's tools are missing, systemd's `busctl`
+#[cfg(target_os = "linux")]
+fn desktop_appearance() -> Option<SystemAppearance> {
+    let gdbus = command_output("gdbus", &[
+        "call",
+        "--session",
+        "--dest",
+        PORTAL_DESTINATION,
+        "--object-path",
+        PORTAL_PATH,
+        "--method",
+        "org.freedesktop.portal.Settings.Read",
+        APPEARANCE_NAMESPACE,
+        COLOR_SCHEME_KEY,
+    ]);
+    let output = gdbus.or_else(|| {
+        command_output("busctl", &[
+            "--user",
+            "call",
+            PORTAL_DESTINATION,
+            PORTAL_PATH,
+            "org.freedesktop.portal.Settings",
+            "Read",
+            "ss",
+            APPEARANCE_NAMESPACE,
+            COLOR_SCHEME_KEY,
+        ])
+    })?;
+    parse_portal_color_scheme(&output)
+}
+
+/// Follow the portal's `SettingChanged` signal with one long-running `gdbus monitor`
+#[cfg(target_os = "linux")]
+fn watch_desktop_appearance(changed: Sender<()>) -> Result<()> {
+    use std::io::BufRead;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("gdbus")
+        .args(["monitor", "--session", "--dest", PORTAL_DESTINATION, "--object-path", PORTAL_PATH])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    std::thread::Builder::new()
+        .name("appearance-portal".to_string())
+        .spawn(move || {
+            for line in std::io::BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if is_color_scheme_change(&line) && changed.send(()).is_err() {
+                    break;
+                }
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        })?;
+    Ok(())
+}
+
+/// Whether a line of `gdbus monitor` announces a new color scheme, such as
+/// `…: org.freedesktop.portal.Settings.SettingChanged ('org.freedesktop.appearance', 'color-scheme', <uint32 1>)`
+#[cfg(target_os = "linux")]
+fn is_color_scheme_change(line: &str) -> bool {
+    line.contains("SettingChanged")
+        && line.contains(&format!("'{}'", APPEARANCE_NAMESPACE))
+        && line.contains(&format!("'{}'", COLOR_SCHEME_KEY))
+}
+
+/// The value of a portal reply such as `(<<uint32 1>>,)` or `v v u 1`:
+/// 1 prefers dark, 2 prefers light and 0 states no preference
+#[cfg(target_os = "linux")]
+fn parse_portal_color_scheme(output: &str) -> Option<SystemAppearance> {
+    let value = output
+        .rsplit(|c: char| !c.is_ascii_alphanumeric())
+        .find(|word| !word.is_empty())?;
+    match value {
+        "1" => Some(SystemAppearance::Dark),
+        "2" => Some(SystemAppearance::Light),
+        _ => None,
+    }
+}
+
+/// `AppleInterfaceStyle` is only set, to `Dark`, in dark mode
+#[cfg(target_os = "macos")]
+fn desktop_appearance() -> Option<SystemAppearance> {
+    let output = std::process::Command::new("defaults")
+        .args(["read", "-g", "AppleInterfaceStyle"])
+        .output()
+        .ok()?;
+    let dark = output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "Dark";
+    Some(if dark { SystemAppearance::Dark } else { SystemAppearance::Light })
+}
+
+#[cfg(target_os = "windows")]
+fn desktop_appearance() -> Option<SystemAppearance> {
+    let output = command_output("reg", &[
+        "query",
+        r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+        "/v",
+        "AppsUseLightTheme",
+    ])?;
+    // The value is printed as `AppsUseLightTheme    REG_DWORD    0x1`
+    match output.split_whitespace().next_back()? {
+        "0x0" => Some(SystemAppearance::Dark),
+        "0x1" => Some(SystemAppearance::Light),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn desktop_appearance() -> Option<SystemAppearance> {
+    None
+}
+
+/// Neither desktop announces changes to programs without a window of their own
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn watch_desktop_appearance(changed: Sender<()>) -> Result<()> {
+    std::thread::Builder::new()
+        .name("appearance-poll".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(POLL_INTERVAL);
+            if changed.send(()).is_err() {
+                break;
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn watch_desktop_appearance(changed: Sender<()>) -> Result<()> {
+    drop(changed);
+    Ok(())
+}
+
+/// Standard output of a command that succeeded
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(appearance: Option<SystemAppearance>) -> (Arc<FixedAppearance>, Arc<AppearanceMonitor>) {
+        let provider = Arc::new(FixedAppearance::new(appearance));
+        let monitor = AppearanceMonitor::new(provider.clone());
+        (provider, monitor)
+    }
+
+    #[test]
+    fn refresh_notifies_subscribers_of_changes_only() {
+        let (provider, monitor) = monitor(Some(SystemAppearance::Dark));
+        let changes = monitor.subscribe();
+        assert_eq!(monitor.current(), Some(SystemAppearance::Dark));
+
+        assert!(!monitor.refresh());
+        assert!(changes.try_recv().is_err());
+
+        provider.set(Some(SystemAppearance::Light));
+        assert!(monitor.refresh());
+        assert_eq!(monitor.current(), Some(SystemAppearance::Light));
+        assert_eq!(changes.try_recv(), Ok(Some(SystemAppearance::Light)));
+
+        provider.set(None);
+        assert!(monitor.refresh());
+        assert_eq!(changes.try_recv(), Ok(None));
+    }
+
+    #[test]
+    fn dropped_subscribers_are_forgotten() {
+        let (provider, monitor) = monitor(None);
+        drop(monitor.subscribe());
+        let changes = monitor.subscribe();
+        provider.set(Some(SystemAppearance::Dark));
+        monitor.refresh();
+        assert_eq!(monitor.subscribers.lock().len(), 1);
+        assert_eq!(changes.try_recv(), Ok(Some(SystemAppearance::Dark)));
+    }
+
+    #[test]
+    fn activating_reads_the_preference_again() {
+        let (provider, monitor) = monitor(Some(SystemAppearance::Dark));
+        let changes = monitor.subscribe();
+        monitor.set_active(false);
+        provider.set(Some(SystemAppearance::Light));
+        assert_eq!(monitor.current(), Some(SystemAppearance::Dark));
+
+        // Staying active reads nothing; becoming active does
+        monitor.set_active(false);
+        assert!(changes.try_recv().is_err());
+        monitor.set_active(true);
+        assert_eq!(monitor.current(), Some(SystemAppearance::Light));
+        assert_eq!(changes.try_recv(), Ok(Some(SystemAppearance::Light)));
+    }
+
+    #[test]
+    fn watching_follows_announced_changes_while_active() {
+        let (provider, monitor) = monitor(Some(SystemAppearance::Dark));
+        let changes = monitor.subscribe();
+        monitor.watch().unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+
+        provider.set(Some(SystemAppearance::Light));
+        assert_eq!(changes.recv_timeout(timeout), Ok(Some(SystemAppearance::Light)));
+
+        monitor.set_active(false);
+        provider.set(Some(SystemAppearance::Dark));
+        provider.set(None);
+        monitor.set_active(true);
+        assert_eq!(changes.recv_timeout(timeout), Ok(None));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parses_portal_replies() {
+        assert_eq!(parse_portal_color_scheme("(<<uint32 1>>,)\n"), Some(SystemAppearance::Dark));
+        assert_eq!(parse_portal_color_scheme("(<<uint32 2>>,)\n"), Some(SystemAppearance::Light));
+        assert_eq!(parse_portal_color_scheme("(<<uint32 0>>,)\n"), None);
+        assert_eq!(parse_portal_color_scheme("v u 1\n"), Some(SystemAppearance::Dark));
+        assert_eq!(parse_portal_color_scheme("v v u 2\n"), Some(SystemAppearance::Light));
+        assert_eq!(parse_portal_color_scheme(""), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn recognizes_color_scheme_signals() {
+        let signal = "/org/freedesktop/portal/desktop: org.freedesktop.portal.Settings.SettingChanged \
+            ('org.freedesktop.appearance', 'color-scheme', <uint32 1>)";
+        assert!(is_color_scheme_change(signal));
+        let accent = "/org/freedesktop/portal/desktop: org.freedesktop.portal.Settings.SettingChanged \
+            ('org.freedesktop.appearance', 'accent-color', <(0.2, 0.5, 0.9)>)";
+        assert!(!is_color_scheme_change(accent));
+    }
+}
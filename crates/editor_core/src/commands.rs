@@ -0,0 +1,274 @@
+use std::fmt;
+
+/// Menu a command is listed under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandCategory {
+    File,
+    Edit,
+    View,
+    Preview,
+    Document,
+    Help,
+}
+
+impl CommandCategory {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CommandCategory::File => "File",
+            CommandCategory::Edit => "Edit",
+            CommandCategory::View => "View",
+            CommandCategory::Preview => "Preview",
+            CommandCategory::Document => "Document",
+            CommandCategory::Help => "Help",
+        }
+    }
+}
+
+impl fmt::Display for CommandCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    /// Stable id used by key bindings, such as `file.save`
+    pub id: &'static str,
+    pub title: &'static str,
+    pub category: CommandCategory,
+    pub description: &'static str,
+}
+
+impl Command {
+    /// Title as listed in the command palette, such as `File: Save`
+    pub fn palette_title(&self) -> String {
+        format!("{}: {}", self.category, self.title)
+    }
+}
+
+/// A command matching a palette query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandMatch<'a> {
+    pub command: &'a Command,
+    pub score: i64,
+    /// Character indices of the matched characters in [`Command::palette_title`]
+    pub positions: Vec<usize>,
+}
+
+/// Every named command of the editor
+#[derive(Debug, Clone, Default)]
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The commands built into the editor
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        for (id, title, category, description) in BUILTIN_COMMANDS {
+            registry.register(Command {
+                id,
+                title,
+                category,
+                description,
+            });
+        }
+        registry
+    }
+
+    /// Add a command, replacing one with the same id
+    pub fn register(&mut self, command: Command) {
+        match self.commands.iter_mut().find(|existing| existing.id == command.id) {
+            Some(existing) => *existing = command,
+            None => self.commands.push(command),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Command> {
+        self.commands.iter().find(|command| command.id == id)
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Commands of a menu, in registration order
+    pub fn in_category(&self, category: CommandCategory) -> impl Iterator<Item = &Command> {
+        self.commands.iter().filter(move |command| command.category == category)
+    }
+
+    /// Commands fuzzily matching a query, best first; an empty query lists all
+    pub fn search(&self, query: &str) -> Vec<CommandMatch<'_>> {
+        let mut matches: Vec<CommandMatch> = self.commands
+            .iter()
+            .filter_map(|command| {
+                let (score, positions) = fuzzy_match(query, &command.palette_title())?;
+                Some(CommandMatch { command, score, positions })
+            })
+            .collect();
+        // Stable, so equal scores keep registration order
+        matches.sort_by_key(|found| std::cmp::Reverse(found.score));
+        matches
+    }
+}
+
+/// Score `candidate` against a query whose characters must appear in order,
+/// ignoring case; returns the score and the matched character indices
+///
+/// Consecutive matches and matches at the start of words score higher, so
+/// `ts` ranks "Toggle Sidebar" above "Toggle Console". Of all the ways the
+/// query matches, the best scoring one is chosen, so `as` matches the word
+/// "As" of "Save As" rather than the first `a` and `s`.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let matches = |wanted: char, index: usize| candidate[index].to_lowercase().eq(std::iter::once(wanted));
+    // One point per matched character, ten more at the start of a word
+    let bonus = |index: usize| {
+        let at_word_start = index == 0 || !candidate[index - 1].is_alphanumeric()
+            || (candidate[index].is_uppercase() && candidate[index - 1].is_lowercase());
+        if at_word_start { 11 } else { 1 }
+    };
+    // Skipped characters count against the match, a little
+    let gap = |skipped: usize| skipped.min(3) as i64;
+
+    // best[i][j]: score of the best match of the first i + 1 query characters
+    // ending at candidate[j], with the position of the previous character.
+    // `max_by_key` keeps the last maximum, so reversing keeps the earliest.
+    let first_row: Vec<Option<(i64, usize)>> = (0..candidate.len())
+        .map(|index| matches(query[0], index).then(|| (bonus(index) - gap(index), 0)))
+        .collect();
+    let mut best = vec![first_row];
+    for &wanted in &query[1..] {
+        let previous_row = &best[best.len() - 1];
+        let row = (0..candidate.len())
+            .map(|index| {
+                if !matches(wanted, index) {
+                    return None;
+                }
+                previous_row[..index]
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(previous, found)| {
+                        let (score, _) = (*found)?;
+                        let contiguous = if previous + 1 == index { 5 } else { 0 };
+                        Some((score + bonus(index) + contiguous - gap(index - previous - 1), previous))
+                    })
+                    .rev()
+                    .max_by_key(|&(score, _)| score)
+            })
+            .collect();
+        best.push(row);
+    }
+
+    let (mut index, score) = best[query.len() - 1]
+        .iter()
+        .enumerate()
+        .filter_map(|(index, found)| Some((index, found.as_ref()?.0)))
+        .rev()
+        .max_by_key(|&(_, score)| score)?;
+    let mut positions = vec![0; query.len()];
+    for i in (0..query.len()).rev() {
+        positions[i] = index;
+        index = best[i][index].map_or(0, |(_, previous)| previous);
+    }
+    Some((score, positions))
+}
+
+const BUILTIN_COMMANDS: [(&str, &str, CommandCategory, &str); 22] = [
+    ("file.new", "New File", CommandCategory::File, "Open an empty, untitled document"),
+    ("file.open", "Open File…", CommandCategory::File, "Open a document from disk"),
+    ("file.open_folder", "Open Folder…", CommandCategory::File, "Open a folder as the project"),
+    ("file.save", "Save", CommandCategory::File, "Save the active document"),
+    ("file.save_as", "Save As…", CommandCategory::File, "Save the active document under a new name"),
+    ("file.export", "Export PDF", CommandCategory::File, "Compile the project and write a PDF"),
+    ("edit.go_to_line", "Go to Line…", CommandCategory::Edit, "Move the cursor to a line of the active document"),
+    ("view.command_palette", "Command Palette", CommandCategory::View, "Search and run any command"),
+    ("view.toggle_sidebar", "Toggle Sidebar", CommandCategory::View, "Show or hide the sidebar"),
+    ("view.toggle_preview", "Toggle Preview", CommandCategory::View, "Show or hide the preview"),
+    ("view.toggle_console", "Toggle Console", CommandCategory::View, "Show or hide the console"),
+    ("view.zoom_in", "Zoom In", CommandCategory::View, "Enlarge the interface"),
+    ("view.zoom_out", "Zoom Out", CommandCategory::View, "Shrink the interface"),
+    ("view.reset_zoom", "Reset Zoom", CommandCategory::View, "Restore the interface to its normal size"),
+    ("preview.reveal", "Reveal in Preview", CommandCategory::Preview, "Show the cursor's line in the preview"),
+    ("preview.find", "Find in Preview", CommandCategory::Preview, "Search the text of the preview"),
+    ("preview.find_next", "Find Next", CommandCategory::Preview, "Go to the next search match"),
+    ("preview.find_previous", "Find Previous", CommandCategory::Preview, "Go to the previous search match"),
+    ("preview.copy", "Copy Selection", CommandCategory::Preview, "Copy the text selected in the preview"),
+    (
+        "document.use_lf_line_endings",
+        "Use LF Line Endings",
+        CommandCategory::Document,
+        "Convert the active document to LF line endings",
+    ),
+    (
+        "document.use_crlf_line_endings",
+        "Use CRLF Line Endings",
+        CommandCategory::Document,
+        "Convert the active document to CRLF line endings",
+    ),
+    ("help.documentation", "Typst Documentation", CommandCategory::Help, "Open the Typst documentation"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(query: &str, candidate: &str) -> i64 {
+        fuzzy_match(query, candidate).unwrap().0
+    }
+
+    #[test]
+    fn matches_characters_in_order_ignoring_case() {
+        assert_eq!(fuzzy_match("SAVE", "File: Save"), fuzzy_match("save", "File: Save"));
+        assert_eq!(fuzzy_match("fs", "File: Save").unwrap().1, vec![0, 6]);
+        assert_eq!(fuzzy_match("ba", "ab"), None);
+        assert_eq!(fuzzy_match("xyz", "File: Save"), None);
+        assert_eq!(fuzzy_match(" ", "File: Save"), Some((0, Vec::new())));
+    }
+
+    #[test]
+    fn word_starts_score_higher() {
+        assert!(score("ts", "Toggle Sidebar") > score("ts", "Toggle Console"));
+        // Both camel case humps and words after punctuation start words
+        assert!(score("gl", "goToLine") > score("gl", "gallery"));
+        assert!(score("s", "File: Save") > score("s", "View: Toggle Console"));
+    }
+
+    #[test]
+    fn contiguous_matches_score_higher() {
+        assert!(score("abc", "abc xyz") > score("abc", "axbxc"));
+        assert!(score("save", "Save") > score("save", "Sxaxvxe"));
+    }
+
+    #[test]
+    fn prefers_word_starts_over_earlier_positions() {
+        assert_eq!(fuzzy_match("as", "Save As").unwrap().1, vec![5, 6]);
+        assert_eq!(fuzzy_match("sv", "Preview: Save View").unwrap().1, vec![9, 14]);
+    }
+
+    #[test]
+    fn search_orders_by_score_then_registration() {
+        let registry = CommandRegistry::with_builtins();
+        let titles = |query: &str| -> Vec<String> {
+            registry.search(query).iter().map(|found| found.command.title.to_string()).collect()
+        };
+        assert_eq!(titles("toggle sidebar")[0], "Toggle Sidebar");
+        assert_eq!(titles("zi")[0], "Zoom In");
+        let all = titles("");
+        assert_eq!(all.len(), BUILTIN_COMMANDS.len());
+        assert_eq!(all[0], "New File");
+    }
+}
@@ -1,10 +1,31 @@
+use crate::appearance::SystemAppearance;
+use crate::document::write_atomic;
+use crate::project::ProjectConfig;
 use anyhow::Result;
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
+use migration::update_document;
+use validation::{find_key_line, line_of_offset, toml_error_key};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+mod migration;
+mod service;
+mod validation;
+
+pub use migration::{migrate, CONFIG_VERSION};
+pub use service::{ConfigEvent, ConfigService, ConfigWatcher};
+pub use validation::{
+    ConfigLocation, AUTO_THEME, BUILTIN_THEMES, FONT_SIZE_RANGE, THEME_EXTENSIONS, UI_SCALE_RANGE,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
+    /// Format version, upgraded automatically when older
+    #[serde(default = "default_version")]
+    pub version: u32,
+
     #[serde(default)]
     pub editor: EditorConfig,
 
@@ -20,6 +41,8 @@ pub struct Config {
     #[serde(default)]
     pub bidi: BidiConfig,
 
+    /// Keys of commands as `command = "keys [when context]"`, such as
+    /// `"preview.find" = "ctrl-f when PreviewPane"`; an empty value unbinds
     #[serde(default)]
     pub keybindings: HashMap<String, String>,
 }
@@ -27,6 +50,7 @@ pub struct Config {
 impl Default for Config {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             editor: EditorConfig::default(),
             appearance: AppearanceConfig::default(),
             lsp: LspConfig::default(),
@@ -37,18 +61,20 @@ impl Default for Config {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct EditorConfig {
     #[serde(default = "default_font_family")]
     pub font_family: String,
 
     #[serde(default = "default_font_size")]
+    #[schemars(range(min = 6, max = 72))]
     pub font_size: u32,
 
     #[serde(default = "default_line_height")]
     pub line_height: f32,
 
     #[serde(default = "default_tab_size")]
+    #[schemars(range(min = 1))]
     pub tab_size: u32,
 
     #[serde(default = "default_true")]
@@ -70,7 +96,11 @@ pub struct EditorConfig {
     pub cursor_blink: bool,
 
     #[serde(default)]
-    pub auto_save: bool,
+    pub auto_save: AutoSave,
+
+    /// Idle time in milliseconds before `auto_save = "after_delay"` saves
+    #[serde(default = "default_auto_save_delay")]
+    pub auto_save_delay: u32,
 
     #[serde(default = "default_true")]
     pub auto_closing_brackets: bool,
@@ -92,14 +122,51 @@ impl Default for EditorConfig {
             minimap: true,
             cursor_style: default_cursor_style(),
             cursor_blink: true,
-            auto_save: false,
+            auto_save: AutoSave::default(),
+            auto_save_delay: default_auto_save_delay(),
             auto_closing_brackets: true,
             auto_closing_quotes: true,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// When edited documents are written to disk without an explicit save
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoSave {
+    #[default]
+    Off,
+    /// After `auto_save_delay` milliseconds without edits
+    AfterDelay,
+    /// When the editor loses focus, including when the window does
+    OnFocusChange,
+    /// When the window loses focus
+    OnWindowChange,
+}
+
+/// What prompted an auto-save check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoSaveTrigger {
+    Idle,
+    FocusChange,
+    WindowBlur,
+}
+
+impl AutoSave {
+    pub fn saves_on(&self, trigger: AutoSaveTrigger) -> bool {
+        matches!(
+            (self, trigger),
+            (AutoSave::AfterDelay, AutoSaveTrigger::Idle)
+                | (
+                    AutoSave::OnFocusChange,
+                    AutoSaveTrigger::FocusChange | AutoSaveTrigger::WindowBlur
+                )
+                | (AutoSave::OnWindowChange, AutoSaveTrigger::WindowBlur)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum CursorStyle {
     Block,
@@ -107,36 +174,66 @@ pub enum CursorStyle {
     Underline,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct AppearanceConfig {
+    /// Name of a theme, or `auto` to follow the desktop's light or dark preference
     #[serde(default = "default_theme")]
     pub theme: String,
 
+    /// Theme of `auto` when the desktop prefers light
+    #[serde(default = "default_light_theme")]
+    pub light_theme: String,
+
+    /// Theme of `auto` when the desktop prefers dark, or states no preference
+    #[serde(default = "default_theme")]
+    pub dark_theme: String,
+
     #[serde(default = "default_ui_scale")]
+    #[schemars(range(min = 0.5, max = 3.0))]
     pub ui_scale: f32,
 
     #[serde(default = "default_sidebar_position")]
     pub sidebar_position: SidebarPosition,
 }
 
+impl AppearanceConfig {
+    /// Whether the theme follows the desktop's light or dark preference
+    pub fn follows_system(&self) -> bool {
+        self.theme.eq_ignore_ascii_case(AUTO_THEME)
+    }
+
+    /// Name of the theme to use given the desktop's preference
+    pub fn theme_for(&self, appearance: Option<SystemAppearance>) -> &str {
+        if !self.follows_system() {
+            return &self.theme;
+        }
+        match appearance {
+            Some(SystemAppearance::Light) => &self.light_theme,
+            Some(SystemAppearance::Dark) | None => &self.dark_theme,
+        }
+    }
+}
+
 impl Default for AppearanceConfig {
     fn default() -> Self {
         Self {
             theme: default_theme(),
+            light_theme: default_light_theme(),
+            dark_theme: default_theme(),
             ui_scale: default_ui_scale(),
             sidebar_position: default_sidebar_position(),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum SidebarPosition {
     Left,
     Right,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct LspConfig {
     #[serde(default = "default_true")]
     pub enabled: bool,
@@ -162,7 +259,7 @@ impl Default for LspConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CompilerConfig {
     #[serde(default = "default_true")]
     pub auto_compile_on_save: bool,
@@ -188,7 +285,7 @@ impl Default for CompilerConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct BidiConfig {
     #[serde(default = "default_true")]
     pub enabled: bool,
@@ -211,6 +308,9 @@ impl Default for BidiConfig {
 }
 
 // Default value functions
+fn default_version() -> u32 {
+    CONFIG_VERSION
+}
 fn default_font_family() -> String {
     "Fira Code".to_string()
 }
@@ -229,6 +329,9 @@ fn default_cursor_style() -> CursorStyle {
 fn default_theme() -> String {
     "dark".to_string()
 }
+fn default_light_theme() -> String {
+    "light".to_string()
+}
 fn default_ui_scale() -> f32 {
     1.0
 }
@@ -244,6 +347,9 @@ fn default_hover_delay() -> u32 {
 fn default_completion_triggers() -> Vec<String> {
     vec!["#".to_string(), ".".to_string(), ":".to_string()]
 }
+fn default_auto_save_delay() -> u32 {
+    1000
+}
 fn default_compilation_delay() -> u32 {
     500
 }
@@ -252,14 +358,42 @@ fn default_true() -> bool {
 }
 
 impl Config {
+    /// Read a TOML or JSON file, upgrading it from older format versions
     pub fn load_from_file(path: &Path) -> Result<Self> {
         let contents = std::fs::read_to_string(path)?;
+        let mut table = parse_table(path, &contents)?;
+        migrate(&mut table)?;
+        Ok(toml::Value::Table(table).try_into()?)
+    }
 
-        if path.extension().and_then(|s| s.to_str()) == Some("toml") {
-            toml::from_str(&contents).map_err(Into::into)
-        } else {
-            serde_json::from_str(&contents).map_err(Into::into)
+    /// Rewrite a file in the current format version, returning the upgrade
+    /// steps applied
+    ///
+    /// A file no step changes is left alone. TOML files keep their comments
+    /// and layout; only the migrated settings and `version` are rewritten.
+    pub fn migrate_file(path: &Path) -> Result<Vec<&'static str>> {
+        let contents = std::fs::read_to_string(path)?;
+        let original = parse_table(path, &contents)?;
+        let mut table = original.clone();
+        let applied = migrate(&mut table)?;
+        if applied.is_empty() {
+            return Ok(applied);
         }
+
+        let contents = if path.extension().and_then(|s| s.to_str()) == Some("json") {
+            serde_json::to_string_pretty(&table)?
+        } else {
+            let mut document: toml_edit::DocumentMut = contents.parse()?;
+            update_document(document.as_table_mut(), &original, &table);
+            document.to_string()
+        };
+        write_atomic(path, contents.as_bytes())?;
+        Ok(applied)
+    }
+
+    /// JSON Schema of the configuration file, for completion in other editors
+    pub fn json_schema() -> RootSchema {
+        schemars::schema_for!(Config)
     }
 
     pub fn save_to_file(&self, path: &Path) -> Result<()> {
@@ -278,22 +412,648 @@ impl Config {
             .map(|dirs| dirs.config_dir().join("config.toml"))
     }
 
+    /// Directory holding the user's theme files
+    pub fn themes_dir() -> Option<PathBuf> {
+        directories::ProjectDirs::from("com", "typst", "typst-studio")
+            .map(|dirs| dirs.config_dir().join("themes"))
+    }
+
+    /// Theme files in [`Config::themes_dir`], sorted; each is named after its file stem
+    pub fn theme_files() -> Vec<PathBuf> {
+        let Some(entries) = Self::themes_dir().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+            return Vec::new();
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let extension = path.extension().and_then(|s| s.to_str());
+                path.is_file() && extension.is_some_and(|extension| THEME_EXTENSIONS.contains(&extension))
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Global configuration merged over the defaults; errors are dropped, see
+    /// [`Config::load_checked`] to report them
     pub fn load() -> Self {
-        Self::global_config_path()
-            .and_then(|path| Self::load_from_file(&path).ok())
-            .unwrap_or_default()
+        Self::load_checked(None).0
+    }
+
+    /// Configuration of a project: the project file merged over the global one
+    pub fn load_for_project(root: &Path) -> Result<Self> {
+        LayeredConfig::load(Some(root))?.resolve()
+    }
+
+    /// Global and, given a root, project configuration together with every
+    /// problem found; unreadable files and invalid settings are replaced by
+    /// their defaults
+    pub fn load_checked(project_root: Option<&Path>) -> (Self, Vec<ConfigError>) {
+        let (layers, mut errors) = LayeredConfig::load_checked(project_root);
+        let (config, invalid) = layers.resolve_checked();
+        errors.extend(invalid);
+        (config, errors)
     }
 }
 
-#[derive(Debug, Clone, thiserror::Error)]
+/// A group of settings that differs between two configurations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConfigChange {
+    Theme,
+    /// Font family, size or line height
+    Font,
+    UiScale,
+    SidebarPosition,
+    /// Any other editor setting
+    Editor,
+    AutoSave,
+    /// The language server must be restarted
+    LspServer,
+    /// Any other language server setting
+    Lsp,
+    Compiler,
+    Bidi,
+    Keybindings,
+}
+
+impl Config {
+    /// What changed going from this configuration to `new`
+    pub fn diff(&self, new: &Config) -> Vec<ConfigChange> {
+        let (old_editor, new_editor) = (&self.editor, &new.editor);
+        let (old_lsp, new_lsp) = (&self.lsp, &new.lsp);
+        let font_changed = old_editor.font_family != new_editor.font_family
+            || old_editor.font_size != new_editor.font_size
+            || old_editor.line_height != new_editor.line_height;
+        let auto_save_changed = old_editor.auto_save != new_editor.auto_save
+            || old_editor.auto_save_delay != new_editor.auto_save_delay;
+        // Compare what is left once the settings reported on their own are equal
+        let other_editor_changed = {
+            let mut old_editor = old_editor.clone();
+            old_editor.font_family = new_editor.font_family.clone();
+            old_editor.font_size = new_editor.font_size;
+            old_editor.line_height = new_editor.line_height;
+            old_editor.auto_save = new_editor.auto_save;
+            old_editor.auto_save_delay = new_editor.auto_save_delay;
+            old_editor != *new_editor
+        };
+        let theme_changed = self.appearance.theme != new.appearance.theme
+            || self.appearance.light_theme != new.appearance.light_theme
+            || self.appearance.dark_theme != new.appearance.dark_theme;
+        let server_changed = old_lsp.enabled != new_lsp.enabled || old_lsp.server_path != new_lsp.server_path;
+        let other_lsp_changed = old_lsp.hover_delay != new_lsp.hover_delay
+            || old_lsp.completion_triggers != new_lsp.completion_triggers;
+
+        [
+            (theme_changed, ConfigChange::Theme),
+            (font_changed, ConfigChange::Font),
+            (self.appearance.ui_scale != new.appearance.ui_scale, ConfigChange::UiScale),
+            (
+                self.appearance.sidebar_position != new.appearance.sidebar_position,
+                ConfigChange::SidebarPosition,
+            ),
+            (other_editor_changed, ConfigChange::Editor),
+            (auto_save_changed, ConfigChange::AutoSave),
+            (server_changed, ConfigChange::LspServer),
+            (other_lsp_changed, ConfigChange::Lsp),
+            (self.compiler != new.compiler, ConfigChange::Compiler),
+            (self.bidi != new.bidi, ConfigChange::Bidi),
+            (self.keybindings != new.keybindings, ConfigChange::Keybindings),
+        ]
+        .into_iter()
+        .filter_map(|(changed, change)| changed.then_some(change))
+        .collect()
+    }
+}
+
+/// A source of configuration values, from lowest to highest precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ConfigLayer {
+    /// Built-in defaults of every setting
+    Default,
+    /// The user's configuration file, see [`Config::global_config_path`]
+    Global,
+    /// `typst-studio.toml` or `.typst-studio/config.toml` at the project root
+    Project,
+    /// Settings of the open workspace, kept in memory rather than in a file
+    Workspace,
+}
+
+/// Configuration files kept as separate layers and merged on demand
+///
+/// Layers are merged in the order defaults → global → project → workspace.
+/// Tables merge key by key, so a project that sets `editor.tab_size` keeps
+/// every other editor setting of the global file; any other value, arrays
+/// included, replaces the value of the layers below it.
+#[derive(Debug, Clone, Default)]
+pub struct LayeredConfig {
+    layers: BTreeMap<ConfigLayer, toml::Table>,
+    /// Files the layers were read from, to locate errors
+    sources: BTreeMap<ConfigLayer, LayerSource>,
+}
+
+#[derive(Debug, Clone)]
+struct LayerSource {
+    path: PathBuf,
+    contents: String,
+}
+
+impl LayeredConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the global file and, given a project root, the project file
+    pub fn load(project_root: Option<&Path>) -> Result<Self> {
+        let (layers, errors) = Self::load_checked(project_root);
+        match errors.into_iter().next() {
+            Some(error) => Err(error.into()),
+            None => Ok(layers),
+        }
+    }
+
+    /// Like [`LayeredConfig::load`], but files that fail to parse are reported
+    /// and left out
+    pub fn load_checked(project_root: Option<&Path>) -> (Self, Vec<ConfigError>) {
+        Self::load_checked_from(Config::global_config_path().as_deref(), project_root)
+    }
+
+    /// Like [`LayeredConfig::load_checked`], reading the global layer from
+    /// `global_path` instead of [`Config::global_config_path`]
+    pub fn load_checked_from(
+        global_path: Option<&Path>,
+        project_root: Option<&Path>,
+    ) -> (Self, Vec<ConfigError>) {
+        let files = [
+            (ConfigLayer::Global, global_path.filter(|path| path.is_file()).map(Path::to_path_buf)),
+            (ConfigLayer::Project, project_root.and_then(ProjectConfig::find)),
+        ];
+
+        let mut layers = Self::new();
+        let mut errors = Vec::new();
+        for (layer, path) in files {
+            if let Some(path) = path {
+                if let Err(error) = layers.read_layer(layer, &path) {
+                    errors.push(error);
+                }
+            }
+        }
+        (layers, errors)
+    }
+
+    /// Replace a layer with the contents of a TOML or JSON file
+    pub fn load_layer(&mut self, layer: ConfigLayer, path: &Path) -> Result<()> {
+        Ok(self.read_layer(layer, path)?)
+    }
+
+    fn read_layer(&mut self, layer: ConfigLayer, path: &Path) -> std::result::Result<(), ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|err| ConfigError::ParseError {
+            message: format!("cannot read {}: {}", path.display(), err),
+            location: None,
+        })?;
+        let mut table = parse_table(path, &contents)?;
+        migrate(&mut table).map_err(|error| match find_key_line(path, &contents, "version") {
+            Some(line) => error.with_location(ConfigLocation {
+                path: path.to_path_buf(),
+                line,
+            }),
+            None => error,
+        })?;
+        self.layers.insert(layer, table);
+        self.sources.insert(
+            layer,
+            LayerSource {
+                path: path.to_path_buf(),
+                contents,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn set_layer(&mut self, layer: ConfigLayer, table: toml::Table) {
+        self.layers.insert(layer, table);
+        self.sources.remove(&layer);
+    }
+
+    pub fn clear_layer(&mut self, layer: ConfigLayer) {
+        self.layers.remove(&layer);
+        self.sources.remove(&layer);
+    }
+
+    pub fn layer(&self, layer: ConfigLayer) -> Option<&toml::Table> {
+        self.layers.get(&layer)
+    }
+
+    /// All layers merged in precedence order
+    pub fn merged(&self) -> toml::Table {
+        merge_layers(&self.layers)
+    }
+
+    /// Merge the layers into a configuration, filling unset values with defaults
+    pub fn resolve(&self) -> Result<Config> {
+        Ok(toml::Value::Table(self.merged()).try_into()?)
+    }
+
+    /// Merge the layers into a configuration, reporting every invalid setting
+    ///
+    /// Settings that fail to parse or validate fall back to the value of the
+    /// next lower layer, or to their defaults, so one mistake does not discard
+    /// the rest of the configuration.
+    pub fn resolve_checked(&self) -> (Config, Vec<ConfigError>) {
+        let mut layers = self.layers.clone();
+        let mut errors = Vec::new();
+
+        loop {
+            let merged = merge_layers(&layers);
+            // Parsed from text, so errors point at the value they are about
+            let text = toml::to_string(&merged).unwrap_or_default();
+            let config = match toml::from_str::<Config>(&text) {
+                Ok(config) => config,
+                Err(err) => {
+                    let error = match toml_error_key(&err, &merged, &text) {
+                        Some(key) => ConfigError::invalid(&key, err.message()),
+                        None => ConfigError::ParseError {
+                            message: err.message().to_string(),
+                            location: None,
+                        },
+                    };
+                    if self.fall_back(&mut layers, error, &mut errors) {
+                        continue;
+                    }
+                    return (Config::default(), errors);
+                }
+            };
+
+            // A default that does not fit this machine, such as a language server
+            // that is not installed, is not a mistake in the user's files
+            let mut fell_back = false;
+            let mut unkeyed = Vec::new();
+            for error in config.validate() {
+                match error.key() {
+                    Some(key) if get_key(&merged, key).is_none() => {}
+                    Some(_) => fell_back |= self.fall_back(&mut layers, error, &mut errors),
+                    None => unkeyed.push(error),
+                }
+            }
+            if !fell_back {
+                errors.extend(unkeyed);
+                return (config, errors);
+            }
+        }
+    }
+
+    /// Report an error and remove its setting from the highest layer setting
+    /// it; false if no layer sets it
+    fn fall_back(
+        &self,
+        layers: &mut BTreeMap<ConfigLayer, toml::Table>,
+        error: ConfigError,
+        errors: &mut Vec<ConfigError>,
+    ) -> bool {
+        let key = error.key().map(str::to_string);
+        errors.push(self.locate(layers, error));
+        key.is_some_and(|key| layers.values_mut().rev().any(|table| remove_key(table, &key)))
+    }
+
+    /// Point an error at the line of the highest file layer that sets its key
+    fn locate(&self, layers: &BTreeMap<ConfigLayer, toml::Table>, error: ConfigError) -> ConfigError {
+        let Some(key) = error.key() else {
+            return error;
+        };
+        let location = self
+            .sources
+            .iter()
+            .rev()
+            .filter(|(layer, _)| layers.get(layer).is_some_and(|table| get_key(table, key).is_some()))
+            .find_map(|(_, source)| {
+                find_key_line(&source.path, &source.contents, key).map(|line| ConfigLocation {
+                    path: source.path.clone(),
+                    line,
+                })
+            });
+        match location {
+            Some(location) => error.with_location(location),
+            None => error,
+        }
+    }
+
+    /// The `[project]` section of the merged layers
+    pub fn project(&self) -> Result<ProjectConfig> {
+        match self.merged().remove("project") {
+            Some(section) => Ok(section.try_into()?),
+            None => Ok(ProjectConfig::default()),
+        }
+    }
+}
+
+/// Parse the contents of a TOML or JSON configuration file as a table
+fn parse_table(path: &Path, contents: &str) -> std::result::Result<toml::Table, ConfigError> {
+    if path.extension().and_then(|s| s.to_str()) == Some("json") {
+        let value: serde_json::Value = serde_json::from_str(contents).map_err(|err| ConfigError::ParseError {
+            message: err.to_string(),
+            location: Some(ConfigLocation {
+                path: path.to_path_buf(),
+                line: err.line(),
+            }),
+        })?;
+        toml::Table::deserialize(value).map_err(|err| ConfigError::ParseError {
+            message: err.to_string(),
+            location: None,
+        })
+    } else {
+        toml::from_str(contents).map_err(|err| ConfigError::ParseError {
+            message: err.message().to_string(),
+            location: err.span().map(|span| ConfigLocation {
+                path: path.to_path_buf(),
+                line: line_of_offset(contents, span.start),
+            }),
+        })
+    }
+}
+
+fn get_key<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let mut parts = key.split('.');
+    let mut value = table.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_table()?.get(part)?;
+    }
+    Some(value)
+}
+
+/// Remove a dotted key, returning whether it was present
+fn remove_key(table: &mut toml::Table, key: &str) -> bool {
+    match key.split_once('.') {
+        Some((head, rest)) => match table.get_mut(head) {
+            Some(toml::Value::Table(nested)) => remove_key(nested, rest),
+            _ => false,
+        },
+        None => table.remove(key).is_some(),
+    }
+}
+
+/// Recursively merge `overlay` into `base`, with `overlay` taking precedence
+/// Layers merged in precedence order
+fn merge_layers(layers: &BTreeMap<ConfigLayer, toml::Table>) -> toml::Table {
+    let mut merged = toml::Table::new();
+    for table in layers.values() {
+        merge_tables(&mut merged, table);
+    }
+    merged
+}
+
+fn merge_tables(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge_tables(base, overlay),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
 pub enum ConfigError {
-    #[error("Invalid value for {key}: {message}")]
-    InvalidValue { key: String, message: String },
+    #[error("{}Invalid value for {key}: {message}", at(.location))]
+    InvalidValue {
+        key: String,
+        message: String,
+        location: Option<ConfigLocation>,
+    },
 
-    #[error("Missing required key: {key}")]
-    MissingRequired { key: String },
+    #[error("{}Missing required key: {key}", at(.location))]
+    MissingRequired {
+        key: String,
+        location: Option<ConfigLocation>,
+    },
 
-    #[error("Parse error: {message}")]
-    ParseError { message: String },
+    #[error("{}Parse error: {message}", at(.location))]
+    ParseError {
+        message: String,
+        location: Option<ConfigLocation>,
+    },
 }
 
+fn at(location: &Option<ConfigLocation>) -> String {
+    location
+        .as_ref()
+        .map(|location| format!("{}: ", location))
+        .unwrap_or_default()
+}
+
+impl ConfigError {
+    pub(crate) fn invalid(key: &str, message: impl Into<String>) -> Self {
+        ConfigError::InvalidValue {
+            key: key.to_string(),
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    pub(crate) fn missing(key: &str) -> Self {
+        ConfigError::MissingRequired {
+            key: key.to_string(),
+            location: None,
+        }
+    }
+
+    /// Dotted path of the offending setting, such as `editor.font_size`
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            ConfigError::InvalidValue { key, .. } | ConfigError::MissingRequired { key, .. } => Some(key),
+            ConfigError::ParseError { .. } => None,
+        }
+    }
+
+    pub fn location(&self) -> Option<&ConfigLocation> {
+        match self {
+            ConfigError::InvalidValue { location, .. }
+            | ConfigError::MissingRequired { location, .. }
+            | ConfigError::ParseError { location, .. } => location.as_ref(),
+        }
+    }
+
+    fn with_location(mut self, at: ConfigLocation) -> Self {
+        match &mut self {
+            ConfigError::InvalidValue { location, .. }
+            | ConfigError::MissingRequired { location, .. }
+            | ConfigError::ParseError { location, .. } => *location = Some(at),
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(toml: &str) -> toml::Table {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn no_layers_resolve_to_defaults() {
+        assert_eq!(LayeredConfig::new().resolve().unwrap(), Config::default());
+    }
+
+    #[test]
+    fn higher_layers_take_precedence() {
+        let mut layers = LayeredConfig::new();
+        // Inserted out of order, merged by precedence
+        layers.set_layer(ConfigLayer::Project, table("[editor]\ntab_size = 2"));
+        layers.set_layer(ConfigLayer::Default, table("[editor]\ntab_size = 3\nfont_size = 12"));
+        layers.set_layer(ConfigLayer::Global, table("[editor]\ntab_size = 8\nfont_size = 16"));
+
+        let config = layers.resolve().unwrap();
+        assert_eq!(config.editor.tab_size, 2);
+        assert_eq!(config.editor.font_size, 16);
+
+        layers.clear_layer(ConfigLayer::Project);
+        assert_eq!(layers.resolve().unwrap().editor.tab_size, 8);
+        layers.clear_layer(ConfigLayer::Global);
+        let config = layers.resolve().unwrap();
+        assert_eq!((config.editor.tab_size, config.editor.font_size), (3, 12));
+    }
+
+    #[test]
+    fn tables_merge_key_by_key_and_arrays_replace() {
+        let mut layers = LayeredConfig::new();
+        layers.set_layer(
+            ConfigLayer::Global,
+            table("[editor]\nfont_size = 18\n[lsp]\ncompletion_triggers = [\"#\", \".\"]"),
+        );
+        layers.set_layer(ConfigLayer::Project, table("[lsp]\ncompletion_triggers = [\"@\"]"));
+
+        let config = layers.resolve().unwrap();
+        assert_eq!(config.editor.font_size, 18);
+        assert_eq!(config.lsp.completion_triggers, vec!["@".to_string()]);
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_lower_layers() {
+        let mut layers = LayeredConfig::new();
+        layers.set_layer(ConfigLayer::Global, table("[editor]\ntab_size = 6"));
+        layers.set_layer(ConfigLayer::Project, table("[editor]\ntab_size = \"wide\"\nfont_size = 20"));
+
+        let (config, errors) = layers.resolve_checked();
+        assert_eq!(config.editor.tab_size, 6);
+        assert_eq!(config.editor.font_size, 20);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].key(), Some("editor.tab_size"));
+
+        // Every invalid layer is reported until a valid value or the default
+        layers.set_layer(ConfigLayer::Workspace, table("[editor]\ntab_size = -1"));
+        layers.set_layer(ConfigLayer::Global, table("[editor]\ntab_size = []"));
+        let (config, errors) = layers.resolve_checked();
+        assert_eq!(config.editor.tab_size, Config::default().editor.tab_size);
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().all(|error| error.key() == Some("editor.tab_size")));
+    }
+
+    #[test]
+    fn workspace_layer_overrides_the_project() {
+        let mut layers = LayeredConfig::new();
+        layers.set_layer(ConfigLayer::Project, table("[editor]\ntab_size = 2\nfont_size = 20"));
+        layers.set_layer(ConfigLayer::Workspace, table("[editor]\ntab_size = 4"));
+
+        let config = layers.resolve().unwrap();
+        assert_eq!((config.editor.tab_size, config.editor.font_size), (4, 20));
+    }
+
+    #[test]
+    fn diff_reports_each_changed_group() {
+        let old = Config::default();
+        assert!(old.diff(&old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.appearance.theme = "light".to_string();
+        new.editor.font_size += 1;
+        new.editor.tab_size += 1;
+        new.lsp.server_path = "/opt/tinymist".to_string();
+        new.lsp.hover_delay += 100;
+        let changes = old.diff(&new);
+        assert_eq!(changes.len(), 5, "{:?}", changes);
+        for change in [
+            ConfigChange::Theme,
+            ConfigChange::Font,
+            ConfigChange::Editor,
+            ConfigChange::LspServer,
+            ConfigChange::Lsp,
+        ] {
+            assert!(changes.contains(&change), "{:?} missing from {:?}", change, changes);
+        }
+
+        // Font and auto-save settings are not reported as other editor settings
+        let mut new = old.clone();
+        new.editor.line_height += 0.5;
+        new.editor.auto_save_delay += 1;
+        assert_eq!(old.diff(&new), vec![ConfigChange::Font, ConfigChange::AutoSave]);
+    }
+
+    #[test]
+    fn service_reload_reports_changes_and_errors() {
+        let root = std::env::temp_dir().join(format!("typst-studio-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let global = root.join("config.toml");
+        let config = std::sync::Arc::new(parking_lot::RwLock::new(Config::default()));
+        let service = ConfigService::with_global_path(config.clone(), None, Some(global.clone()));
+        let events = service.subscribe();
+
+        // Nothing changes without a file
+        assert!(service.reload().is_empty());
+        assert!(events.try_recv().is_err());
+
+        std::fs::write(&global, "[lsp]\nenabled = false\n").unwrap();
+        assert_eq!(service.reload(), vec![ConfigChange::LspServer]);
+        assert!(!config.read().lsp.enabled);
+        match events.try_recv() {
+            Ok(ConfigEvent::Changed { config, changes }) => {
+                assert!(!config.lsp.enabled);
+                assert_eq!(changes, vec![ConfigChange::LspServer]);
+            }
+            event => panic!("expected a change, got {:?}", event),
+        }
+        assert!(service.reload().is_empty());
+        assert!(events.try_recv().is_err());
+
+        // An invalid value is reported and the rest still applies
+        std::fs::write(&global, "[lsp]\nenabled = false\nhover_delay = \"soon\"\n").unwrap();
+        assert!(service.reload().is_empty());
+        match events.try_recv() {
+            Ok(ConfigEvent::Invalid(errors)) => assert_eq!(errors[0].key(), Some("lsp.hover_delay")),
+            event => panic!("expected errors, got {:?}", event),
+        }
+        assert!(!config.read().lsp.enabled);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn service_applies_the_project_opened_later() {
+        let root = std::env::temp_dir().join(format!("typst-studio-config-{}", uuid::Uuid::new_v4()));
+        let project = root.join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        // The user's own configuration must not leak into the test
+        let global = root.join("config.toml");
+        std::fs::write(&global, "[editor]\nfont_size = 18\n").unwrap();
+        std::fs::write(project.join("typst-studio.toml"), "[editor]\ntab_size = 7\n").unwrap();
+
+        let (layers, _errors) = LayeredConfig::load_checked_from(Some(&global), None);
+        let config = layers.resolve().unwrap();
+        let service = ConfigService::with_global_path(
+            std::sync::Arc::new(parking_lot::RwLock::new(config)),
+            None,
+            Some(global),
+        );
+        let events = service.subscribe();
+        let changes = service.set_project_root(Some(project)).unwrap();
+
+        assert!(changes.contains(&ConfigChange::Editor));
+        let config = service.config();
+        assert_eq!((config.read().editor.tab_size, config.read().editor.font_size), (7, 18));
+        assert!(matches!(events.try_recv(), Ok(ConfigEvent::Changed { .. })));
+
+        let changes = service.set_workspace_settings(table("[editor]\ntab_size = 3"));
+        assert_eq!(changes, vec![ConfigChange::Editor]);
+        assert_eq!(config.read().editor.tab_size, 3);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
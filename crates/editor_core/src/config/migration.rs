@@ -0,0 +1,158 @@
+use super::ConfigError;
+
+/// Version of the configuration format this build reads and writes
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Files without a `version` key predate versioning
+const UNVERSIONED: u32 = 1;
+
+/// One step upgrading the format from `from` to `from + 1`
+struct Migration {
+    from: u32,
+    description: &'static str,
+    /// Returns whether the table needed the change
+    apply: fn(&mut toml::Table) -> bool,
+}
+
+const MIGRATIONS: [Migration; 1] = [Migration {
+    from: 1,
+    description: "editor.auto_save is a mode instead of a bool",
+    apply: auto_save_mode,
+}];
+
+/// Upgrade a configuration table to [`CONFIG_VERSION`], returning a
+/// description of each step that changed it
+pub fn migrate(table: &mut toml::Table) -> Result<Vec<&'static str>, ConfigError> {
+    let version = match table.get("version") {
+        None => UNVERSIONED,
+        Some(toml::Value::Integer(version)) => u32::try_from(*version).map_err(|_| {
+            ConfigError::invalid("version", format!("{} is not a valid version", version))
+        })?,
+        Some(other) => {
+            return Err(ConfigError::invalid(
+                "version",
+                format!("expected an integer, found {}", other.type_str()),
+            ));
+        }
+    };
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::invalid(
+            "version",
+            format!(
+                "version {} was written by a newer release, this one reads up to {}",
+                version, CONFIG_VERSION
+            ),
+        ));
+    }
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|migration| migration.from >= version) {
+        if (migration.apply)(table) {
+            applied.push(migration.description);
+        }
+    }
+    table.insert("version".to_string(), toml::Value::Integer(CONFIG_VERSION.into()));
+    Ok(applied)
+}
+
+/// `auto_save = true` became `auto_save = "after_delay"`
+fn auto_save_mode(table: &mut toml::Table) -> bool {
+    let Some(toml::Value::Table(editor)) = table.get_mut("editor") else {
+        return false;
+    };
+    let Some(toml::Value::Boolean(enabled)) = editor.get("auto_save") else {
+        return false;
+    };
+    let mode = if *enabled { "after_delay" } else { "off" };
+    editor.insert("auto_save".to_string(), toml::Value::String(mode.to_string()));
+    true
+}
+
+/// Carry the differences between two tables into a TOML document, leaving
+/// the comments and layout of everything else as they were
+pub(super) fn update_document(document: &mut toml_edit::Table, old: &toml::Table, new: &toml::Table) {
+    for key in old.keys().filter(|key| !new.contains_key(*key)) {
+        document.remove(key);
+    }
+    for (key, value) in new {
+        if old.get(key) == Some(value) {
+            continue;
+        }
+        match (value, old.get(key), document.get_mut(key).and_then(|item| item.as_table_mut())) {
+            (toml::Value::Table(new_table), Some(toml::Value::Table(old_table)), Some(table)) => {
+                update_document(table, old_table, new_table);
+            }
+            _ => {
+                let mut item = toml_item(value);
+                // Keep a replaced value's trailing comment; the key keeps its own
+                if let (Some(new_value), Some(old_value)) =
+                    (item.as_value_mut(), document.get(key).and_then(|item| item.as_value()))
+                {
+                    *new_value.decor_mut() = old_value.decor().clone();
+                }
+                document[key.as_str()] = item;
+            }
+        }
+    }
+}
+
+fn toml_item(value: &toml::Value) -> toml_edit::Item {
+    match value {
+        toml::Value::Table(table) => {
+            let mut item = toml_edit::Table::new();
+            for (key, value) in table {
+                item.insert(key, toml_item(value));
+            }
+            toml_edit::Item::Table(item)
+        }
+        other => {
+            let value: toml_edit::Value = other.to_string().parse().expect("TOML values print as TOML");
+            toml_edit::Item::Value(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(toml: &str) -> toml::Table {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn reports_only_steps_that_change_something() {
+        let mut unrelated = table("[editor]\ntab_size = 2");
+        assert_eq!(migrate(&mut unrelated).unwrap(), Vec::<&str>::new());
+        assert_eq!(unrelated["version"].as_integer(), Some(CONFIG_VERSION.into()));
+
+        let mut old = table("[editor]\nauto_save = true");
+        assert_eq!(migrate(&mut old).unwrap(), vec![MIGRATIONS[0].description]);
+        assert_eq!(old["editor"]["auto_save"].as_str(), Some("after_delay"));
+    }
+
+    #[test]
+    fn rejects_newer_and_invalid_versions() {
+        assert!(migrate(&mut table("version = 99")).is_err());
+        assert!(migrate(&mut table("version = -1")).is_err());
+        assert!(migrate(&mut table("version = \"2\"")).is_err());
+        assert!(migrate(&mut table(&format!("version = {}", CONFIG_VERSION))).unwrap().is_empty());
+    }
+
+    #[test]
+    fn document_updates_keep_comments() {
+        let contents = "# My settings\n[editor]\n# Save often\nauto_save = false # for now\ntab_size = 2\n";
+        let old = table(contents);
+        let mut new = old.clone();
+        migrate(&mut new).unwrap();
+
+        let mut document: toml_edit::DocumentMut = contents.parse().unwrap();
+        update_document(document.as_table_mut(), &old, &new);
+        let updated = document.to_string();
+        assert!(updated.contains("# My settings"));
+        assert!(updated.contains("# Save often"));
+        assert!(updated.contains("auto_save = \"off\" # for now"));
+        assert!(updated.contains("tab_size = 2"));
+        assert_eq!(table(&updated), new);
+    }
+}
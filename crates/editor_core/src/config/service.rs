@@ -0,0 +1,213 @@
+use super::{Config, ConfigChange, ConfigError, ConfigLayer, LayeredConfig};
+use crate::project::PROJECT_CONFIG_FILES;
+use anyhow::Result;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+/// Quiet time after the last write before the files are re-read
+const RELOAD_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+pub enum ConfigEvent {
+    /// The configuration was reloaded and these settings differ
+    Changed {
+        config: Arc<Config>,
+        changes: Vec<ConfigChange>,
+    },
+    /// Problems found while reloading; the valid settings still apply
+    Invalid(Vec<ConfigError>),
+}
+
+/// Owner of the live configuration, re-reading it when its files change
+pub struct ConfigService {
+    config: Arc<RwLock<Config>>,
+    project_root: RwLock<Option<PathBuf>>,
+    /// The global configuration file, see [`Config::global_config_path`]
+    global_path: Option<PathBuf>,
+    /// Settings of the [`ConfigLayer::Workspace`] layer
+    workspace: RwLock<toml::Table>,
+    subscribers: Mutex<Vec<Sender<ConfigEvent>>>,
+    /// The watcher of [`ConfigService::watch`], to watch a project opened later
+    watcher: Mutex<Weak<Mutex<RecommendedWatcher>>>,
+}
+
+/// Keeps the configuration files watched until dropped
+pub struct ConfigWatcher {
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+impl ConfigService {
+    /// Manage `config`, which is updated in place on every reload
+    pub fn new(config: Arc<RwLock<Config>>, project_root: Option<PathBuf>) -> Arc<Self> {
+        Self::with_global_path(config, project_root, Config::global_config_path())
+    }
+
+    /// Like [`ConfigService::new`], reading the global configuration from
+    /// `global_path` instead of the user's configuration directory
+    pub fn with_global_path(
+        config: Arc<RwLock<Config>>,
+        project_root: Option<PathBuf>,
+        global_path: Option<PathBuf>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            project_root: RwLock::new(project_root),
+            global_path,
+            workspace: RwLock::new(toml::Table::new()),
+            subscribers: Mutex::new(Vec::new()),
+            watcher: Mutex::new(Weak::new()),
+        })
+    }
+
+    pub fn config(&self) -> Arc<RwLock<Config>> {
+        self.config.clone()
+    }
+
+    /// Receive an event for every reload that changes something or finds errors
+    pub fn subscribe(&self) -> Receiver<ConfigEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    pub fn project_root(&self) -> Option<PathBuf> {
+        self.project_root.read().clone()
+    }
+
+    /// Apply the configuration of the project at `root`, or only the global
+    /// one given `None`, watching the project's files if the service watches
+    pub fn set_project_root(&self, root: Option<PathBuf>) -> Result<Vec<ConfigChange>> {
+        let previous = watched_directories(&self.config_files());
+        *self.project_root.write() = root;
+        if let Some(watcher) = self.watcher.lock().upgrade() {
+            let mut watcher = watcher.lock();
+            let current = watched_directories(&self.config_files());
+            // Directories of the previous project, unless the new one shares them
+            for directory in previous.difference(&current) {
+                // Fails for directories removed since, which are not watched anymore
+                let _ = watcher.unwatch(directory);
+            }
+            for directory in &current {
+                watcher.watch(directory, RecursiveMode::NonRecursive)?;
+            }
+        }
+        Ok(self.reload())
+    }
+
+    /// Replace the settings of the workspace layer, which override the files
+    pub fn set_workspace_settings(&self, settings: toml::Table) -> Vec<ConfigChange> {
+        *self.workspace.write() = settings;
+        self.reload()
+    }
+
+    /// Global and project configuration files, whether or not they exist yet
+    pub fn config_files(&self) -> Vec<PathBuf> {
+        let project_root = self.project_root();
+        let project_files = project_root.iter().flat_map(|root| {
+            PROJECT_CONFIG_FILES.iter().map(move |name| root.join(name))
+        });
+        self.global_path
+            .clone()
+            .into_iter()
+            .chain(project_files)
+            .collect()
+    }
+
+    /// Re-read the configuration files, apply them and notify subscribers
+    pub fn reload(&self) -> Vec<ConfigChange> {
+        let project_root = self.project_root();
+        let (mut layers, mut errors) =
+            LayeredConfig::load_checked_from(self.global_path.as_deref(), project_root.as_deref());
+        let workspace = self.workspace.read().clone();
+        if !workspace.is_empty() {
+            layers.set_layer(ConfigLayer::Workspace, workspace);
+        }
+        let (new, invalid) = layers.resolve_checked();
+        errors.extend(invalid);
+        if !errors.is_empty() {
+            self.emit(ConfigEvent::Invalid(errors));
+        }
+
+        let changes = {
+            let mut config = self.config.write();
+            let changes = config.diff(&new);
+            *config = new.clone();
+            changes
+        };
+        if !changes.is_empty() {
+            self.emit(ConfigEvent::Changed {
+                config: Arc::new(new),
+                changes: changes.clone(),
+            });
+        }
+        changes
+    }
+
+    /// Reload whenever one of the configuration files is written
+    ///
+    /// Only directories that exist are watched, so a configuration file whose
+    /// directory is created later is picked up on the next start.
+    pub fn watch(self: &Arc<Self>) -> Result<ConfigWatcher> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let _ = tx.send(event);
+            }
+        })?;
+        for directory in watched_directories(&self.config_files()) {
+            watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+        }
+        let watcher = Arc::new(Mutex::new(watcher));
+        *self.watcher.lock() = Arc::downgrade(&watcher);
+
+        let service = self.clone();
+        std::thread::Builder::new()
+            .name("config-watcher".to_string())
+            .spawn(move || service.reload_on_change(rx))?;
+        Ok(ConfigWatcher { _watcher: watcher })
+    }
+
+    fn reload_on_change(&self, events: Receiver<Event>) {
+        // The project, and with it the files, may change while watching
+        let touches_config = |event: &Event| {
+            let files: HashSet<PathBuf> = self.config_files().into_iter().collect();
+            event.paths.iter().any(|path| files.contains(path))
+        };
+        // Ends when the watcher, and with it the event sender, is dropped
+        while let Ok(event) = events.recv() {
+            if event.kind.is_access() || !touches_config(&event) {
+                continue;
+            }
+            // Editors often write a file in several steps; wait for the last
+            loop {
+                match events.recv_timeout(RELOAD_DELAY) {
+                    Ok(_) => {}
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            self.reload();
+        }
+    }
+
+    fn emit(&self, event: ConfigEvent) {
+        self.subscribers
+            .lock()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+/// Existing directories holding `files`
+fn watched_directories(files: &[PathBuf]) -> HashSet<PathBuf> {
+    files
+        .iter()
+        .filter_map(|path| path.parent())
+        .filter(|directory| directory.is_dir())
+        .map(Path::to_path_buf)
+        .collect()
+}
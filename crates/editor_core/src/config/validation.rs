@@ -0,0 +1,401 @@
+use super::{Config, ConfigError};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+/// Themes that ship with the editor
+pub const BUILTIN_THEMES: [&str; 4] = [
+    "dark",
+    "light",
+    "high_contrast_dark",
+    "high_contrast_light",
+];
+
+/// `appearance.theme` following the desktop's light or dark preference
+pub const AUTO_THEME: &str = "auto";
+
+/// Formats of theme files
+pub const THEME_EXTENSIONS: [&str; 2] = ["toml", "json"];
+
+pub const FONT_SIZE_RANGE: RangeInclusive<u32> = 6..=72;
+pub const UI_SCALE_RANGE: RangeInclusive<f32> = 0.5..=3.0;
+
+/// A line of a configuration file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigLocation {
+    pub path: PathBuf,
+    /// One-based
+    pub line: usize,
+}
+
+impl fmt::Display for ConfigLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line)
+    }
+}
+
+impl Config {
+    /// Check values that parse but cannot be used, against the built-in themes
+    /// and those in [`Config::themes_dir`]
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let files = Config::theme_files();
+        let themes: Vec<&str> = BUILTIN_THEMES
+            .into_iter()
+            .chain(files.iter().filter_map(|path| path.file_stem()?.to_str()))
+            .collect();
+        self.validate_with_themes(&themes)
+    }
+
+    /// Every invalid setting, given the names of the available themes
+    pub fn validate_with_themes(&self, themes: &[&str]) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+
+        let font_size = self.editor.font_size;
+        if !FONT_SIZE_RANGE.contains(&font_size) {
+            errors.push(ConfigError::invalid(
+                "editor.font_size",
+                format!(
+                    "{} is outside {}..={}",
+                    font_size,
+                    FONT_SIZE_RANGE.start(),
+                    FONT_SIZE_RANGE.end()
+                ),
+            ));
+        }
+        if self.editor.tab_size == 0 {
+            errors.push(ConfigError::invalid("editor.tab_size", "must be greater than 0"));
+        }
+
+        // NaN is never contained, so it is rejected too
+        let ui_scale = self.appearance.ui_scale;
+        if !UI_SCALE_RANGE.contains(&ui_scale) {
+            errors.push(ConfigError::invalid(
+                "appearance.ui_scale",
+                format!(
+                    "{} is outside {}..={}",
+                    ui_scale,
+                    UI_SCALE_RANGE.start(),
+                    UI_SCALE_RANGE.end()
+                ),
+            ));
+        }
+
+        let appearance = &self.appearance;
+        let mut theme_settings = Vec::new();
+        if appearance.follows_system() {
+            // The light and dark themes are only used by `auto`
+            theme_settings.push(("appearance.light_theme", &appearance.light_theme));
+            theme_settings.push(("appearance.dark_theme", &appearance.dark_theme));
+        } else {
+            theme_settings.push(("appearance.theme", &appearance.theme));
+        }
+        for (key, theme) in theme_settings {
+            if theme.is_empty() {
+                errors.push(ConfigError::missing(key));
+            } else if !themes.iter().any(|known| known.eq_ignore_ascii_case(theme)) {
+                errors.push(ConfigError::invalid(
+                    key,
+                    format!(
+                        "unknown theme '{}', expected {} or one of {}",
+                        theme,
+                        AUTO_THEME,
+                        themes.join(", ")
+                    ),
+                ));
+            }
+        }
+
+        if self.lsp.enabled {
+            let server_path = &self.lsp.server_path;
+            if server_path.is_empty() {
+                errors.push(ConfigError::missing("lsp.server_path"));
+            } else if find_executable(server_path).is_none() {
+                errors.push(ConfigError::invalid(
+                    "lsp.server_path",
+                    format!("'{}' was not found", server_path),
+                ));
+            }
+        }
+
+        errors
+    }
+}
+
+/// Resolve a program name through `PATH`, or check a path to a program directly
+fn find_executable(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.is_absolute() || path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    let extensions: &[&str] = if cfg!(windows) { &["exe", "cmd", "bat"] } else { &[] };
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).find_map(|directory| {
+        let candidate = directory.join(program);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        extensions
+            .iter()
+            .map(|extension| candidate.with_extension(extension))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// The dotted key an error deserializing `text`, the serialization of `table`,
+/// refers to, if any
+///
+/// Serialized tables set every key on a line of its own, so the deepest key
+/// set on the line of the error's span is the one.
+pub(super) fn toml_error_key(error: &toml::de::Error, table: &toml::Table, text: &str) -> Option<String> {
+    let line = line_of_offset(text, error.span()?.start);
+    let mut keys = Vec::new();
+    collect_keys(table, &mut Vec::new(), &mut keys);
+    keys.into_iter()
+        .filter(|key| {
+            let key: Vec<&str> = key.iter().map(String::as_str).collect();
+            find_toml_key(text, &key) == Some(line)
+        })
+        .max_by_key(Vec::len)
+        .map(|key| key.join("."))
+}
+
+/// Every key of a table and its nested tables, as paths
+fn collect_keys(table: &toml::Table, prefix: &mut Vec<String>, keys: &mut Vec<Vec<String>>) {
+    for (name, value) in table {
+        prefix.push(name.clone());
+        keys.push(prefix.clone());
+        if let toml::Value::Table(table) = value {
+            collect_keys(table, prefix, keys);
+        }
+        prefix.pop();
+    }
+}
+
+/// One-based line of a byte offset
+pub(super) fn line_of_offset(contents: &str, offset: usize) -> usize {
+    let offset = offset.min(contents.len());
+    contents.as_bytes()[..offset].iter().filter(|&&byte| byte == b'\n').count() + 1
+}
+
+/// One-based line on which a dotted key is set in a TOML or JSON file
+pub(super) fn find_key_line(path: &Path, contents: &str, key: &str) -> Option<usize> {
+    let key: Vec<&str> = key.split('.').collect();
+    if path.extension().and_then(|s| s.to_str()) == Some("json") {
+        find_json_key(contents, &key)
+    } else {
+        find_toml_key(contents, &key)
+    }
+}
+
+fn find_toml_key(contents: &str, key: &[&str]) -> Option<usize> {
+    let mut table: Vec<String> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.trim_start_matches('[');
+            table = split_toml_key(header.split(']').next().unwrap_or_default());
+            if table.iter().eq(key.iter()) {
+                return Some(index + 1);
+            }
+            continue;
+        }
+
+        let Some((name, _)) = line.split_once('=') else {
+            continue;
+        };
+        let full: Vec<String> = table.iter().cloned().chain(split_toml_key(name)).collect();
+        // A prefix match is an inline table containing the key
+        if full.len() <= key.len() && full.iter().zip(key).all(|(part, expected)| part == expected) {
+            return Some(index + 1);
+        }
+    }
+    None
+}
+
+fn split_toml_key(key: &str) -> Vec<String> {
+    key.split('.')
+        .map(|part| part.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .collect()
+}
+
+fn find_json_key(contents: &str, key: &[&str]) -> Option<usize> {
+    // The key currently being read in each open object, `None` in arrays
+    let mut path: Vec<Option<String>> = Vec::new();
+    let mut last_string = None;
+    let mut line = 1;
+    let mut chars = contents.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '"' => {
+                let mut string = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => {
+                            chars.next();
+                        }
+                        c => string.push(c),
+                    }
+                }
+                last_string = Some(string);
+            }
+            ':' => {
+                if let Some(slot) = path.last_mut() {
+                    *slot = last_string.take();
+                    if path.iter().flatten().map(String::as_str).eq(key.iter().copied()) {
+                        return Some(line);
+                    }
+                }
+            }
+            '{' | '[' => path.push(None),
+            '}' | ']' => {
+                path.pop();
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Default settings without the language server, which may not be installed
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.lsp.enabled = false;
+        config
+    }
+
+    fn invalid_keys(config: &Config) -> Vec<&'static str> {
+        let keys = [
+            "editor.font_size",
+            "editor.tab_size",
+            "appearance.ui_scale",
+            "appearance.theme",
+            "appearance.light_theme",
+            "appearance.dark_theme",
+            "lsp.server_path",
+        ];
+        let errors = config.validate_with_themes(&BUILTIN_THEMES);
+        keys.into_iter().filter(|key| errors.iter().any(|error| error.key() == Some(*key))).collect()
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(config().validate_with_themes(&BUILTIN_THEMES).is_empty());
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        let mut config = config();
+        config.editor.font_size = 5;
+        config.editor.tab_size = 0;
+        config.appearance.ui_scale = f32::NAN;
+        assert_eq!(invalid_keys(&config), ["editor.font_size", "editor.tab_size", "appearance.ui_scale"]);
+
+        config.editor.font_size = *FONT_SIZE_RANGE.end();
+        config.editor.tab_size = 1;
+        config.appearance.ui_scale = *UI_SCALE_RANGE.start();
+        assert!(invalid_keys(&config).is_empty());
+    }
+
+    #[test]
+    fn checks_the_themes_in_use() {
+        let mut config = config();
+        config.appearance.theme = "Solarized".to_string();
+        assert_eq!(invalid_keys(&config), ["appearance.theme"]);
+        config.appearance.theme = "HIGH_CONTRAST_DARK".to_string();
+        assert!(invalid_keys(&config).is_empty());
+        assert!(config.validate_with_themes(&["dark", "Solarized"]).iter().any(|error| {
+            error.key() == Some("appearance.theme")
+        }));
+
+        // `auto` uses the light and dark themes instead
+        config.appearance.theme = AUTO_THEME.to_string();
+        config.appearance.light_theme = "Solarized".to_string();
+        config.appearance.dark_theme = String::new();
+        assert_eq!(invalid_keys(&config), ["appearance.light_theme", "appearance.dark_theme"]);
+        let errors = config.validate_with_themes(&["Solarized"]);
+        assert!(matches!(
+            errors.as_slice(),
+            [ConfigError::MissingRequired { key, .. }] if key == "appearance.dark_theme"
+        ));
+    }
+
+    #[test]
+    fn checks_the_language_server_only_when_enabled() {
+        let mut config = config();
+        config.lsp.server_path = "typst-studio-no-such-server".to_string();
+        assert!(invalid_keys(&config).is_empty());
+        config.lsp.enabled = true;
+        assert_eq!(invalid_keys(&config), ["lsp.server_path"]);
+        config.lsp.server_path = String::new();
+        assert_eq!(invalid_keys(&config), ["lsp.server_path"]);
+    }
+
+    #[test]
+    fn finds_toml_keys() {
+        let contents = "# editor.font_size = 1\nversion = 2\n\n[editor]\nfont_size = 14\n\"tab_size\" = 4\n\n\
+            [appearance]\nsidebar = { position = \"left\" }\n[lsp.hover]\ndelay = 3\n";
+        assert_eq!(find_toml_key(contents, &["version"]), Some(2));
+        assert_eq!(find_toml_key(contents, &["editor"]), Some(4));
+        assert_eq!(find_toml_key(contents, &["editor", "font_size"]), Some(5));
+        assert_eq!(find_toml_key(contents, &["editor", "tab_size"]), Some(6));
+        // Inside an inline table
+        assert_eq!(find_toml_key(contents, &["appearance", "sidebar", "position"]), Some(9));
+        assert_eq!(find_toml_key(contents, &["lsp", "hover", "delay"]), Some(11));
+        assert_eq!(find_toml_key(contents, &["editor", "line_height"]), None);
+        assert_eq!(find_toml_key(contents, &["font_size"]), None);
+    }
+
+    #[test]
+    fn finds_json_keys() {
+        let contents = r##"{
+  "version": 2,
+  "editor": {
+    "font_family": "a \"b\": c",
+    "font_size": 14
+  },
+  "lsp": { "completion_triggers": ["#", {"x": 1}] },
+  "tab_size": 4
+}
+"##;
+        assert_eq!(find_json_key(contents, &["version"]), Some(2));
+        assert_eq!(find_json_key(contents, &["editor"]), Some(3));
+        // Escaped quotes and colons inside strings are skipped
+        assert_eq!(find_json_key(contents, &["editor", "font_size"]), Some(5));
+        assert_eq!(find_json_key(contents, &["lsp", "completion_triggers"]), Some(7));
+        assert_eq!(find_json_key(contents, &["tab_size"]), Some(8));
+        assert_eq!(find_json_key(contents, &["editor", "tab_size"]), None);
+    }
+
+    #[test]
+    fn finds_the_key_of_deserialization_errors() {
+        let table: toml::Table = toml::from_str("[editor]\ntab_size = 4\nfont_size = \"big\"\n").unwrap();
+        let text = toml::to_string(&table).unwrap();
+        let error = toml::from_str::<Config>(&text).unwrap_err();
+        assert_eq!(toml_error_key(&error, &table, &text).as_deref(), Some("editor.font_size"));
+
+        let table: toml::Table = toml::from_str("editor = 3\n").unwrap();
+        let text = toml::to_string(&table).unwrap();
+        let error = toml::from_str::<Config>(&text).unwrap_err();
+        assert_eq!(toml_error_key(&error, &table, &text).as_deref(), Some("editor"));
+    }
+
+    #[test]
+    fn counts_lines_of_offsets() {
+        let contents = "a\nbc\n\nd";
+        assert_eq!(line_of_offset(contents, 0), 1);
+        assert_eq!(line_of_offset(contents, 2), 2);
+        assert_eq!(line_of_offset(contents, 5), 3);
+        assert_eq!(line_of_offset(contents, 100), 4);
+    }
+}
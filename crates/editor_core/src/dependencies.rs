@@ -0,0 +1,361 @@
+//! Which files each source of a project imports, includes or reads
+//!
+//! Paths are resolved from string literals in the syntax tree, so only
+//! dependencies known without evaluating the document are found.
+
+use crate::project::DependencyType;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use typst_syntax::ast;
+use typst_syntax::SyntaxNode;
+
+/// Functions whose first argument is a path to a file read at compile time
+const ASSET_FUNCTIONS: [&str; 10] = [
+    "image",
+    "read",
+    "json",
+    "csv",
+    "yaml",
+    "toml",
+    "xml",
+    "cbor",
+    "bibliography",
+    "plugin",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DependencyTarget {
+    File(PathBuf),
+    /// A package spec such as `@preview/cetz:0.3.0`
+    Package(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dependency {
+    pub target: DependencyTarget,
+    pub dependency_type: DependencyType,
+}
+
+/// Imports, includes and assets of every source file of a project
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    root: PathBuf,
+    dependencies: HashMap<PathBuf, Vec<Dependency>>,
+    dependents: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+impl DependencyGraph {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            dependencies: HashMap::new(),
+            dependents: HashMap::new(),
+        }
+    }
+
+    /// Parse every source and collect its dependencies; unreadable files are skipped
+    pub fn build(root: PathBuf, sources: &[PathBuf]) -> Self {
+        let mut graph = Self::new(root);
+        for path in sources {
+            if let Ok(text) = std::fs::read_to_string(path) {
+                graph.update_file(path, &text);
+            }
+        }
+        graph
+    }
+
+    /// Re-parse one source after it changed
+    pub fn update_file(&mut self, path: &Path, text: &str) {
+        self.remove_file(path);
+
+        let directory = path.parent().unwrap_or(&self.root).to_path_buf();
+        let mut dependencies = Vec::new();
+        collect(&typst_syntax::parse(text), &mut |spec, dependency_type| {
+            let target = if spec.starts_with('@') {
+                DependencyTarget::Package(spec.to_string())
+            } else if let Some(absolute) = spec.strip_prefix('/') {
+                DependencyTarget::File(normalize(&self.root.join(absolute)))
+            } else {
+                DependencyTarget::File(normalize(&directory.join(spec)))
+            };
+            let dependency_type = match target {
+                DependencyTarget::Package(_) => DependencyType::Package,
+                DependencyTarget::File(_) => dependency_type,
+            };
+            dependencies.push(Dependency { target, dependency_type });
+        });
+
+        for dependency in &dependencies {
+            if let DependencyTarget::File(target) = &dependency.target {
+                self.dependents.entry(target.clone()).or_default().insert(path.to_path_buf());
+            }
+        }
+        self.dependencies.insert(path.to_path_buf(), dependencies);
+    }
+
+    pub fn remove_file(&mut self, path: &Path) {
+        let Some(previous) = self.dependencies.remove(path) else {
+            return;
+        };
+        for dependency in previous {
+            if let DependencyTarget::File(target) = dependency.target {
+                if let Some(dependents) = self.dependents.get_mut(&target) {
+                    dependents.remove(path);
+                    if dependents.is_empty() {
+                        self.dependents.remove(&target);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Direct dependencies of a source
+    pub fn dependencies_of(&self, path: &Path) -> &[Dependency] {
+        self.dependencies.get(path).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Sources that directly import, include or read a file
+    pub fn dependents_of(&self, path: &Path) -> impl Iterator<Item = &PathBuf> {
+        self.dependents.get(path).into_iter().flatten()
+    }
+
+    pub fn is_referenced(&self, path: &Path) -> bool {
+        self.dependents.contains_key(path)
+    }
+
+    /// Every package used anywhere in the project
+    pub fn packages(&self) -> BTreeSet<&str> {
+        self.dependencies
+            .values()
+            .flatten()
+            .filter_map(|dependency| match &dependency.target {
+                DependencyTarget::Package(spec) => Some(spec.as_str()),
+                DependencyTarget::File(_) => None,
+            })
+            .collect()
+    }
+
+    /// Sources that no other source depends on, i.e. candidate main files
+    pub fn roots(&self) -> Vec<&PathBuf> {
+        let mut roots: Vec<&PathBuf> = self.dependencies
+            .keys()
+            .filter(|path| !self.is_referenced(path))
+            .collect();
+        roots.sort();
+        roots
+    }
+
+    /// Main files that need recompiling when a file changes
+    pub fn affected_main_files<'a>(&self, changed: &Path, main_files: &'a [PathBuf]) -> Vec<&'a PathBuf> {
+        let mut affected = HashSet::new();
+        let mut queue = VecDeque::from([changed.to_path_buf()]);
+        while let Some(path) = queue.pop_front() {
+            if affected.insert(path.clone()) {
+                queue.extend(self.dependents_of(&path).cloned());
+            }
+        }
+        main_files
+            .iter()
+            .filter(|main| affected.contains(*main))
+            .collect()
+    }
+
+    /// Every file reachable from the main files
+    pub fn reachable_from(&self, main_files: &[PathBuf]) -> HashSet<PathBuf> {
+        let mut reachable = HashSet::new();
+        let mut queue: VecDeque<PathBuf> = main_files.iter().cloned().collect();
+        while let Some(path) = queue.pop_front() {
+            if !reachable.insert(path.clone()) {
+                continue;
+            }
+            for dependency in self.dependencies_of(&path) {
+                if let DependencyTarget::File(target) = &dependency.target {
+                    queue.push_back(target.clone());
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Files that none of the main files use, directly or indirectly
+    pub fn unused_files<'a>(&self, files: &'a [PathBuf], main_files: &[PathBuf]) -> Vec<&'a PathBuf> {
+        let reachable = self.reachable_from(main_files);
+        files
+            .iter()
+            .filter(|path| !reachable.contains(*path))
+            .collect()
+    }
+}
+
+/// Report the string literal path of every import, include and asset read below a node
+fn collect(node: &SyntaxNode, report: &mut dyn FnMut(&str, DependencyType)) {
+    if let Some(import) = node.cast::<ast::ModuleImport>() {
+        if let ast::Expr::Str(source) = import.source() {
+            report(&source.get(), DependencyType::Import);
+        }
+    } else if let Some(include) = node.cast::<ast::ModuleInclude>() {
+        if let ast::Expr::Str(source) = include.source() {
+            report(&source.get(), DependencyType::Include);
+        }
+    } else if let Some(call) = node.cast::<ast::FuncCall>() {
+        let is_asset = matches!(
+            call.callee(),
+            ast::Expr::Ident(ident) if ASSET_FUNCTIONS.contains(&ident.get().as_str())
+        );
+        let first = call.args().items().find_map(|arg| match arg {
+            ast::Arg::Pos(expr) => Some(expr),
+            _ => None,
+        });
+        if let (true, Some(first)) = (is_asset, first) {
+            match first {
+                ast::Expr::Str(path) => report(&path.get(), DependencyType::Asset),
+                // `bibliography` also takes an array of paths
+                ast::Expr::Array(array) => {
+                    for item in array.items() {
+                        if let ast::ArrayItem::Pos(ast::Expr::Str(path)) = item {
+                            report(&path.get(), DependencyType::Asset);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for child in node.children() {
+        collect(child, report);
+    }
+}
+
+/// Resolve `.` and `..` components without touching the file system
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> PathBuf {
+        Path::new("/project").join(path)
+    }
+
+    fn graph(sources: &[(&str, &str)]) -> DependencyGraph {
+        let mut graph = DependencyGraph::new(path(""));
+        for (source, text) in sources {
+            graph.update_file(&path(source), text);
+        }
+        graph
+    }
+
+    fn targets(graph: &DependencyGraph, source: &str) -> Vec<(DependencyTarget, DependencyType)> {
+        graph
+            .dependencies_of(&path(source))
+            .iter()
+            .map(|dependency| (dependency.target.clone(), dependency.dependency_type))
+            .collect()
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(|name| path(name)).collect()
+    }
+
+    #[test]
+    fn collects_imports_includes_assets_and_packages() {
+        let graph = graph(&[(
+            "chapters/intro.typ",
+            "#import \"../lib.typ\": *\n\
+             #import \"@preview/cetz:0.3.0\"\n\
+             #include \"./part.typ\"\n\
+             #image(\"/figures/plot.svg\", width: 50%)\n\
+             #bibliography((\"a.bib\", \"b.bib\"))\n\
+             #let data = json(\"data.json\")\n\
+             #let name = \"ignored.typ\"\n\
+             #image(name)",
+        )]);
+        let file = |name: &str, kind| (DependencyTarget::File(path(name)), kind);
+        assert_eq!(targets(&graph, "chapters/intro.typ"), [
+            file("lib.typ", DependencyType::Import),
+            (DependencyTarget::Package("@preview/cetz:0.3.0".to_string()), DependencyType::Package),
+            file("chapters/part.typ", DependencyType::Include),
+            file("figures/plot.svg", DependencyType::Asset),
+            file("chapters/a.bib", DependencyType::Asset),
+            file("chapters/b.bib", DependencyType::Asset),
+            file("chapters/data.json", DependencyType::Asset),
+        ]);
+        assert_eq!(graph.packages().into_iter().collect::<Vec<_>>(), ["@preview/cetz:0.3.0"]);
+    }
+
+    #[test]
+    fn updates_and_removals_replace_dependents() {
+        let mut graph = graph(&[("main.typ", "#include \"a.typ\""), ("a.typ", "")]);
+        assert!(graph.is_referenced(&path("a.typ")));
+        assert_eq!(graph.roots(), [&path("main.typ")]);
+
+        graph.update_file(&path("main.typ"), "#include \"b.typ\"");
+        assert!(!graph.is_referenced(&path("a.typ")));
+        assert_eq!(graph.dependents_of(&path("b.typ")).collect::<Vec<_>>(), [&path("main.typ")]);
+        assert_eq!(graph.roots(), [&path("a.typ"), &path("main.typ")]);
+
+        graph.remove_file(&path("main.typ"));
+        assert!(!graph.is_referenced(&path("b.typ")));
+        assert!(graph.dependencies_of(&path("main.typ")).is_empty());
+    }
+
+    #[test]
+    fn changes_affect_every_main_file_using_them() {
+        let graph = graph(&[
+            ("thesis.typ", "#include \"chapter.typ\""),
+            ("slides.typ", "#import \"lib.typ\": *"),
+            ("chapter.typ", "#import \"lib.typ\": *\n#image(\"plot.png\")"),
+            ("lib.typ", ""),
+        ]);
+        let mains = paths(&["thesis.typ", "slides.typ"]);
+        let affected = |changed: &str| graph.affected_main_files(&path(changed), &mains);
+
+        assert_eq!(affected("lib.typ"), [&mains[0], &mains[1]]);
+        assert_eq!(affected("plot.png"), [&mains[0]]);
+        assert_eq!(affected("slides.typ"), [&mains[1]]);
+        assert!(affected("other.typ").is_empty());
+    }
+
+    #[test]
+    fn finds_files_no_main_file_uses() {
+        let graph = graph(&[
+            ("main.typ", "#include \"a.typ\""),
+            ("a.typ", "#image(\"used.png\")"),
+            ("draft.typ", "#include \"b.typ\""),
+            ("b.typ", ""),
+        ]);
+        let files = paths(&["main.typ", "a.typ", "draft.typ", "b.typ", "used.png", "unused.png"]);
+        let mains = paths(&["main.typ"]);
+        assert_eq!(graph.unused_files(&files, &mains), [&files[2], &files[3], &files[5]]);
+        assert_eq!(graph.reachable_from(&mains).len(), 3);
+    }
+
+    #[test]
+    fn cycles_terminate() {
+        let graph = graph(&[
+            ("main.typ", "#include \"a.typ\""),
+            ("a.typ", "#import \"b.typ\""),
+            ("b.typ", "#import \"a.typ\""),
+        ]);
+        let mains = paths(&["main.typ"]);
+        assert_eq!(graph.affected_main_files(&path("b.typ"), &mains), [&mains[0]]);
+        let reachable: HashSet<PathBuf> = paths(&["main.typ", "a.typ", "b.typ"]).into_iter().collect();
+        assert_eq!(graph.reachable_from(&mains), reachable);
+        assert!(graph.unused_files(&paths(&["a.typ", "b.typ"]), &mains).is_empty());
+        // Neither file of the cycle is a root, since each is referenced
+        assert_eq!(graph.roots(), [&path("main.typ")]);
+    }
+}
@@ -1,5 +1,7 @@
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -24,6 +26,16 @@ pub enum Language {
     PlainText,
 }
 
+impl Language {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("typ") => Self::Typst,
+            Some("md" | "markdown") => Self::Markdown,
+            _ => Self::PlainText,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Encoding {
     Utf8,
@@ -32,6 +44,89 @@ pub enum Encoding {
     Utf16Be,
 }
 
+impl Encoding {
+    /// Detect the encoding of raw file contents, returning it with the length of its BOM
+    ///
+    /// Files without a BOM are UTF-8 unless they look like UTF-16, i.e. every
+    /// other byte of ASCII text is zero.
+    pub fn detect(bytes: &[u8]) -> (Self, usize) {
+        if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+            return (Self::Utf8Bom, 3);
+        }
+        if bytes.starts_with(&[0xff, 0xfe]) {
+            return (Self::Utf16Le, 2);
+        }
+        if bytes.starts_with(&[0xfe, 0xff]) {
+            return (Self::Utf16Be, 2);
+        }
+
+        if bytes.len() >= 2 && bytes.len().is_multiple_of(2) {
+            let sample = &bytes[..bytes.len().min(1024)];
+            let zeros_at = |parity: usize| sample.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+            let pairs = sample.len() / 2;
+            if zeros_at(1) * 2 > pairs && zeros_at(0) == 0 {
+                return (Self::Utf16Le, 0);
+            }
+            if zeros_at(0) * 2 > pairs && zeros_at(1) == 0 {
+                return (Self::Utf16Be, 0);
+            }
+        }
+        (Self::Utf8, 0)
+    }
+
+    fn bom(&self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => &[],
+            Self::Utf8Bom => &[0xef, 0xbb, 0xbf],
+            Self::Utf16Le => &[0xff, 0xfe],
+            Self::Utf16Be => &[0xfe, 0xff],
+        }
+    }
+
+    /// Decode file contents, which may start with this encoding's BOM
+    pub fn decode(&self, bytes: &[u8]) -> Result<String> {
+        let bytes = bytes.strip_prefix(self.bom()).unwrap_or(bytes);
+        match self {
+            Self::Utf8 | Self::Utf8Bom => {
+                String::from_utf8(bytes.to_vec()).map_err(|_| anyhow!("File is not valid UTF-8"))
+            }
+            Self::Utf16Le | Self::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(anyhow!("File is not valid UTF-16: odd number of bytes"));
+                }
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| match self {
+                        Self::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                String::from_utf16(&units).map_err(|_| anyhow!("File is not valid UTF-16"))
+            }
+        }
+    }
+
+    /// Encode text, including the BOM for encodings that have one
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let mut bytes = self.bom().to_vec();
+        match self {
+            Self::Utf8 | Self::Utf8Bom => bytes.extend_from_slice(text.as_bytes()),
+            Self::Utf16Le => bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes)),
+            Self::Utf16Be => bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes)),
+        }
+        bytes
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf8Bom => "UTF-8 with BOM",
+            Self::Utf16Le => "UTF-16 LE",
+            Self::Utf16Be => "UTF-16 BE",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LineEnding {
     Lf,    // Unix: \n
@@ -57,6 +152,77 @@ impl LineEnding {
             Self::Cr => "\r",
         }
     }
+
+    /// The most frequent line ending of a text, or `None` if it has a single line
+    pub fn detect(text: &str) -> Option<Self> {
+        LineEndingStats::count(text).dominant()
+    }
+
+    /// Convert every line ending of a text to `\n`
+    pub fn normalize(text: &str) -> String {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    }
+
+    /// Convert `\n` line endings to this line ending
+    pub fn apply(&self, text: &str) -> String {
+        match self {
+            Self::Lf => text.to_string(),
+            _ => text.replace('\n', self.as_str()),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+            Self::Cr => "CR",
+        }
+    }
+}
+
+/// How often each line ending occurs in a text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineEndingStats {
+    pub lf: usize,
+    pub crlf: usize,
+    pub cr: usize,
+}
+
+impl LineEndingStats {
+    pub fn count(text: &str) -> Self {
+        let mut stats = Self::default();
+        let mut bytes = text.bytes().peekable();
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'\r' if bytes.peek() == Some(&b'\n') => {
+                    bytes.next();
+                    stats.crlf += 1;
+                }
+                b'\r' => stats.cr += 1,
+                b'\n' => stats.lf += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+
+    /// Whether more than one kind of line ending occurs
+    pub fn is_mixed(&self) -> bool {
+        [self.lf, self.crlf, self.cr].iter().filter(|&&count| count > 0).count() > 1
+    }
+
+    /// The most frequent line ending, preferring CRLF, then LF on ties
+    pub fn dominant(&self) -> Option<LineEnding> {
+        if self.lf + self.crlf + self.cr == 0 {
+            None
+        } else if self.crlf >= self.lf && self.crlf >= self.cr {
+            Some(LineEnding::CrLf)
+        } else if self.lf >= self.cr {
+            Some(LineEnding::Lf)
+        } else {
+            Some(LineEnding::Cr)
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -66,6 +232,8 @@ pub struct Document {
     pub language: Language,
     pub encoding: Encoding,
     pub line_ending: LineEnding,
+    /// The file had more than one kind of line ending when it was loaded
+    pub mixed_line_endings: bool,
     pub modified_time: Option<SystemTime>,
     pub is_dirty: bool,
     pub is_read_only: bool,
@@ -80,6 +248,7 @@ impl Document {
             language: Language::Typst,
             encoding: Encoding::Utf8,
             line_ending: LineEnding::default_for_platform(),
+            mixed_line_endings: false,
             modified_time: None,
             is_dirty: false,
             is_read_only: false,
@@ -87,6 +256,76 @@ impl Document {
         }
     }
 
+    /// Read a file, detecting its encoding and line ending
+    ///
+    /// The returned content always uses `\n` line endings; [`Document::save`]
+    /// converts them back.
+    pub fn load(path: &Path) -> Result<(Self, String)> {
+        let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let (encoding, _) = Encoding::detect(&bytes);
+        let text = encoding
+            .decode(&bytes)
+            .with_context(|| format!("Failed to decode {}", path.display()))?;
+
+        let mut document = Self::new(Some(path.to_path_buf()));
+        document.encoding = encoding;
+        let line_endings = LineEndingStats::count(&text);
+        document.line_ending = line_endings.dominant().unwrap_or_else(LineEnding::default_for_platform);
+        document.mixed_line_endings = line_endings.is_mixed();
+        document.language = Language::from_path(path);
+        document.modified_time = document.disk_modified_time();
+        document.is_read_only = is_read_only_file(path);
+        Ok((document, LineEnding::normalize(&text)))
+    }
+
+    /// Write content to the document's file in its encoding and line ending
+    ///
+    /// The file is written to a temporary file next to it and renamed over the
+    /// original, so a failed save never leaves a truncated file behind. The
+    /// rename would replace a read-only file, so those are refused.
+    pub fn save(&mut self, content: &str) -> Result<()> {
+        let path = self.path.clone().ok_or_else(|| anyhow!("Document has no path"))?;
+        if self.is_read_only {
+            return Err(anyhow!("{} is read-only", path.display()));
+        }
+        self.write(&path, content)?;
+        self.mark_saved();
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, content: &str) -> Result<()> {
+        let bytes = self.encoding.encode(&self.line_ending.apply(content));
+        write_atomic(path, &bytes)
+    }
+
+    fn mark_saved(&mut self) {
+        self.modified_time = self.disk_modified_time();
+        self.mixed_line_endings = false;
+        self.mark_clean();
+    }
+
+    /// Use one line ending for the whole file from the next save on
+    pub fn convert_line_endings(&mut self, line_ending: LineEnding) {
+        if self.line_ending != line_ending || self.mixed_line_endings {
+            self.line_ending = line_ending;
+            self.mixed_line_endings = false;
+            self.mark_dirty();
+        }
+    }
+
+    /// Save under a new path, which becomes the document's path once written
+    pub fn save_as(&mut self, path: PathBuf, content: &str) -> Result<()> {
+        if is_read_only_file(&path) {
+            return Err(anyhow!("{} is read-only", path.display()));
+        }
+        self.write(&path, content)?;
+        self.language = Language::from_path(&path);
+        self.path = Some(path);
+        self.is_read_only = false;
+        self.mark_saved();
+        Ok(())
+    }
+
     pub fn mark_dirty(&mut self) {
         self.is_dirty = true;
     }
@@ -99,6 +338,21 @@ impl Document {
         self.version += 1;
     }
 
+    /// Modification time of the file on disk, if it exists
+    pub fn disk_modified_time(&self) -> Option<SystemTime> {
+        let path = self.path.as_ref()?;
+        std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+    }
+
+    /// Whether the file changed on disk since it was last loaded or saved
+    pub fn is_modified_on_disk(&self) -> bool {
+        match (self.modified_time, self.disk_modified_time()) {
+            (Some(known), Some(on_disk)) => on_disk != known,
+            (None, Some(_)) => self.path.is_some(),
+            _ => false,
+        }
+    }
+
     pub fn file_name(&self) -> String {
         self.path
             .as_ref()
@@ -109,3 +363,215 @@ impl Document {
     }
 }
 
+fn is_read_only_file(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+/// Replace a file's contents through a temporary file in the same directory
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let directory = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name().ok_or_else(|| anyhow!("Invalid file path {}", path.display()))?;
+    let temp = directory.join(format!(".{}.{}.tmp", name.to_string_lossy(), uuid::Uuid::new_v4().simple()));
+
+    let result = (|| -> Result<()> {
+        let mut file = std::fs::File::create(&temp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        // Keep the permissions of the file being replaced
+        if let Ok(metadata) = std::fs::metadata(path) {
+            std::fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result.with_context(|| format!("Failed to save {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("typst-studio-document-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn encodings_round_trip_through_detection() {
+        let text = "= Titre\nÉtude ∑ 🙂";
+        for (encoding, bom) in [
+            (Encoding::Utf8, 0),
+            (Encoding::Utf8Bom, 3),
+            (Encoding::Utf16Le, 2),
+            (Encoding::Utf16Be, 2),
+        ] {
+            let bytes = encoding.encode(text);
+            assert_eq!(Encoding::detect(&bytes), (encoding, bom), "{}", encoding.label());
+            assert_eq!(encoding.decode(&bytes).unwrap(), text, "{}", encoding.label());
+        }
+    }
+
+    #[test]
+    fn detects_utf16_without_bom() {
+        assert_eq!(Encoding::detect(b"a\0b\0"), (Encoding::Utf16Le, 0));
+        assert_eq!(Encoding::detect(b"\0a\0b"), (Encoding::Utf16Be, 0));
+        assert_eq!(Encoding::detect(b"ab"), (Encoding::Utf8, 0));
+        assert_eq!(Encoding::detect(b""), (Encoding::Utf8, 0));
+    }
+
+    #[test]
+    fn rejects_invalid_contents() {
+        assert!(Encoding::Utf8.decode(&[0x66, 0xff]).is_err());
+        assert!(Encoding::Utf16Le.decode(&[0xff, 0xfe, 0x61]).is_err());
+        // An unpaired surrogate
+        assert!(Encoding::Utf16Be.decode(&[0xd8, 0x00]).is_err());
+    }
+
+    #[test]
+    fn line_endings_round_trip_through_normalization() {
+        for (line_ending, text) in [
+            (LineEnding::Lf, "a\nb\n\nc"),
+            (LineEnding::CrLf, "a\r\nb\r\n\r\nc"),
+            (LineEnding::Cr, "a\rb\r\rc"),
+        ] {
+            let normalized = LineEnding::normalize(text);
+            assert_eq!(normalized, "a\nb\n\nc");
+            assert_eq!(LineEnding::detect(text), Some(line_ending));
+            assert_eq!(line_ending.apply(&normalized), text);
+        }
+    }
+
+    #[test]
+    fn counts_mixed_line_endings() {
+        let stats = LineEndingStats::count("a\r\nb\nc\r\nd\re");
+        assert_eq!(stats, LineEndingStats { lf: 1, crlf: 2, cr: 1 });
+        assert!(stats.is_mixed());
+        assert_eq!(stats.dominant(), Some(LineEnding::CrLf));
+
+        let stats = LineEndingStats::count("a\nb\nc\r\n");
+        assert!(stats.is_mixed());
+        assert_eq!(stats.dominant(), Some(LineEnding::Lf));
+
+        let stats = LineEndingStats::count("a\nb\n");
+        assert!(!stats.is_mixed());
+        assert_eq!(stats.dominant(), Some(LineEnding::Lf));
+    }
+
+    #[test]
+    fn ties_prefer_crlf_then_lf() {
+        assert_eq!(LineEndingStats::count("a\r\nb\n").dominant(), Some(LineEnding::CrLf));
+        assert_eq!(LineEndingStats::count("a\nb\r").dominant(), Some(LineEnding::Lf));
+    }
+
+    #[test]
+    fn text_without_newlines_has_no_line_ending() {
+        let stats = LineEndingStats::count("one line");
+        assert_eq!(stats, LineEndingStats::default());
+        assert!(!stats.is_mixed());
+        assert_eq!(stats.dominant(), None);
+        assert_eq!(LineEnding::detect(""), None);
+    }
+
+    #[test]
+    fn converting_mixed_line_endings_rewrites_every_line() {
+        let directory = temp_dir();
+        let path = directory.join("mixed.typ");
+        std::fs::write(&path, "a\r\nb\nc\r\n").unwrap();
+
+        let (mut document, text) = Document::load(&path).unwrap();
+        assert!(document.mixed_line_endings);
+        assert_eq!(document.line_ending, LineEnding::CrLf);
+
+        // Converting to the dominant line ending still fixes the stray line
+        document.convert_line_endings(LineEnding::CrLf);
+        assert!(document.is_dirty);
+        assert!(!document.mixed_line_endings);
+        document.save(&text).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\r\nb\r\nc\r\n");
+
+        document.convert_line_endings(LineEnding::CrLf);
+        assert!(!document.is_dirty, "nothing left to convert");
+        document.convert_line_endings(LineEnding::Lf);
+        document.save(&text).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nb\nc\n");
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn saves_in_the_loaded_encoding_and_line_ending() {
+        let directory = temp_dir();
+        let path = directory.join("windows.typ");
+        let original = Encoding::Utf16Le.encode("a\r\nb\r\n");
+        std::fs::write(&path, &original).unwrap();
+
+        let (mut document, text) = Document::load(&path).unwrap();
+        assert_eq!(text, "a\nb\n");
+        assert_eq!((document.encoding, document.line_ending), (Encoding::Utf16Le, LineEnding::CrLf));
+        document.save(&text).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn failed_writes_keep_the_old_contents() {
+        let directory = temp_dir();
+        // Renaming over a non-empty directory fails after the temporary file is written
+        let path = directory.join("occupied");
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(path.join("kept.typ"), "original").unwrap();
+
+        assert!(write_atomic(&path, b"edited").is_err());
+        assert_eq!(std::fs::read_to_string(path.join("kept.typ")).unwrap(), "original");
+        let entries: Vec<_> = std::fs::read_dir(&directory).unwrap().collect();
+        assert_eq!(entries.len(), 1, "the temporary file is removed");
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn failed_save_as_keeps_the_path() {
+        let directory = temp_dir();
+        let path = directory.join("main.typ");
+        let mut document = Document::new(Some(path.clone()));
+        document.save("text").unwrap();
+
+        let unreachable = directory.join("missing").join("copy.md");
+        assert!(document.save_as(unreachable, "text").is_err());
+        assert_eq!(document.path.as_deref(), Some(path.as_path()));
+        assert_eq!(document.language, Language::Typst);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_save_read_only_files() {
+        let directory = temp_dir();
+        let path = directory.join("locked.typ");
+        std::fs::write(&path, "original").unwrap();
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&path, permissions).unwrap();
+
+        let (mut document, _text) = Document::load(&path).unwrap();
+        assert!(document.is_read_only);
+        assert!(document.save("edited").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original");
+
+        // A copy elsewhere can still be written
+        let copy = directory.join("copy.typ");
+        document.save_as(copy.clone(), "edited").unwrap();
+        assert!(!document.is_read_only);
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "edited");
+        assert!(document.save_as(path.clone(), "edited").is_err());
+        assert_eq!(document.path.as_deref(), Some(copy.as_path()));
+
+        let mut permissions = std::fs::metadata(&path).unwrap().permissions();
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(&path, permissions).unwrap();
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
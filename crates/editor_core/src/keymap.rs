@@ -0,0 +1,778 @@
+use crate::config::Config;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Key context of the text editor
+pub const EDITOR_CONTEXT: &str = "Editor";
+/// Key context of the preview pane
+pub const PREVIEW_CONTEXT: &str = "PreviewPane";
+/// Key context while the completion menu is open
+pub const COMPLETION_CONTEXT: &str = "Completion";
+
+/// Separates the keys of a user binding from its context predicate
+const WHEN: &str = " when ";
+
+const NAMED_KEYS: [&str; 20] = [
+    "escape", "enter", "tab", "space", "backspace", "delete", "insert", "home", "end", "pageup",
+    "pagedown", "up", "down", "left", "right", "menu", "capslock", "printscreen", "pause", "scrolllock",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum KeymapError {
+    #[error("Invalid keystroke '{input}': {message}")]
+    InvalidKeystroke { input: String, message: String },
+
+    #[error("Invalid context '{input}': {message}")]
+    InvalidContext { input: String, message: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    MacOS,
+    Linux,
+    Windows,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(target_os = "macos") {
+            Platform::MacOS
+        } else if cfg!(windows) {
+            Platform::Windows
+        } else {
+            Platform::Linux
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub control: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// Command on macOS, Super or Windows elsewhere
+    pub platform: bool,
+}
+
+/// A key pressed together with modifiers, such as `ctrl-shift-p`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Keystroke {
+    pub modifiers: Modifiers,
+    /// Lowercase key name, a single character or a name such as `enter` or `f5`
+    pub key: String,
+}
+
+impl Keystroke {
+    /// Parse `ctrl-shift-p` or `Ctrl+Shift+P`
+    ///
+    /// `secondary` is the platform's main modifier: Command on macOS and
+    /// Control elsewhere.
+    pub fn parse(input: &str, platform: Platform) -> Result<Self, KeymapError> {
+        let error = |message: &str| KeymapError::InvalidKeystroke {
+            input: input.to_string(),
+            message: message.to_string(),
+        };
+
+        let mut modifiers = Modifiers::default();
+        let mut rest = input.trim();
+        // The key itself may be `-` or `+`, so a separator needs text on both sides
+        while let Some(index) = rest.find(['-', '+']).filter(|&index| index > 0 && index + 1 < rest.len()) {
+            let name = rest[..index].to_ascii_lowercase();
+            let modifier = match name.as_str() {
+                "ctrl" | "control" => &mut modifiers.control,
+                "alt" | "option" | "opt" => &mut modifiers.alt,
+                "shift" => &mut modifiers.shift,
+                "cmd" | "command" | "super" | "win" | "meta" | "platform" => &mut modifiers.platform,
+                "secondary" if platform == Platform::MacOS => &mut modifiers.platform,
+                "secondary" => &mut modifiers.control,
+                _ => return Err(error(&format!("unknown modifier '{}'", &rest[..index]))),
+            };
+            *modifier = true;
+            rest = &rest[index + 1..];
+        }
+
+        let key = normalize_key(rest).ok_or_else(|| {
+            if rest.is_empty() {
+                error("missing key")
+            } else {
+                error(&format!("unknown key '{}'", rest))
+            }
+        })?;
+        Ok(Self { modifiers, key })
+    }
+}
+
+fn normalize_key(key: &str) -> Option<String> {
+    let key = key.to_lowercase();
+    let key = match key.as_str() {
+        "esc" => "escape".to_string(),
+        "return" => "enter".to_string(),
+        "del" => "delete".to_string(),
+        "pgup" => "pageup".to_string(),
+        "pgdn" => "pagedown".to_string(),
+        _ => key,
+    };
+
+    let is_function_key = key
+        .strip_prefix('f')
+        .and_then(|number| number.parse::<u8>().ok())
+        .is_some_and(|number| (1..=24).contains(&number));
+    let is_character = key.chars().count() == 1 && !key.starts_with(char::is_whitespace);
+    (is_character || is_function_key || NAMED_KEYS.contains(&key.as_str())).then_some(key)
+}
+
+impl fmt::Display for Keystroke {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = [
+            (self.modifiers.control, "ctrl"),
+            (self.modifiers.alt, "alt"),
+            (self.modifiers.shift, "shift"),
+            (self.modifiers.platform, "cmd"),
+        ];
+        for (_, name) in modifiers.iter().filter(|(pressed, _)| *pressed) {
+            write!(f, "{}-", name)?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Keystrokes typed one after another, such as `ctrl-k ctrl-s`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeySequence(pub Vec<Keystroke>);
+
+impl KeySequence {
+    pub fn parse(input: &str, platform: Platform) -> Result<Self, KeymapError> {
+        let strokes = input
+            .split_whitespace()
+            .map(|stroke| Keystroke::parse(stroke, platform))
+            .collect::<Result<Vec<_>, _>>()?;
+        if strokes.is_empty() {
+            return Err(KeymapError::InvalidKeystroke {
+                input: input.to_string(),
+                message: "missing key".to_string(),
+            });
+        }
+        Ok(Self(strokes))
+    }
+
+    pub fn strokes(&self) -> &[Keystroke] {
+        &self.0
+    }
+
+    /// Whether `self` is the start of a longer sequence
+    pub fn is_strict_prefix_of(&self, other: &KeySequence) -> bool {
+        self.0.len() < other.0.len() && other.0.starts_with(&self.0)
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, stroke) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", stroke)?;
+        }
+        Ok(())
+    }
+}
+
+/// When a binding applies, such as `Editor && !Completion`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ContextPredicate {
+    Identifier(String),
+    Not(Box<ContextPredicate>),
+    And(Box<ContextPredicate>, Box<ContextPredicate>),
+    Or(Box<ContextPredicate>, Box<ContextPredicate>),
+}
+
+impl ContextPredicate {
+    /// Parse identifiers combined with `!`, `&&`, `||` and parentheses
+    pub fn parse(input: &str) -> Result<Self, KeymapError> {
+        let mut parser = PredicateParser {
+            input,
+            tokens: tokenize(input)?,
+            position: 0,
+        };
+        let predicate = parser.or()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(predicate),
+            Some(token) => Err(parser.error(&format!("unexpected '{}'", token))),
+        }
+    }
+
+    /// Whether the predicate holds while the given contexts are active
+    pub fn eval(&self, contexts: &[&str]) -> bool {
+        match self {
+            ContextPredicate::Identifier(name) => contexts.contains(&name.as_str()),
+            ContextPredicate::Not(inner) => !inner.eval(contexts),
+            ContextPredicate::And(left, right) => left.eval(contexts) && right.eval(contexts),
+            ContextPredicate::Or(left, right) => left.eval(contexts) || right.eval(contexts),
+        }
+    }
+}
+
+impl fmt::Display for ContextPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextPredicate::Identifier(name) => write!(f, "{}", name),
+            ContextPredicate::Not(inner) => match **inner {
+                ContextPredicate::Identifier(_) | ContextPredicate::Not(_) => write!(f, "!{}", inner),
+                _ => write!(f, "!({})", inner),
+            },
+            ContextPredicate::And(left, right) => {
+                for (index, operand) in [left, right].into_iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " && ")?;
+                    }
+                    match **operand {
+                        ContextPredicate::Or(..) => write!(f, "({})", operand)?,
+                        _ => write!(f, "{}", operand)?,
+                    }
+                }
+                Ok(())
+            }
+            ContextPredicate::Or(left, right) => write!(f, "{} || {}", left, right),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<String>, KeymapError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '!' | '(' | ')' => tokens.push(c.to_string()),
+            '&' | '|' => {
+                if chars.next_if(|&(_, next)| next == c).is_none() {
+                    return Err(KeymapError::InvalidContext {
+                        input: input.to_string(),
+                        message: format!("expected '{}{}'", c, c),
+                    });
+                }
+                tokens.push(format!("{}{}", c, c));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, next)) = chars.next_if(|&(_, next)| next.is_alphanumeric() || next == '_') {
+                    end = index + next.len_utf8();
+                }
+                tokens.push(input[start..end].to_string());
+            }
+            c => {
+                return Err(KeymapError::InvalidContext {
+                    input: input.to_string(),
+                    message: format!("unexpected '{}'", c),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over `or := and ('||' and)*`, `and := unary ('&&' unary)*`
+struct PredicateParser<'a> {
+    input: &'a str,
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl PredicateParser<'_> {
+    fn error(&self, message: &str) -> KeymapError {
+        KeymapError::InvalidContext {
+            input: self.input.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let matches = self.tokens.get(self.position).is_some_and(|next| next == token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn or(&mut self) -> Result<ContextPredicate, KeymapError> {
+        let mut predicate = self.and()?;
+        while self.eat("||") {
+            predicate = ContextPredicate::Or(Box::new(predicate), Box::new(self.and()?));
+        }
+        Ok(predicate)
+    }
+
+    fn and(&mut self) -> Result<ContextPredicate, KeymapError> {
+        let mut predicate = self.unary()?;
+        while self.eat("&&") {
+            predicate = ContextPredicate::And(Box::new(predicate), Box::new(self.unary()?));
+        }
+        Ok(predicate)
+    }
+
+    fn unary(&mut self) -> Result<ContextPredicate, KeymapError> {
+        if self.eat("!") {
+            return Ok(ContextPredicate::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let predicate = self.or()?;
+            if !self.eat(")") {
+                return Err(self.error("missing ')'"));
+            }
+            return Ok(predicate);
+        }
+        match self.tokens.get(self.position) {
+            Some(token) if token.starts_with(|c: char| c.is_alphanumeric() || c == '_') => {
+                self.position += 1;
+                Ok(ContextPredicate::Identifier(token.clone()))
+            }
+            Some(token) => Err(self.error(&format!("unexpected '{}'", token))),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+}
+
+/// Where a binding comes from; user bindings take precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BindingSource {
+    Default,
+    User,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub keys: KeySequence,
+    /// Command id, such as `preview.find_next`
+    pub command: String,
+    /// Applies everywhere when `None`
+    pub context: Option<ContextPredicate>,
+    pub source: BindingSource,
+}
+
+/// Result of looking up typed keystrokes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeymapMatch<'a> {
+    None,
+    /// The keystrokes start a longer sequence; wait for the next one
+    Pending,
+    Command(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both bindings use the same keys in the same context
+    SameKeys,
+    /// The first binding's keys start the second's, so one of them never runs
+    Prefix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeymapConflict<'a> {
+    pub kind: ConflictKind,
+    pub first: &'a Binding,
+    pub second: &'a Binding,
+}
+
+impl fmt::Display for KeymapConflict<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ConflictKind::SameKeys => write!(
+                f,
+                "'{}' is bound to both {} and {}",
+                self.first.keys, self.first.command, self.second.command
+            ),
+            ConflictKind::Prefix => write!(
+                f,
+                "'{}' ({}) starts '{}' ({})",
+                self.first.keys, self.first.command, self.second.keys, self.second.command
+            ),
+        }
+    }
+}
+
+/// Key bindings of the editor: platform defaults followed by user overrides
+///
+/// Later bindings win over earlier ones of the same source, user bindings win
+/// over defaults, and bindings with a context win over global ones.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    platform: Platform,
+    bindings: Vec<Binding>,
+}
+
+impl Keymap {
+    pub fn new(platform: Platform) -> Self {
+        Self {
+            platform,
+            bindings: Vec::new(),
+        }
+    }
+
+    /// The built-in bindings of a platform
+    pub fn with_defaults(platform: Platform) -> Self {
+        let mut keymap = Self::new(platform);
+        for (keys, command, context) in default_bindings(platform) {
+            keymap
+                .add(keys, command, context, BindingSource::Default)
+                .expect("default bindings are valid");
+        }
+        keymap
+    }
+
+    /// Defaults overridden by `Config::keybindings`, with the entries that failed to parse
+    pub fn from_config(config: &Config, platform: Platform) -> (Self, Vec<KeymapError>) {
+        let mut keymap = Self::with_defaults(platform);
+        let errors = keymap.add_user_bindings(&config.keybindings);
+        (keymap, errors)
+    }
+
+    pub fn add(
+        &mut self,
+        keys: &str,
+        command: &str,
+        context: Option<&str>,
+        source: BindingSource,
+    ) -> Result<(), KeymapError> {
+        let binding = Binding {
+            keys: KeySequence::parse(keys, self.platform)?,
+            command: command.to_string(),
+            context: context.map(ContextPredicate::parse).transpose()?,
+            source,
+        };
+        self.bindings.push(binding);
+        Ok(())
+    }
+
+    /// Add entries of the form `command = "keys [when context]"`
+    ///
+    /// An entry replaces the default keys of its command; an empty value
+    /// leaves the command without keys.
+    pub fn add_user_bindings(&mut self, entries: &HashMap<String, String>) -> Vec<KeymapError> {
+        let mut entries: Vec<_> = entries.iter().collect();
+        entries.sort();
+
+        let mut errors = Vec::new();
+        for (command, value) in entries {
+            self.bindings
+                .retain(|binding| binding.source == BindingSource::User || &binding.command != command);
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let (keys, context) = match value.split_once(WHEN) {
+                Some((keys, context)) => (keys, Some(context)),
+                None => (value, None),
+            };
+            if let Err(error) = self.add(keys, command, context, BindingSource::User) {
+                errors.push(error);
+            }
+        }
+        errors
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Bindings that can run: those not replaced by a later one with the same
+    /// keys and context
+    pub fn active_bindings(&self) -> impl Iterator<Item = &Binding> {
+        self.bindings
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !self.is_overridden(*index))
+            .map(|(_, binding)| binding)
+    }
+
+    fn is_overridden(&self, index: usize) -> bool {
+        let binding = &self.bindings[index];
+        self.bindings.iter().enumerate().any(|(other_index, other)| {
+            (other.source, other_index) > (binding.source, index)
+                && other.keys == binding.keys
+                && other.context == binding.context
+        })
+    }
+
+    /// Keys that run a command, for display next to it
+    pub fn keys_for(&self, command: &str) -> Vec<&KeySequence> {
+        self.active_bindings()
+            .filter(|binding| binding.command == command)
+            .map(|binding| &binding.keys)
+            .collect()
+    }
+
+    /// Look up keystrokes typed so far while the given contexts are active
+    pub fn resolve(&self, typed: &[Keystroke], contexts: &[&str]) -> KeymapMatch<'_> {
+        let candidates: Vec<(usize, &Binding)> = self
+            .active_bindings()
+            .enumerate()
+            .filter(|(_, binding)| binding.context.as_ref().is_none_or(|context| context.eval(contexts)))
+            .collect();
+
+        if candidates
+            .iter()
+            .any(|(_, binding)| binding.keys.0.len() > typed.len() && binding.keys.0.starts_with(typed))
+        {
+            return KeymapMatch::Pending;
+        }
+        candidates
+            .iter()
+            .filter(|(_, binding)| binding.keys.0 == typed)
+            .max_by_key(|(index, binding)| (binding.source, binding.context.is_some(), *index))
+            .map_or(KeymapMatch::None, |(_, binding)| KeymapMatch::Command(&binding.command))
+    }
+
+    /// Bindings that shadow each other within the same context
+    ///
+    /// A user binding replacing a default is intended and not reported.
+    /// Contexts are compared as written, so `Editor` and `Editor && !Completion`
+    /// are treated as different.
+    pub fn conflicts(&self) -> Vec<KeymapConflict<'_>> {
+        let mut conflicts = Vec::new();
+
+        // Only the later of two such bindings is active, so look at all of them
+        for (index, first) in self.bindings.iter().enumerate() {
+            for second in &self.bindings[index + 1..] {
+                if first.source == second.source
+                    && first.keys == second.keys
+                    && first.context == second.context
+                    && first.command != second.command
+                {
+                    conflicts.push(KeymapConflict {
+                        kind: ConflictKind::SameKeys,
+                        first,
+                        second,
+                    });
+                }
+            }
+        }
+
+        let active: Vec<&Binding> = self.active_bindings().collect();
+        for first in &active {
+            for second in &active {
+                if first.context == second.context && first.keys.is_strict_prefix_of(&second.keys) {
+                    conflicts.push(KeymapConflict {
+                        kind: ConflictKind::Prefix,
+                        first,
+                        second,
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+/// Built-in bindings as keys, command and context
+fn default_bindings(platform: Platform) -> Vec<(&'static str, &'static str, Option<&'static str>)> {
+    let mut bindings = vec![
+        ("secondary-n", "file.new", None),
+        ("secondary-o", "file.open", None),
+        ("secondary-k secondary-o", "file.open_folder", None),
+        ("secondary-s", "file.save", None),
+        ("secondary-shift-s", "file.save_as", None),
+        ("secondary-e", "file.export", None),
+        ("secondary-g", "edit.go_to_line", Some(EDITOR_CONTEXT)),
+        ("secondary-shift-p", "view.command_palette", None),
+        ("secondary-b", "view.toggle_sidebar", None),
+        ("secondary-k v", "view.toggle_preview", None),
+        ("secondary-j", "view.toggle_console", None),
+        ("secondary-=", "view.zoom_in", None),
+        ("secondary--", "view.zoom_out", None),
+        ("secondary-0", "view.reset_zoom", None),
+        ("ctrl-alt-j", "preview.reveal", None),
+        ("secondary-c", "preview.copy", Some(PREVIEW_CONTEXT)),
+        ("secondary-f", "preview.find", Some(PREVIEW_CONTEXT)),
+        ("secondary-g", "preview.find_next", Some(PREVIEW_CONTEXT)),
+        ("secondary-shift-g", "preview.find_previous", Some(PREVIEW_CONTEXT)),
+    ];
+    if platform != Platform::MacOS {
+        bindings.extend([
+            ("f3", "preview.find_next", Some(PREVIEW_CONTEXT)),
+            ("shift-f3", "preview.find_previous", Some(PREVIEW_CONTEXT)),
+        ]);
+    }
+    bindings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(input: &str) -> Keystroke {
+        Keystroke::parse(input, Platform::Linux).unwrap()
+    }
+
+    fn strokes(input: &str) -> Vec<Keystroke> {
+        KeySequence::parse(input, Platform::Linux).unwrap().0
+    }
+
+    fn user_keymap(entries: &[(&str, &str)]) -> Keymap {
+        let mut keymap = Keymap::with_defaults(Platform::Linux);
+        let entries = entries
+            .iter()
+            .map(|(command, keys)| (command.to_string(), keys.to_string()))
+            .collect();
+        assert_eq!(keymap.add_user_bindings(&entries), Vec::new());
+        keymap
+    }
+
+    #[test]
+    fn parses_modifiers_in_either_notation() {
+        let parsed = stroke("Ctrl+Shift+P");
+        assert!(parsed.modifiers.control && parsed.modifiers.shift);
+        assert!(!parsed.modifiers.alt && !parsed.modifiers.platform);
+        assert_eq!(parsed.key, "p");
+        assert_eq!(parsed, stroke("ctrl-shift-p"));
+        assert_eq!(stroke("esc").key, "escape");
+        assert_eq!(stroke("shift-f13").key, "f13");
+    }
+
+    #[test]
+    fn minus_and_plus_can_be_keys() {
+        let zoom_out = stroke("secondary--");
+        assert!(zoom_out.modifiers.control);
+        assert_eq!(zoom_out.key, "-");
+
+        let plus = stroke("ctrl-+");
+        assert!(plus.modifiers.control);
+        assert_eq!(plus.key, "+");
+
+        assert_eq!(stroke("-").key, "-");
+        assert_eq!(stroke("ctrl+-").key, "-");
+    }
+
+    #[test]
+    fn secondary_is_command_on_macos() {
+        let mac = Keystroke::parse("secondary-s", Platform::MacOS).unwrap();
+        assert!(mac.modifiers.platform && !mac.modifiers.control);
+        let linux = stroke("secondary-s");
+        assert!(linux.modifiers.control && !linux.modifiers.platform);
+    }
+
+    #[test]
+    fn rejects_invalid_keystrokes() {
+        for input in ["hyper-x", "ctrl-", "ctrl-f25", "ctrl-banana", ""] {
+            assert!(
+                matches!(Keystroke::parse(input, Platform::Linux), Err(KeymapError::InvalidKeystroke { .. })),
+                "{input}"
+            );
+        }
+        assert!(KeySequence::parse("   ", Platform::Linux).is_err());
+    }
+
+    #[test]
+    fn parses_chords() {
+        let chord = KeySequence::parse("ctrl-k  ctrl-s", Platform::Linux).unwrap();
+        assert_eq!(chord.0, vec![stroke("ctrl-k"), stroke("ctrl-s")]);
+        assert_eq!(chord.to_string(), "ctrl-k ctrl-s");
+        let prefix = KeySequence::parse("ctrl-k", Platform::Linux).unwrap();
+        assert!(prefix.is_strict_prefix_of(&chord));
+        assert!(!chord.is_strict_prefix_of(&chord));
+    }
+
+    #[test]
+    fn context_predicates_follow_precedence() {
+        let predicate = ContextPredicate::parse("Editor && !Completion || PreviewPane").unwrap();
+        assert!(predicate.eval(&["Editor"]));
+        assert!(!predicate.eval(&["Editor", "Completion"]));
+        assert!(predicate.eval(&["PreviewPane", "Completion"]));
+        assert!(!predicate.eval(&[]));
+
+        let grouped = ContextPredicate::parse("Editor && !(Completion || PreviewPane)").unwrap();
+        assert!(grouped.eval(&["Editor"]));
+        assert!(!grouped.eval(&["Editor", "PreviewPane"]));
+        assert_eq!(ContextPredicate::parse(&grouped.to_string()).unwrap(), grouped);
+
+        assert!(ContextPredicate::parse("!!Editor").unwrap().eval(&["Editor"]));
+    }
+
+    #[test]
+    fn rejects_invalid_predicates() {
+        for input in ["", "Editor &&", "(Editor", "Editor)", "Editor & Completion", "Editor Completion"] {
+            assert!(
+                matches!(ContextPredicate::parse(input), Err(KeymapError::InvalidContext { .. })),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn resolves_chords_as_pending_then_command() {
+        let keymap = Keymap::with_defaults(Platform::Linux);
+        assert_eq!(keymap.resolve(&strokes("ctrl-k"), &[]), KeymapMatch::Pending);
+        assert_eq!(
+            keymap.resolve(&strokes("ctrl-k v"), &[]),
+            KeymapMatch::Command("view.toggle_preview")
+        );
+        assert_eq!(keymap.resolve(&strokes("ctrl-k x"), &[]), KeymapMatch::None);
+        assert_eq!(keymap.resolve(&strokes("ctrl-q"), &[]), KeymapMatch::None);
+    }
+
+    #[test]
+    fn resolves_by_context() {
+        let keymap = Keymap::with_defaults(Platform::Linux);
+        let go = strokes("ctrl-g");
+        assert_eq!(keymap.resolve(&go, &[EDITOR_CONTEXT]), KeymapMatch::Command("edit.go_to_line"));
+        assert_eq!(keymap.resolve(&go, &[PREVIEW_CONTEXT]), KeymapMatch::Command("preview.find_next"));
+        assert_eq!(keymap.resolve(&go, &[]), KeymapMatch::None);
+    }
+
+    #[test]
+    fn user_bindings_replace_the_defaults_of_their_command() {
+        let keymap = user_keymap(&[("file.save", "ctrl-alt-s"), ("file.export", "")]);
+        assert_eq!(keymap.keys_for("file.save"), vec![&KeySequence(strokes("ctrl-alt-s"))]);
+        assert_eq!(keymap.resolve(&strokes("ctrl-s"), &[]), KeymapMatch::None);
+        assert!(keymap.keys_for("file.export").is_empty());
+        assert_eq!(keymap.resolve(&strokes("ctrl-e"), &[]), KeymapMatch::None);
+    }
+
+    #[test]
+    fn user_bindings_win_over_defaults_with_the_same_keys() {
+        let keymap = user_keymap(&[("view.toggle_sidebar", "ctrl-s when Editor")]);
+        let save = strokes("ctrl-s");
+        assert_eq!(
+            keymap.resolve(&save, &[EDITOR_CONTEXT]),
+            KeymapMatch::Command("view.toggle_sidebar")
+        );
+        assert_eq!(keymap.resolve(&save, &[]), KeymapMatch::Command("file.save"));
+        assert!(keymap.conflicts().is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_user_bindings() {
+        let mut keymap = Keymap::with_defaults(Platform::Linux);
+        let entries = HashMap::from([("file.save".to_string(), "ctrl-s when Editor &&".to_string())]);
+        let errors = keymap.add_user_bindings(&entries);
+        assert!(matches!(errors.as_slice(), [KeymapError::InvalidContext { .. }]));
+    }
+
+    #[test]
+    fn default_bindings_do_not_conflict() {
+        for platform in [Platform::Linux, Platform::MacOS, Platform::Windows] {
+            let keymap = Keymap::with_defaults(platform);
+            let conflicts: Vec<String> = keymap.conflicts().iter().map(ToString::to_string).collect();
+            assert!(conflicts.is_empty(), "{platform:?}: {conflicts:?}");
+        }
+    }
+
+    #[test]
+    fn reports_conflicts() {
+        let mut keymap = Keymap::new(Platform::Linux);
+        for (keys, command) in [("ctrl-k ctrl-o", "file.open_folder"), ("ctrl-k", "file.open"), ("f5", "a"), ("f5", "b")] {
+            keymap.add(keys, command, None, BindingSource::User).unwrap();
+        }
+        let conflicts = keymap.conflicts();
+        let kinds: Vec<(ConflictKind, &str, &str)> = conflicts
+            .iter()
+            .map(|conflict| (conflict.kind, conflict.first.command.as_str(), conflict.second.command.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ConflictKind::SameKeys, "a", "b"),
+                (ConflictKind::Prefix, "file.open", "file.open_folder"),
+            ]
+        );
+    }
+}
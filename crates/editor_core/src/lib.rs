@@ -1,12 +1,26 @@
+pub mod appearance;
+pub mod commands;
 pub mod config;
+pub mod dependencies;
 pub mod document;
+pub mod keymap;
 pub mod project;
 pub mod buffer;
 pub mod selection;
+pub mod session;
 pub mod state;
 
-pub use config::Config;
+pub use appearance::{
+    AppearanceMonitor, AppearanceProvider, DesktopAppearance, FixedAppearance, SystemAppearance,
+};
+pub use commands::{CommandCategory, CommandRegistry};
+pub use config::{
+    AutoSave, AutoSaveTrigger, Config, ConfigChange, ConfigError, ConfigEvent, ConfigLayer, ConfigService,
+    LayeredConfig,
+};
 pub use document::{Document, DocumentId};
-pub use project::Project;
-pub use state::{ApplicationState, WorkspaceState, EditorState};
+pub use keymap::{Keymap, KeymapError, Platform};
+pub use project::{Project, ProjectConfig, ProjectSettings};
+pub use session::Session;
+pub use state::{ApplicationState, AutoSaveReport, WorkspaceState, EditorState, ExternalChange};
 
@@ -1,9 +1,18 @@
-use anyhow::Result;
+use crate::config::Config;
+use crate::dependencies::{DependencyGraph, DependencyTarget};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
+use typst_syntax::ast;
+use typst_syntax::SyntaxNode;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Project configuration file names, relative to the project root, in lookup order
+pub const PROJECT_CONFIG_FILES: [&str; 2] = ["typst-studio.toml", ".typst-studio/config.toml"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DependencyType {
     Import,   // Typst import
     Include,  // Typst include
@@ -18,19 +27,72 @@ pub struct FileDependency {
     pub last_modified: SystemTime,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProjectSettings {
+    #[serde(default)]
     pub format_on_save: bool,
+
+    #[serde(default)]
     pub auto_compile: bool,
+
+    #[serde(default)]
     pub compile_on_save: bool,
+
+    #[serde(default)]
     pub output_directory: Option<PathBuf>,
+
+    /// Exported file name without extension; supports `{name}`, `{page}`,
+    /// `{0page}` and `{total}`
+    #[serde(default)]
+    pub output_filename: Option<String>,
+}
+
+/// The `[project]` section of a project configuration file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProjectConfig {
+    /// Main file relative to the project root; detected when absent
+    #[serde(default)]
+    pub main: Option<PathBuf>,
+
+    /// Arguments as given to `typst compile`; only `--input key=value` is supported
+    #[serde(default)]
+    pub compiler_args: Vec<String>,
+
+    #[serde(flatten)]
+    pub settings: ProjectSettings,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ProjectConfigFile {
+    #[serde(default)]
+    project: ProjectConfig,
+}
+
+impl ProjectConfig {
+    /// Path of the configuration file of a project root, if one exists
+    pub fn find(root: &Path) -> Option<PathBuf> {
+        PROJECT_CONFIG_FILES
+            .iter()
+            .map(|name| root.join(name))
+            .find(|path| path.is_file())
+    }
+
+    pub fn load_from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let file: ProjectConfigFile = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(file.project)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Project {
     pub root: PathBuf,
     pub main_file: Option<PathBuf>,
+    /// Every `.typ` file of the project that is not ignored
+    pub sources: Vec<PathBuf>,
     pub dependencies: HashMap<PathBuf, FileDependency>,
+    pub dependency_graph: DependencyGraph,
     pub settings: ProjectSettings,
     pub compiler_args: Vec<String>,
 }
@@ -38,26 +100,122 @@ pub struct Project {
 impl Project {
     pub fn new(root: PathBuf) -> Self {
         Self {
-            root,
+            root: root.clone(),
             main_file: None,
+            sources: Vec::new(),
             dependencies: HashMap::new(),
+            dependency_graph: DependencyGraph::new(root.clone()),
             settings: ProjectSettings::default(),
             compiler_args: Vec::new(),
         }
     }
 
+    /// Scan a project root for sources, load its configuration and detect the main file
     pub fn discover(root: PathBuf) -> Result<Self> {
-        // TODO: Scan directory for .typ files
-        // TODO: Look for project configuration
-        // TODO: Detect main file
-        Ok(Self::new(root))
+        let mut project = Self::new(root);
+        project.sources = scan_sources(&project.root);
+        project.rebuild_dependencies();
+
+        let config = match ProjectConfig::find(&project.root) {
+            Some(path) => ProjectConfig::load_from_file(&path)?,
+            None => ProjectConfig::default(),
+        };
+        project.settings = config.settings;
+        project.compiler_args = config.compiler_args;
+        project.main_file = match config.main {
+            Some(main) => Some(project.root.join(main)),
+            None => detect_main_file(&project.sources, &project.dependency_graph),
+        };
+        Ok(project)
+    }
+
+    /// Re-parse every source and refresh the dependency graph and list
+    pub fn rebuild_dependencies(&mut self) {
+        self.dependency_graph = DependencyGraph::build(self.root.clone(), &self.sources);
+        self.sync_dependencies();
+    }
+
+    /// Refill the flat dependency list from the graph
+    fn sync_dependencies(&mut self) {
+        self.dependencies.clear();
+        let sources = self.sources.clone();
+        for source in &sources {
+            for dependency in self.dependency_graph.dependencies_of(source).to_vec() {
+                let path = match dependency.target {
+                    DependencyTarget::File(path) => path,
+                    DependencyTarget::Package(spec) => PathBuf::from(spec),
+                };
+                self.add_dependency(path, dependency.dependency_type);
+            }
+        }
+    }
+
+    /// Update the project after a file was created, modified or removed on
+    /// disk, returning the main files that need recompiling
+    pub fn file_changed(&mut self, path: &Path, exists: bool) -> Vec<PathBuf> {
+        let is_source = path.extension().is_some_and(|ext| ext == "typ") && self.is_file_in_project(path);
+        if is_source && exists {
+            if let Err(index) = self.sources.binary_search_by(|source| source.as_path().cmp(path)) {
+                self.sources.insert(index, path.to_path_buf());
+            }
+            match std::fs::read_to_string(path) {
+                Ok(text) => self.dependency_graph.update_file(path, &text),
+                Err(_) => self.dependency_graph.remove_file(path),
+            }
+            self.sync_dependencies();
+        } else if is_source {
+            self.sources.retain(|source| source != path);
+            self.dependency_graph.remove_file(path);
+            self.sync_dependencies();
+        } else if let Some(dependency) = self.dependencies.get_mut(path) {
+            dependency.last_modified = std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .unwrap_or_else(|_| SystemTime::now());
+        }
+
+        self.affected_main_files(path)
+    }
+
+    /// The configured or detected main file, or every source nothing depends on
+    pub fn main_files(&self) -> Vec<PathBuf> {
+        match &self.main_file {
+            Some(main) => vec![main.clone()],
+            None => self.dependency_graph.roots().into_iter().cloned().collect(),
+        }
+    }
+
+    /// Main files that need recompiling when a file changes
+    pub fn affected_main_files(&self, changed: &Path) -> Vec<PathBuf> {
+        let main_files = self.main_files();
+        self.dependency_graph
+            .affected_main_files(changed, &main_files)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Sources that the main files never import or include
+    pub fn unused_files(&self) -> Vec<PathBuf> {
+        self.dependency_graph
+            .unused_files(&self.sources, &self.main_files())
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Configuration with this project's file merged over the global one
+    pub fn config(&self) -> Result<Config> {
+        Config::load_for_project(&self.root)
     }
 
     pub fn add_dependency(&mut self, path: PathBuf, dep_type: DependencyType) {
+        let last_modified = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or_else(|_| SystemTime::now());
         let dep = FileDependency {
             path: path.clone(),
             dependency_type: dep_type,
-            last_modified: SystemTime::now(),
+            last_modified,
         };
         self.dependencies.insert(path, dep);
     }
@@ -67,3 +225,129 @@ impl Project {
     }
 }
 
+/// All `.typ` files below a root, honoring `.gitignore` and `.ignore` files
+fn scan_sources(root: &Path) -> Vec<PathBuf> {
+    let mut sources: Vec<PathBuf> = ignore::WalkBuilder::new(root)
+        .require_git(false)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "typ"))
+        .collect();
+    sources.sort();
+    sources
+}
+
+/// Pick the most likely main file among the sources
+///
+/// Files imported or included by another source are never the main file. Of
+/// the rest, one setting document metadata wins, then one named `main.typ`,
+/// then the one pulling in the most files, then the one closest to the root.
+fn detect_main_file(sources: &[PathBuf], graph: &DependencyGraph) -> Option<PathBuf> {
+    sources
+        .iter()
+        .filter(|path| !graph.is_referenced(path))
+        .filter_map(|path| Some((path, std::fs::read_to_string(path).ok()?)))
+        .min_by_key(|(path, text)| {
+            (
+                !sets_document(text),
+                path.file_name().is_none_or(|name| name != "main.typ"),
+                Reverse(graph.reachable_from(std::slice::from_ref(*path)).len()),
+                path.components().count(),
+                path.to_path_buf(),
+            )
+        })
+        .map(|(path, _)| path.to_path_buf())
+}
+
+/// Whether a source has a `set document(..)` rule, in markup or code
+fn sets_document(text: &str) -> bool {
+    fn visit(node: &SyntaxNode) -> bool {
+        let sets = node.cast::<ast::SetRule>().is_some_and(|rule| {
+            matches!(rule.target(), ast::Expr::Ident(ident) if ident.get().as_str() == "document")
+        });
+        sets || node.children().any(visit)
+    }
+    visit(&typst_syntax::parse(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The main file detected in a project made of `files`, relative to its root
+    fn detected_main(files: &[(&str, &str)]) -> Option<PathBuf> {
+        let root = std::env::temp_dir().join(format!("typst-studio-project-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        for (path, contents) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        let project = Project::discover(root.clone()).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+        project.main_file.map(|main| main.strip_prefix(&root).unwrap().to_path_buf())
+    }
+
+    #[test]
+    fn detects_set_document_in_markup_and_code() {
+        assert!(sets_document("#set document(title: \"Thesis\")"));
+        assert!(sets_document("#set   document (author: \"A\")"));
+        assert!(sets_document("#{\n  set document(title: \"Code\")\n}"));
+        assert!(sets_document("#let setup(body) = {\n  set document(date: auto)\n  body\n}"));
+        assert!(sets_document("#show: body => {\n  set document(title: \"Show\")\n  body\n}"));
+
+        assert!(!sets_document("#set text(lang: \"de\")"));
+        assert!(!sets_document("// #set document(title: \"Commented\")"));
+        assert!(!sets_document("`#set document(title: \"Raw\")`"));
+        assert!(!sets_document("#let document = none"));
+    }
+
+    #[test]
+    fn referenced_files_are_never_the_main_file() {
+        let main = detected_main(&[
+            ("main.typ", "#include \"chapter.typ\""),
+            ("chapter.typ", "#set document(title: \"Chapter\")"),
+        ]);
+        assert_eq!(main, Some(PathBuf::from("main.typ")));
+    }
+
+    #[test]
+    fn setting_the_document_wins_over_the_name() {
+        let main = detected_main(&[
+            ("main.typ", "= Notes"),
+            ("thesis.typ", "#{ set document(title: \"Thesis\") }\n#import \"a.typ\": *"),
+            ("a.typ", ""),
+        ]);
+        assert_eq!(main, Some(PathBuf::from("thesis.typ")));
+    }
+
+    #[test]
+    fn main_typ_wins_over_the_number_of_dependencies() {
+        let main = detected_main(&[
+            ("main.typ", "= Notes"),
+            ("book.typ", "#include \"a.typ\"\n#include \"b.typ\""),
+            ("a.typ", ""),
+            ("b.typ", ""),
+        ]);
+        assert_eq!(main, Some(PathBuf::from("main.typ")));
+    }
+
+    #[test]
+    fn more_dependencies_win_over_depth() {
+        let main = detected_main(&[
+            ("notes.typ", "= Notes"),
+            ("book/book.typ", "#include \"a.typ\""),
+            ("book/a.typ", ""),
+        ]);
+        assert_eq!(main, Some(PathBuf::from("book/book.typ")));
+    }
+
+    #[test]
+    fn shallowest_then_first_path_breaks_ties() {
+        let main = detected_main(&[("sub/a.typ", ""), ("c.typ", ""), ("b.typ", "")]);
+        assert_eq!(main, Some(PathBuf::from("b.typ")));
+        assert_eq!(detected_main(&[]), None);
+    }
+}
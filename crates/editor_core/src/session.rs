@@ -0,0 +1,300 @@
+use crate::document::{write_atomic, Document, Encoding, LineEnding};
+use crate::selection::{Cursor, MultiCursor};
+use crate::state::{ApplicationState, EditorState, WorkspaceId, WorkspaceState};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often the open session is written to disk
+pub const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+const SESSION_VERSION: u32 = 1;
+
+/// Everything needed to reopen the editor as it was, including unsaved edits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    pub workspaces: Vec<WorkspaceSession>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceSession {
+    pub root: Option<PathBuf>,
+    pub documents: Vec<DocumentSession>,
+    /// Index into `documents`
+    pub active_document: Option<usize>,
+    pub sidebar_visible: bool,
+    pub preview_visible: bool,
+    pub console_visible: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentSession {
+    pub path: Option<PathBuf>,
+    /// Buffer content, kept only when it differs from the file
+    pub unsaved_content: Option<String>,
+    /// Modification time of the file the unsaved content was based on
+    pub modified_time: Option<SystemTime>,
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    pub cursors: Vec<Cursor>,
+    pub scroll_offset: f32,
+}
+
+impl Session {
+    /// Default session file in the user's data directory
+    pub fn default_path() -> Option<PathBuf> {
+        directories::ProjectDirs::from("com", "typst", "typst-studio")
+            .map(|dirs| dirs.data_dir().join("session.json"))
+    }
+
+    pub fn capture(state: &ApplicationState) -> Self {
+        let workspaces = state.windows
+            .iter()
+            .filter_map(|window| state.workspaces.get(window))
+            .map(|workspace| WorkspaceSession::capture(&workspace.read()))
+            .collect();
+        Self {
+            version: SESSION_VERSION,
+            workspaces,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Write the session, replacing the previous one atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        write_atomic(path, serde_json::to_string(self)?.as_bytes())
+    }
+}
+
+impl WorkspaceSession {
+    pub fn capture(workspace: &WorkspaceState) -> Self {
+        let mut editors: Vec<_> = workspace.open_documents.iter().collect();
+        // Untitled documents last, the rest by path, for a stable order
+        editors.sort_by_key(|(_, editor)| {
+            let path = editor.read().document.path.clone();
+            (path.is_none(), path)
+        });
+
+        let active_document = editors
+            .iter()
+            .position(|(id, _)| Some(**id) == workspace.active_document);
+        let documents = editors
+            .iter()
+            .map(|(_, editor)| DocumentSession::capture(&editor.read()))
+            .collect();
+
+        Self {
+            root: workspace.root.clone(),
+            documents,
+            active_document,
+            sidebar_visible: workspace.sidebar_visible,
+            preview_visible: workspace.preview_visible,
+            console_visible: workspace.console_visible,
+        }
+    }
+
+    /// Reopen the workspace; documents whose file and edits are both gone are skipped
+    pub fn restore(&self, workspace_id: WorkspaceId) -> WorkspaceState {
+        let mut workspace = WorkspaceState::new(workspace_id);
+        workspace.root = self.root.clone();
+        workspace.sidebar_visible = self.sidebar_visible;
+        workspace.preview_visible = self.preview_visible;
+        workspace.console_visible = self.console_visible;
+
+        let mut active = None;
+        for (index, session) in self.documents.iter().enumerate() {
+            let Some(editor) = session.restore() else {
+                continue;
+            };
+            let id = workspace.open_editor(editor);
+            if self.active_document == Some(index) {
+                active = Some(id);
+            }
+        }
+        workspace.active_document = active.or_else(|| workspace.open_documents.keys().next().copied());
+        workspace
+    }
+}
+
+impl DocumentSession {
+    pub fn capture(editor: &EditorState) -> Self {
+        let document = &editor.document;
+        Self {
+            path: document.path.clone(),
+            unsaved_content: (document.is_dirty || document.path.is_none()).then(|| editor.content.clone()),
+            modified_time: document.modified_time,
+            encoding: document.encoding,
+            line_ending: document.line_ending,
+            cursors: editor.cursors.cursors().to_vec(),
+            scroll_offset: editor.scroll_offset,
+        }
+    }
+
+    fn restore(&self) -> Option<EditorState> {
+        let loaded = self.path.as_deref().and_then(|path| Document::load(path).ok());
+        let (document, content) = match (loaded, &self.unsaved_content) {
+            (Some(loaded), None) => loaded,
+            (Some((mut document, _)), Some(content)) => {
+                // Keep the time the edits were based on, so a file changed
+                // since then is reported as a conflict
+                document.modified_time = self.modified_time;
+                document.encoding = self.encoding;
+                document.line_ending = self.line_ending;
+                document.mark_dirty();
+                (document, content.clone())
+            }
+            (None, Some(content)) => {
+                let mut document = Document::new(self.path.clone());
+                document.encoding = self.encoding;
+                document.line_ending = self.line_ending;
+                // An empty untitled document has nothing to save
+                if document.path.is_some() || !content.is_empty() {
+                    document.mark_dirty();
+                }
+                (document, content.clone())
+            }
+            (None, None) => return None,
+        };
+
+        let mut editor = EditorState::new(document);
+        editor.content = content;
+        // Cursor offsets count characters
+        let len = editor.content.chars().count();
+        let cursors: Vec<Cursor> = self.cursors
+            .iter()
+            .map(|cursor| Cursor::with_selection(cursor.anchor.min(len), cursor.head.min(len)))
+            .collect();
+        if !cursors.is_empty() {
+            editor.cursors = MultiCursor::from_cursors(cursors);
+        }
+        editor.scroll_offset = self.scroll_offset;
+        Some(editor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ExternalChange;
+
+    fn editor(path: &Path, cursor: Cursor) -> EditorState {
+        let (document, content) = Document::load(path).unwrap();
+        let mut editor = EditorState::new(document);
+        editor.content = content;
+        editor.cursors = MultiCursor::from_cursors(vec![cursor]);
+        editor
+    }
+
+    /// Capture a workspace and restore it after a trip through the session file
+    fn round_trip(workspace: &WorkspaceState) -> WorkspaceState {
+        let session = WorkspaceSession::capture(workspace);
+        let json = serde_json::to_string(&session).unwrap();
+        serde_json::from_str::<WorkspaceSession>(&json).unwrap().restore(1)
+    }
+
+    fn restored<'a>(workspace: &'a WorkspaceState, path: &Path) -> parking_lot::RwLockReadGuard<'a, EditorState> {
+        let id = workspace.find_document(path).unwrap();
+        workspace.open_documents[&id].read()
+    }
+
+    #[test]
+    fn restores_unsaved_edits_and_clamps_cursors() {
+        let root = std::env::temp_dir().join(format!("typst-studio-session-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let edited = root.join("edited.typ");
+        let shrunk = root.join("shrunk.typ");
+        std::fs::write(&edited, "saved").unwrap();
+        std::fs::write(&shrunk, "a long line of text").unwrap();
+
+        let mut workspace = WorkspaceState::new(0);
+        workspace.root = Some(root.clone());
+        workspace.console_visible = true;
+        let mut edited_editor = editor(&edited, Cursor::with_selection(1, 3));
+        edited_editor.set_content("unsaved edits".to_string());
+        workspace.open_editor(edited_editor);
+        let active = workspace.open_editor(editor(&shrunk, Cursor::new(15)));
+        let mut untitled = EditorState::new(Document::new(None));
+        untitled.set_content("scratch".to_string());
+        workspace.open_editor(untitled);
+        workspace.active_document = Some(active);
+
+        // The file of a clean document is the source of truth on restore
+        std::fs::write(&shrunk, "short").unwrap();
+        let restored_workspace = round_trip(&workspace);
+        assert_eq!(restored_workspace.root.as_deref(), Some(root.as_path()));
+        assert!(restored_workspace.console_visible);
+        assert_eq!(restored_workspace.open_documents.len(), 3);
+
+        let editor = restored(&restored_workspace, &edited);
+        assert_eq!(editor.content, "unsaved edits");
+        assert!(editor.document.is_dirty);
+        assert_eq!(editor.cursors.cursors(), [Cursor::with_selection(1, 3)]);
+
+        let editor = restored(&restored_workspace, &shrunk);
+        assert_eq!(editor.content, "short");
+        assert!(!editor.document.is_dirty);
+        assert_eq!(editor.cursors.cursors(), [Cursor::new(5)]);
+        assert_eq!(restored_workspace.active_document, Some(editor.document.id));
+
+        let untitled = restored_workspace
+            .open_documents
+            .values()
+            .find(|editor| editor.read().document.path.is_none())
+            .unwrap();
+        assert_eq!(untitled.read().content, "scratch");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn files_changed_since_the_edits_are_conflicts() {
+        let root = std::env::temp_dir().join(format!("typst-studio-session-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("main.typ");
+        std::fs::write(&path, "saved").unwrap();
+
+        let mut workspace = WorkspaceState::new(0);
+        let mut editor = editor(&path, Cursor::new(0));
+        editor.set_content("unsaved".to_string());
+        workspace.open_editor(editor);
+        let session = WorkspaceSession::capture(&workspace);
+
+        let restored_workspace = session.restore(1);
+        assert!(!restored(&restored_workspace, &path).document.is_modified_on_disk());
+
+        // Another program writes the file while the editor is closed
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        let restored_workspace = session.restore(1);
+        let editor = restored(&restored_workspace, &path);
+        assert_eq!(editor.content, "unsaved");
+        assert!(editor.document.is_modified_on_disk());
+        assert!(matches!(restored_workspace.external_change(&path), Some(ExternalChange::Conflict(_))));
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn skips_documents_whose_file_and_edits_are_gone() {
+        let root = std::env::temp_dir().join(format!("typst-studio-session-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("deleted.typ");
+        std::fs::write(&path, "saved").unwrap();
+
+        let mut workspace = WorkspaceState::new(0);
+        workspace.open_editor(editor(&path, Cursor::new(0)));
+        let session = WorkspaceSession::capture(&workspace);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let restored_workspace = session.restore(1);
+        assert!(restored_workspace.open_documents.is_empty());
+        assert_eq!(restored_workspace.active_document, None);
+    }
+}
@@ -1,10 +1,13 @@
+use crate::buffer::{RopeBuffer, TextBuffer};
 use crate::config::Config;
 use crate::document::{Document, DocumentId};
 use crate::selection::MultiCursor;
+use anyhow::Result;
 use parking_lot::RwLock;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub type WindowId = usize;
 pub type WorkspaceId = usize;
@@ -45,6 +48,27 @@ impl ApplicationState {
     }
 }
 
+/// An open document whose file changed on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternalChange {
+    /// Changed while the document had no edits; safe to reload
+    Modified(DocumentId),
+    /// Changed while the document had unsaved edits
+    Conflict(DocumentId),
+    /// Deleted or moved away
+    Removed(DocumentId),
+}
+
+/// Outcome of auto-saving a workspace's documents
+#[derive(Debug, Default)]
+pub struct AutoSaveReport {
+    pub saved: Vec<PathBuf>,
+    /// Documents whose file changed on disk since it was loaded; left unsaved
+    /// so the external change can be resolved first
+    pub conflicts: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, anyhow::Error)>,
+}
+
 #[derive(Clone)]
 pub struct WorkspaceState {
     pub workspace_id: WorkspaceId,
@@ -70,14 +94,144 @@ impl WorkspaceState {
     }
 
     pub fn open_document(&mut self, document: Document) -> DocumentId {
-        let id = document.id;
-        let editor_state = EditorState::new(document);
+        self.open_editor(EditorState::new(document))
+    }
+
+    /// Open a document together with its content and cursors
+    pub fn open_editor(&mut self, editor_state: EditorState) -> DocumentId {
+        let id = editor_state.document.id;
         self.open_documents
             .insert(id, Arc::new(RwLock::new(editor_state)));
         self.active_document = Some(id);
         id
     }
 
+    /// Find an open document by its path
+    pub fn find_document(&self, path: &Path) -> Option<DocumentId> {
+        self.open_documents
+            .iter()
+            .find(|(_, editor)| editor.read().document.path.as_deref() == Some(path))
+            .map(|(id, _)| *id)
+    }
+
+    /// Open a file (or activate it if already open) and move the cursor to a
+    /// zero-based line and column
+    pub fn open_file_at(&mut self, path: &Path, line: usize, column: usize) -> Result<DocumentId> {
+        let id = match self.find_document(path) {
+            Some(id) => id,
+            None => {
+                let (document, content) = Document::load(path)?;
+                let id = self.open_document(document);
+                if let Some(editor) = self.open_documents.get(&id) {
+                    editor.write().content = content;
+                }
+                id
+            }
+        };
+
+        self.active_document = Some(id);
+        if let Some(editor) = self.open_documents.get(&id) {
+            editor.write().set_cursor_position(line, column);
+        }
+        Ok(id)
+    }
+
+    /// How an open document is affected by its file changing on disk
+    pub fn external_change(&self, path: &Path) -> Option<ExternalChange> {
+        let id = self.find_document(path)?;
+        let editor = self.open_documents.get(&id)?.read();
+        let document = &editor.document;
+        if document.path.as_deref().is_some_and(|path| !path.exists()) {
+            return Some(ExternalChange::Removed(id));
+        }
+        if !document.is_modified_on_disk() {
+            return None;
+        }
+        Some(if document.is_dirty { ExternalChange::Conflict(id) } else { ExternalChange::Modified(id) })
+    }
+
+    /// Replace a document's content with the file on disk, discarding edits
+    pub fn reload_document(&mut self, id: DocumentId) -> Result<()> {
+        let Some(editor) = self.open_documents.get(&id) else {
+            return Ok(());
+        };
+        let mut editor = editor.write();
+        let Some(path) = editor.document.path.clone() else {
+            return Ok(());
+        };
+        let (document, content) = Document::load(&path)?;
+        editor.document.encoding = document.encoding;
+        editor.document.line_ending = document.line_ending;
+        editor.document.mixed_line_endings = document.mixed_line_endings;
+        editor.document.modified_time = document.modified_time;
+        editor.document.is_read_only = document.is_read_only;
+        editor.document.mark_clean();
+        editor.document.increment_version();
+        editor.content = content;
+        Ok(())
+    }
+
+    /// Keep the edited content over a changed file, without asking again until
+    /// the file changes once more
+    pub fn keep_local_changes(&mut self, id: DocumentId) {
+        if let Some(editor) = self.open_documents.get(&id) {
+            let mut editor = editor.write();
+            editor.document.modified_time = editor.document.disk_modified_time();
+            editor.document.mark_dirty();
+        }
+    }
+
+    /// Save every edited document that has been idle for at least `idle`
+    ///
+    /// Untitled and read-only documents are skipped, as are documents whose
+    /// file changed on disk, which would otherwise be overwritten.
+    pub fn auto_save(&mut self, idle: Duration) -> AutoSaveReport {
+        let mut report = AutoSaveReport::default();
+        for editor in self.open_documents.values() {
+            let mut editor = editor.write();
+            if !editor.can_auto_save() || editor.last_edited.is_some_and(|edited| edited.elapsed() < idle) {
+                continue;
+            }
+            let Some(path) = editor.document.path.clone() else {
+                continue;
+            };
+            if editor.document.is_modified_on_disk() {
+                report.conflicts.push(path);
+                continue;
+            }
+            match editor.save() {
+                Ok(()) => report.saved.push(path),
+                Err(err) => {
+                    // Wait for another idle period before trying again
+                    editor.last_edited = Some(Instant::now());
+                    report.failed.push((path, err));
+                }
+            }
+        }
+        report
+    }
+
+    /// Unsaved contents of edited documents, by path, for compiling what the
+    /// user sees rather than what is on disk
+    pub fn unsaved_buffers(&self) -> HashMap<PathBuf, String> {
+        self.open_documents
+            .values()
+            .filter_map(|editor| {
+                let editor = editor.read();
+                let path = editor.document.path.clone()?;
+                editor.document.is_dirty.then(|| (path, editor.content.clone()))
+            })
+            .collect()
+    }
+
+    /// When any open document was last changed by the user
+    pub fn last_edited(&self) -> Option<Instant> {
+        self.open_documents
+            .values()
+            .filter_map(|editor| editor.read().last_edited)
+            .max()
+    }
+
     pub fn get_active_editor(&self) -> Option<Arc<RwLock<EditorState>>> {
         self.active_document
             .and_then(|id| self.open_documents.get(&id))
@@ -98,6 +252,8 @@ pub struct EditorState {
     pub content: String,
     pub cursors: MultiCursor,
     pub scroll_offset: f32,
+    /// When the content was last changed by the user
+    pub last_edited: Option<Instant>,
 }
 
 impl EditorState {
@@ -107,12 +263,156 @@ impl EditorState {
             content: String::new(),
             cursors: MultiCursor::default(),
             scroll_offset: 0.0,
+            last_edited: None,
         }
     }
 
     pub fn set_content(&mut self, content: String) {
         self.content = content;
         self.document.mark_dirty();
+        self.last_edited = Some(Instant::now());
+    }
+
+    /// Whether the document has edits that auto-save may write
+    pub fn can_auto_save(&self) -> bool {
+        self.document.is_dirty && self.document.path.is_some() && !self.document.is_read_only
+    }
+
+    /// Write the content to the document's file
+    pub fn save(&mut self) -> Result<()> {
+        self.document.save(&self.content)
+    }
+
+    /// Zero-based line and column of the primary cursor
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let buffer = RopeBuffer::new(&self.content);
+        let offset = self.cursors.primary_cursor().position().min(buffer.len());
+        buffer.offset_to_line_col(offset)
+    }
+
+    /// Collapse the cursors to a single one at a zero-based line and column,
+    /// clamped to the content
+    pub fn set_cursor_position(&mut self, line: usize, column: usize) {
+        let buffer = RopeBuffer::new(&self.content);
+        let offset = match buffer.line_range(line) {
+            Some(range) => range.start + column.min(range.len()),
+            None => buffer.len(),
+        };
+        self.cursors = MultiCursor::new(offset);
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("typst-studio-state-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Open a file and replace its content, as typing would
+    fn open_edited(workspace: &mut WorkspaceState, path: &Path, content: &str) -> DocumentId {
+        let (document, _content) = Document::load(path).unwrap();
+        let mut editor = EditorState::new(document);
+        editor.set_content(content.to_string());
+        workspace.open_editor(editor)
+    }
+
+    /// Pretend another program wrote the file
+    fn touch(path: &Path) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(std::time::SystemTime::now() + Duration::from_secs(60)).unwrap();
+    }
+
+    #[test]
+    fn auto_save_skips_untitled_read_only_and_recent_documents() {
+        let directory = temp_dir();
+        let edited = directory.join("edited.typ");
+        let locked = directory.join("locked.typ");
+        let recent = directory.join("recent.typ");
+        for path in [&edited, &locked, &recent] {
+            std::fs::write(path, "saved").unwrap();
+        }
+
+        let mut workspace = WorkspaceState::new(0);
+        let edited_id = open_edited(&mut workspace, &edited, "auto-saved");
+        let locked_id = open_edited(&mut workspace, &locked, "edited");
+        workspace.open_documents[&locked_id].write().document.is_read_only = true;
+        let recent_id = open_edited(&mut workspace, &recent, "still typing");
+        let mut untitled = EditorState::new(Document::new(None));
+        untitled.set_content("scratch".to_string());
+        let untitled_id = workspace.open_editor(untitled);
+        for id in [edited_id, locked_id, untitled_id] {
+            workspace.open_documents[&id].write().last_edited = Some(Instant::now() - Duration::from_secs(10));
+        }
+
+        let report = workspace.auto_save(Duration::from_secs(5));
+        assert_eq!(report.saved, vec![edited.clone()]);
+        assert!(report.conflicts.is_empty() && report.failed.is_empty());
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "auto-saved");
+        assert!(!workspace.open_documents[&edited_id].read().document.is_dirty);
+        for (id, path) in [(locked_id, &locked), (recent_id, &recent)] {
+            assert!(workspace.open_documents[&id].read().document.is_dirty);
+            assert_eq!(std::fs::read_to_string(path).unwrap(), "saved");
+        }
+        assert!(workspace.open_documents[&untitled_id].read().document.is_dirty);
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn auto_save_leaves_files_changed_on_disk_alone() {
+        let directory = temp_dir();
+        let path = directory.join("main.typ");
+        std::fs::write(&path, "saved").unwrap();
+
+        let mut workspace = WorkspaceState::new(0);
+        let id = open_edited(&mut workspace, &path, "edited");
+        std::fs::write(&path, "written elsewhere").unwrap();
+        touch(&path);
+
+        let report = workspace.auto_save(Duration::ZERO);
+        assert!(report.saved.is_empty());
+        assert_eq!(report.conflicts, vec![path.clone()]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "written elsewhere");
+        assert_eq!(workspace.external_change(&path), Some(ExternalChange::Conflict(id)));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn external_changes_depend_on_unsaved_edits() {
+        let directory = temp_dir();
+        let clean = directory.join("clean.typ");
+        let edited = directory.join("edited.typ");
+        std::fs::write(&clean, "saved").unwrap();
+        std::fs::write(&edited, "saved").unwrap();
+
+        let mut workspace = WorkspaceState::new(0);
+        let (document, content) = Document::load(&clean).unwrap();
+        let mut editor = EditorState::new(document);
+        editor.content = content;
+        let clean_id = workspace.open_editor(editor);
+        let edited_id = open_edited(&mut workspace, &edited, "edited");
+        assert_eq!(workspace.external_change(&clean), None);
+        assert_eq!(workspace.external_change(&edited), None);
+
+        std::fs::write(&clean, "changed").unwrap();
+        touch(&clean);
+        touch(&edited);
+        assert_eq!(workspace.external_change(&clean), Some(ExternalChange::Modified(clean_id)));
+        assert_eq!(workspace.external_change(&edited), Some(ExternalChange::Conflict(edited_id)));
+
+        workspace.reload_document(clean_id).unwrap();
+        assert_eq!(workspace.open_documents[&clean_id].read().content, "changed");
+        assert_eq!(workspace.external_change(&clean), None);
+        workspace.keep_local_changes(edited_id);
+        assert_eq!(workspace.open_documents[&edited_id].read().content, "edited");
+        assert_eq!(workspace.external_change(&edited), None);
+
+        std::fs::remove_file(&edited).unwrap();
+        assert_eq!(workspace.external_change(&edited), Some(ExternalChange::Removed(edited_id)));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,4 +1,14 @@
 // Preview rendering module - to be implemented in Phase 7
+pub mod links;
 pub mod renderer;
+pub mod search;
+pub mod sync;
+pub mod text;
+pub mod viewport;
 
+pub use links::{ DocumentLinks, LinkTarget, OutlineItem };
 pub use renderer::PdfRenderer;
+pub use search::{ PreviewSearch, SearchQuery };
+pub use sync::{ SourceMapping, SyncManager };
+pub use text::{ DocumentText, TextSelection };
+pub use viewport::{ PageSize, Viewport };
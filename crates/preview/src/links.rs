@@ -0,0 +1,161 @@
+//! Hyperlinks and heading outline of a compiled document
+
+use crate::sync::{ PreviewPosition, PreviewRect };
+use serde::{ Deserialize, Serialize };
+
+/// Where a link leads
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LinkTarget {
+    /// External URL, opened in the system browser
+    Url(String),
+    /// Position inside the document (labels, cross-references, footnotes)
+    Position(PreviewPosition),
+}
+
+/// A clickable area of a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreviewLink {
+    pub page: usize,
+    pub rect: PreviewRect,
+    pub target: LinkTarget,
+}
+
+/// All links of a compiled document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentLinks {
+    pub links: Vec<PreviewLink>,
+}
+
+impl DocumentLinks {
+    pub fn new(links: Vec<PreviewLink>) -> Self {
+        Self { links }
+    }
+
+    pub fn on_page(&self, page: usize) -> impl Iterator<Item = &PreviewLink> {
+        self.links.iter().filter(move |link| link.page == page)
+    }
+
+    /// The smallest link containing a point on a page
+    pub fn hit_test(&self, page: usize, x: f32, y: f32) -> Option<&PreviewLink> {
+        self.on_page(page)
+            .filter(|link| link.rect.contains(x, y))
+            .min_by(|a, b| a.rect.area().total_cmp(&b.rect.area()))
+    }
+}
+
+/// A heading of the document with its nested subheadings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutlineItem {
+    pub title: String,
+    /// Heading level, starting at 1
+    pub level: usize,
+    pub position: PreviewPosition,
+    pub children: Vec<OutlineItem>,
+}
+
+impl OutlineItem {
+    pub fn new(title: impl Into<String>, level: usize, position: PreviewPosition) -> Self {
+        Self {
+            title: title.into(),
+            level,
+            position,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Nest a flat list of headings in document order into a tree by level
+///
+/// A heading becomes a child of the closest preceding heading with a lower
+/// level, so skipped levels (a level 3 directly under a level 1) still nest.
+pub fn build_outline(headings: Vec<OutlineItem>) -> Vec<OutlineItem> {
+    let mut roots: Vec<OutlineItem> = Vec::new();
+    let mut stack: Vec<OutlineItem> = Vec::new();
+
+    for heading in headings {
+        while stack.last().is_some_and(|open| open.level >= heading.level) {
+            close_top(&mut stack, &mut roots);
+        }
+        stack.push(heading);
+    }
+    while !stack.is_empty() {
+        close_top(&mut stack, &mut roots);
+    }
+    roots
+}
+
+/// Pop the innermost open heading and attach it to its parent or the roots
+fn close_top(stack: &mut Vec<OutlineItem>, roots: &mut Vec<OutlineItem>) {
+    if let Some(item) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(item),
+            None => roots.push(item),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heading(title: &str, level: usize) -> OutlineItem {
+        OutlineItem::new(title, level, PreviewPosition { page: 0, x: 0.0, y: 0.0 })
+    }
+
+    /// Titles of an outline, indented by nesting depth
+    fn titles(items: &[OutlineItem], depth: usize, out: &mut Vec<String>) {
+        for item in items {
+            out.push(format!("{}{}", "  ".repeat(depth), item.title));
+            titles(&item.children, depth + 1, out);
+        }
+    }
+
+    fn outline(headings: &[(&str, usize)]) -> Vec<String> {
+        let headings = headings.iter().map(|(title, level)| heading(title, *level)).collect();
+        let mut out = Vec::new();
+        titles(&build_outline(headings), 0, &mut out);
+        out
+    }
+
+    #[test]
+    fn nests_headings_by_level() {
+        let tree = outline(&[("Intro", 1), ("Goals", 2), ("Scope", 2), ("Method", 1), ("Data", 2)]);
+        assert_eq!(tree, ["Intro", "  Goals", "  Scope", "Method", "  Data"]);
+    }
+
+    #[test]
+    fn nests_skipped_levels_under_the_closest_lower_heading() {
+        let tree = outline(&[("Intro", 1), ("Detail", 3), ("Aside", 2), ("Deeper", 4)]);
+        assert_eq!(tree, ["Intro", "  Detail", "  Aside", "    Deeper"]);
+    }
+
+    #[test]
+    fn headings_before_the_first_top_level_heading_stay_roots() {
+        let tree = outline(&[("Preface", 2), ("Intro", 1), ("Goals", 2)]);
+        assert_eq!(tree, ["Preface", "Intro", "  Goals"]);
+        assert!(build_outline(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn hit_test_picks_the_smallest_link_on_the_page() {
+        let link = |page, rect, target: &str| PreviewLink {
+            page,
+            rect,
+            target: LinkTarget::Url(target.to_string()),
+        };
+        let links = DocumentLinks::new(vec![
+            link(0, PreviewRect::new(0.0, 0.0, 100.0, 20.0), "outer"),
+            link(0, PreviewRect::new(10.0, 5.0, 20.0, 10.0), "inner"),
+            link(1, PreviewRect::new(0.0, 0.0, 5.0, 5.0), "other page"),
+        ]);
+        let target = |page, x, y| links.hit_test(page, x, y).map(|link| link.target.clone());
+        let url = |url: &str| Some(LinkTarget::Url(url.to_string()));
+
+        assert_eq!(target(0, 15.0, 10.0), url("inner"));
+        assert_eq!(target(0, 50.0, 10.0), url("outer"));
+        assert_eq!(target(0, 50.0, 30.0), None);
+        assert_eq!(target(1, 2.0, 2.0), url("other page"));
+        assert_eq!(target(1, 15.0, 10.0), None);
+        assert_eq!(target(2, 2.0, 2.0), None);
+    }
+}
@@ -0,0 +1,322 @@
+//! Find-in-preview over the text of compiled pages
+
+use crate::sync::PreviewRect;
+use crate::text::{ DocumentText, TextPosition, TextSelection };
+use crate::viewport::Viewport;
+
+/// Hyphen only shown where line breaking splits a word; inserted hyphen
+/// glyphs, which have no text of their own, are indexed as one too. Soft
+/// hyphens are kept only until the next character shows they did not end a line.
+const SOFT_HYPHEN: char = '\u{00ad}';
+
+/// Hyphens written in the text, which stay part of the word at a line break
+const HARD_HYPHENS: [char; 2] = ['-', '\u{2010}'];
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SearchQuery {
+    pub text: String,
+    pub case_sensitive: bool,
+}
+
+impl SearchQuery {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            case_sensitive: false,
+        }
+    }
+}
+
+/// One occurrence of the query and the rectangles it covers on each page
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    pub selection: TextSelection,
+    pub rects: Vec<(usize, PreviewRect)>,
+}
+
+/// The document text flattened into one searchable character sequence
+struct SearchIndex {
+    chars: Vec<char>,
+    /// Glyph each character belongs to; `None` for inserted separators
+    origins: Vec<Option<TextPosition>>,
+}
+
+impl SearchIndex {
+    fn new(document: &DocumentText, case_sensitive: bool) -> Self {
+        let mut index = Self {
+            chars: Vec::new(),
+            origins: Vec::new(),
+        };
+
+        let mut previous: Option<(usize, f32)> = None;
+        for (page, page_text) in document.pages.iter().enumerate() {
+            for (run_index, run) in page_text.runs.iter().enumerate() {
+                if let Some((previous_page, baseline)) = previous {
+                    let new_line = previous_page != page || (baseline - run.baseline).abs() >= 1.0;
+                    let last = index.chars.last().copied();
+                    if new_line && last == Some(SOFT_HYPHEN) {
+                        // The word was split by line breaking, so join both halves
+                        index.chars.pop();
+                        index.origins.pop();
+                    } else if !(new_line && last.is_some_and(|c| HARD_HYPHENS.contains(&c))) {
+                        // A compound such as "well-known" continues without a space
+                        index.push_separator();
+                    }
+                }
+
+                for (glyph_index, glyph) in run.glyphs.iter().enumerate() {
+                    let position = TextPosition { page, run: run_index, glyph: glyph_index };
+                    // Hyphens inserted by line breaking have no text of their own
+                    if glyph.range.is_empty() {
+                        index.push(SOFT_HYPHEN, position);
+                        continue;
+                    }
+                    // Ligatures cover several characters, all mapped to the same glyph
+                    for c in run.text.get(glyph.range.clone()).unwrap_or("").chars() {
+                        if c.is_whitespace() {
+                            index.push_separator();
+                        } else if case_sensitive {
+                            index.push(c, position);
+                        } else {
+                            for lower in c.to_lowercase() {
+                                index.push(lower, position);
+                            }
+                        }
+                    }
+                }
+                previous = Some((page, run.baseline));
+            }
+        }
+        index.drop_soft_hyphen();
+        index
+    }
+
+    fn push(&mut self, c: char, origin: TextPosition) {
+        if c != SOFT_HYPHEN {
+            self.drop_soft_hyphen();
+        }
+        self.chars.push(c);
+        self.origins.push(Some(origin));
+    }
+
+    /// Collapse whitespace and run boundaries into a single space
+    fn push_separator(&mut self) {
+        self.drop_soft_hyphen();
+        if self.chars.last().is_some_and(|&c| c != ' ') {
+            self.chars.push(' ');
+            self.origins.push(None);
+        }
+    }
+
+    /// Remove a soft hyphen that turned out not to end a line
+    fn drop_soft_hyphen(&mut self) {
+        if self.chars.last() == Some(&SOFT_HYPHEN) {
+            self.chars.pop();
+            self.origins.pop();
+        }
+    }
+
+    fn find_all(&self, needle: &[char]) -> Vec<(TextPosition, TextPosition)> {
+        if needle.is_empty() || needle.len() > self.chars.len() {
+            return Vec::new();
+        }
+
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start + needle.len() <= self.chars.len() {
+            if self.chars[start..start + needle.len()] == *needle {
+                let origins = &self.origins[start..start + needle.len()];
+                let first = origins.iter().flatten().next();
+                let last = origins.iter().flatten().next_back();
+                if let (Some(first), Some(last)) = (first, last) {
+                    let end = TextPosition { glyph: last.glyph + 1, ..*last };
+                    matches.push((*first, end));
+                }
+                start += needle.len();
+            } else {
+                start += 1;
+            }
+        }
+        matches
+    }
+}
+
+/// Search state with the matches of the current query and the selected one
+#[derive(Debug, Clone, Default)]
+pub struct PreviewSearch {
+    query: SearchQuery,
+    matches: Vec<SearchMatch>,
+    current: Option<usize>,
+}
+
+impl PreviewSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run a query against the document, replacing previous matches
+    pub fn search(&mut self, document: &DocumentText, query: SearchQuery) -> &[SearchMatch] {
+        let index = SearchIndex::new(document, query.case_sensitive);
+        let normalized = query.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let needle: Vec<char> = if query.case_sensitive {
+            normalized.chars().collect()
+        } else {
+            normalized.to_lowercase().chars().collect()
+        };
+
+        self.matches = index
+            .find_all(&needle)
+            .into_iter()
+            .map(|(anchor, head)| {
+                let selection = TextSelection { anchor, head };
+                SearchMatch {
+                    rects: selection.page_rects(document),
+                    selection,
+                }
+            })
+            .collect();
+        self.current = None;
+        self.query = query;
+        &self.matches
+    }
+
+    /// Re-run the current query after the document was recompiled
+    pub fn refresh(&mut self, document: &DocumentText) {
+        let current = self.current;
+        self.search(document, self.query.clone());
+        self.current = current.filter(|&index| index < self.matches.len());
+    }
+
+    pub fn clear(&mut self) {
+        self.query = SearchQuery::default();
+        self.matches.clear();
+        self.current = None;
+    }
+
+    pub fn query(&self) -> &SearchQuery {
+        &self.query
+    }
+
+    pub fn matches(&self) -> &[SearchMatch] {
+        &self.matches
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<&SearchMatch> {
+        self.current.and_then(|index| self.matches.get(index))
+    }
+
+    /// Select the next match, wrapping around, and scroll the viewport to it
+    pub fn next(&mut self, viewport: &mut Viewport) -> Option<&SearchMatch> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let index = self.current.map_or(0, |index| (index + 1) % self.matches.len());
+        self.select(index, viewport)
+    }
+
+    /// Select the previous match, wrapping around, and scroll the viewport to it
+    pub fn previous(&mut self, viewport: &mut Viewport) -> Option<&SearchMatch> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let len = self.matches.len();
+        let index = self.current.map_or(len - 1, |index| (index + len - 1) % len);
+        self.select(index, viewport)
+    }
+
+    fn select(&mut self, index: usize, viewport: &mut Viewport) -> Option<&SearchMatch> {
+        self.current = Some(index);
+        let found = &self.matches[index];
+        if let Some((page, rect)) = found.rects.first() {
+            viewport.reveal(*page, rect);
+        }
+        Some(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{ GlyphBox, PageText, TextRun };
+
+    /// A line of text with one glyph per character, ten points apart
+    fn line(text: &str, baseline: f32) -> TextRun {
+        let glyphs = text
+            .char_indices()
+            .enumerate()
+            .map(|(index, (offset, c))| GlyphBox {
+                rect: PreviewRect::new(index as f32 * 10.0, baseline - 10.0, 10.0, 12.0),
+                range: offset..offset + c.len_utf8(),
+            })
+            .collect();
+        TextRun { text: text.to_string(), baseline, glyphs }
+    }
+
+    fn document(lines: &[&str]) -> DocumentText {
+        let runs = lines
+            .iter()
+            .enumerate()
+            .map(|(index, text)| line(text, 20.0 + index as f32 * 20.0))
+            .collect();
+        DocumentText { pages: vec![PageText { runs }] }
+    }
+
+    fn count(document: &DocumentText, query: &str) -> usize {
+        PreviewSearch::new().search(document, SearchQuery::new(query)).len()
+    }
+
+    #[test]
+    fn keeps_hyphens_written_at_line_ends() {
+        let document = document(&["a well-", "known fact"]);
+        assert_eq!(count(&document, "well-known"), 1);
+        assert_eq!(count(&document, "wellknown"), 0);
+    }
+
+    #[test]
+    fn joins_words_split_by_soft_hyphens() {
+        let document = document(&["hyphen\u{00ad}", "ation"]);
+        assert_eq!(count(&document, "hyphenation"), 1);
+    }
+
+    #[test]
+    fn ignores_soft_hyphens_within_lines() {
+        let document = document(&["hyphen\u{00ad}ation and soft\u{00ad} words\u{00ad}"]);
+        assert_eq!(count(&document, "hyphenation"), 1);
+        assert_eq!(count(&document, "soft words"), 1);
+        assert_eq!(count(&document, "words"), 1);
+        assert_eq!(count(&document, "\u{00ad}"), 0);
+    }
+
+    #[test]
+    fn joins_words_split_by_inserted_hyphens() {
+        let mut split = line("hyphen", 20.0);
+        // Line breaking adds a hyphen glyph without text
+        split.glyphs.push(GlyphBox {
+            rect: PreviewRect::new(60.0, 10.0, 5.0, 12.0),
+            range: 6..6,
+        });
+        let document = DocumentText {
+            pages: vec![PageText { runs: vec![split, line("ation", 40.0)] }],
+        };
+        assert_eq!(count(&document, "hyphenation"), 1);
+    }
+
+    #[test]
+    fn separates_lines_with_a_space() {
+        let document = document(&["first", "second"]);
+        assert_eq!(count(&document, "first second"), 1);
+        assert_eq!(count(&document, "firstsecond"), 0);
+    }
+
+    #[test]
+    fn matches_ignore_case_unless_asked() {
+        let document = document(&["Typst and typst"]);
+        assert_eq!(count(&document, "TYPST"), 2);
+        let query = SearchQuery { text: "Typst".to_string(), case_sensitive: true };
+        assert_eq!(PreviewSearch::new().search(&document, query).len(), 1);
+    }
+}
@@ -1,16 +1,30 @@
 //! Source-preview synchronization
 
+use crate::viewport::Viewport;
 use serde::{ Deserialize, Serialize };
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{ BTreeMap, HashMap };
+use std::path::{ Path, PathBuf };
+use std::time::{ Duration, Instant };
+
+/// Size of a spatial index cell, in points
+const CELL_SIZE: f32 = 32.0;
+
+/// How long a revealed region stays highlighted
+pub const HIGHLIGHT_DURATION: Duration = Duration::from_millis(1500);
 
 /// Position in source code
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct SourcePosition {
     pub line: usize,
     pub column: usize,
 }
 
+impl SourcePosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
 /// Position in preview (page and coordinates)
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct PreviewPosition {
@@ -19,106 +33,411 @@ pub struct PreviewPosition {
     pub y: f32,
 }
 
+/// Rectangle on a page, in points from the page's top-left corner
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct PreviewRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl PreviewRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Smallest rectangle containing all given points
+    pub fn from_points(points: impl IntoIterator<Item = (f32, f32)>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let (x, y) = points.next()?;
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (x, y, x, y);
+        for (x, y) in points {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        Some(Self::new(min_x, min_y, max_x - min_x, max_y - min_y))
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    pub fn area(&self) -> f32 {
+        self.width * self.height
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.right() && y >= self.y && y <= self.bottom()
+    }
+
+    /// Distance from a point to the closest point of the rectangle (zero inside)
+    pub fn distance_to(&self, x: f32, y: f32) -> f32 {
+        let dx = (self.x - x).max(0.0).max(x - self.right());
+        let dy = (self.y - y).max(0.0).max(y - self.bottom());
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    /// Smallest rectangle containing both rectangles
+    pub fn union(&self, other: &PreviewRect) -> PreviewRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        PreviewRect::new(x, y, self.right().max(other.right()) - x, self.bottom().max(other.bottom()) - y)
+    }
+}
+
+/// A region of a compiled page produced by a range of source text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncEntry {
+    pub file: PathBuf,
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+    pub page: usize,
+    pub rect: PreviewRect,
+}
+
+impl SyncEntry {
+    pub fn preview_position(&self) -> PreviewPosition {
+        PreviewPosition {
+            page: self.page,
+            x: self.rect.x,
+            y: self.rect.y,
+        }
+    }
+
+    fn contains_source(&self, pos: SourcePosition) -> bool {
+        self.start <= pos && pos <= self.end
+    }
+}
+
+/// Uniform grid over one page, mapping cells to the entries overlapping them
+#[derive(Clone, Default)]
+struct PageIndex {
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    min_cell: (i32, i32),
+    max_cell: (i32, i32),
+}
+
+impl PageIndex {
+    fn cell_of(x: f32, y: f32) -> (i32, i32) {
+        ((x / CELL_SIZE).floor() as i32, (y / CELL_SIZE).floor() as i32)
+    }
+
+    fn insert(&mut self, index: usize, rect: &PreviewRect) {
+        let (x0, y0) = Self::cell_of(rect.x, rect.y);
+        let (x1, y1) = Self::cell_of(rect.right(), rect.bottom());
+        if self.cells.is_empty() {
+            self.min_cell = (x0, y0);
+            self.max_cell = (x1, y1);
+        } else {
+            self.min_cell = (self.min_cell.0.min(x0), self.min_cell.1.min(y0));
+            self.max_cell = (self.max_cell.0.max(x1), self.max_cell.1.max(y1));
+        }
+        for cx in x0..=x1 {
+            for cy in y0..=y1 {
+                self.cells.entry((cx, cy)).or_default().push(index);
+            }
+        }
+    }
+
+    /// Find the entry closest to a point, searching rings of cells outwards
+    fn nearest(&self, entries: &[SyncEntry], x: f32, y: f32) -> Option<usize> {
+        if self.cells.is_empty() {
+            return None;
+        }
+
+        // Start from the indexed cell closest to the point, so points far off the page don't
+        // search rings of empty cells; rings around it still bound the distance to the point
+        let (cx, cy) = Self::cell_of(x, y);
+        let (cx, cy) = (
+            cx.clamp(self.min_cell.0, self.max_cell.0),
+            cy.clamp(self.min_cell.1, self.max_cell.1),
+        );
+        let max_ring = [
+            cx - self.min_cell.0,
+            self.max_cell.0 - cx,
+            cy - self.min_cell.1,
+            self.max_cell.1 - cy,
+        ]
+            .into_iter()
+            .max()
+            .unwrap_or(0)
+            .max(0);
+
+        // (distance, area) of the best candidate; among containing entries the smallest wins
+        let mut best: Option<(usize, f32, f32)> = None;
+        for ring in 0..=max_ring {
+            for (dx, dy) in ring_cells(ring) {
+                let Some(indices) = self.cells.get(&(cx + dx, cy + dy)) else {
+                    continue;
+                };
+                for &index in indices {
+                    let rect = &entries[index].rect;
+                    let candidate = (index, rect.distance_to(x, y), rect.area());
+                    let better = match best {
+                        None => true,
+                        Some((_, distance, area)) =>
+                            candidate.1 < distance || (candidate.1 == distance && candidate.2 < area),
+                    };
+                    if better {
+                        best = Some(candidate);
+                    }
+                }
+            }
+
+            // Every cell in the next ring is at least `ring` cells away from the point
+            if let Some((_, distance, _)) = best {
+                if distance <= (ring as f32) * CELL_SIZE {
+                    break;
+                }
+            }
+        }
+
+        best.map(|(index, _, _)| index)
+    }
+}
+
+/// Offsets of the cells forming the square ring at the given Chebyshev distance
+fn ring_cells(ring: i32) -> impl Iterator<Item = (i32, i32)> {
+    (-ring..=ring).flat_map(move |dx| {
+        (-ring..=ring).filter_map(move |dy| {
+            if dx.abs() == ring || dy.abs() == ring { Some((dx, dy)) } else { None }
+        })
+    })
+}
+
 /// Source mapping between source and preview
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct SourceMapping {
-    /// Map from source positions to preview positions
-    source_to_preview: HashMap<(PathBuf, SourcePosition), Vec<PreviewPosition>>,
-    /// Map from preview positions to source positions
-    preview_to_source: HashMap<(usize, (u32, u32)), Vec<(PathBuf, SourcePosition)>>,
+    entries: Vec<SyncEntry>,
+    /// Spatial index per page
+    pages: HashMap<usize, PageIndex>,
+    /// Entries of each file ordered by their start position
+    files: HashMap<PathBuf, BTreeMap<SourcePosition, Vec<usize>>>,
 }
 
 impl SourceMapping {
     pub fn new() -> Self {
-        Self {
-            source_to_preview: HashMap::new(),
-            preview_to_source: HashMap::new(),
-        }
+        Self::default()
     }
 
-    /// Add a mapping
+    /// Add a region produced by a range of source text
+    pub fn add_entry(&mut self, entry: SyncEntry) {
+        let index = self.entries.len();
+        self.pages.entry(entry.page).or_default().insert(index, &entry.rect);
+        self.files
+            .entry(entry.file.clone())
+            .or_default()
+            .entry(entry.start)
+            .or_default()
+            .push(index);
+        self.entries.push(entry);
+    }
+
+    /// Add a mapping between a single source position and a preview point
     pub fn add_mapping(
         &mut self,
         file: PathBuf,
         source_pos: SourcePosition,
         preview_pos: PreviewPosition
     ) {
-        self.source_to_preview
-            .entry((file.clone(), source_pos))
-            .or_insert_with(Vec::new)
-            .push(preview_pos);
-
-        let grid_pos = (((preview_pos.x as u32) / 10) * 10, ((preview_pos.y as u32) / 10) * 10);
-        self.preview_to_source
-            .entry((preview_pos.page, grid_pos))
-            .or_insert_with(Vec::new)
-            .push((file, source_pos));
-    }
-
-    /// Find preview positions for a source position
-    pub fn source_to_preview_lookup(
-        &self,
-        file: &PathBuf,
-        pos: SourcePosition
-    ) -> Option<&[PreviewPosition]> {
-        self.source_to_preview.get(&(file.clone(), pos)).map(|v| v.as_slice())
-    }
-
-    /// Find source positions for a preview position
-    pub fn preview_to_source_lookup(
-        &self,
-        page: usize,
-        x: f32,
-        y: f32
-    ) -> Option<&[(PathBuf, SourcePosition)]> {
-        let grid_pos = (((x as u32) / 10) * 10, ((y as u32) / 10) * 10);
-        self.preview_to_source.get(&(page, grid_pos)).map(|v| v.as_slice())
+        self.add_entry(SyncEntry {
+            file,
+            start: source_pos,
+            end: source_pos,
+            page: preview_pos.page,
+            rect: PreviewRect::new(preview_pos.x, preview_pos.y, 0.0, 0.0),
+        });
+    }
+
+    pub fn entries(&self) -> &[SyncEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Find the region closest to a source position
+    ///
+    /// Prefers the innermost region containing the position, then a region
+    /// on the same line, then the closest region by line distance.
+    pub fn source_to_preview_lookup(&self, file: &Path, pos: SourcePosition) -> Option<&SyncEntry> {
+        let index = self.files.get(file)?;
+
+        // Text runs rarely span lines, so only look back a single line for containment
+        let containing = index
+            .range(..=pos)
+            .rev()
+            .take_while(|(start, _)| start.line + 1 >= pos.line)
+            .flat_map(|(_, indices)| indices.iter().map(|&i| &self.entries[i]))
+            .filter(|entry| entry.contains_source(pos))
+            .min_by_key(|entry| {
+                (entry.end.line - entry.start.line, entry.end.column.saturating_sub(entry.start.column))
+            });
+        if containing.is_some() {
+            return containing;
+        }
+
+        let before = index
+            .range(..=pos)
+            .next_back()
+            .map(|(_, indices)| &self.entries[indices[0]]);
+        let after = index
+            .range(pos..)
+            .next()
+            .map(|(_, indices)| &self.entries[indices[0]]);
+
+        match (before, after) {
+            (Some(before), Some(after)) => {
+                if after.start.line == pos.line && before.end.line != pos.line {
+                    return Some(after);
+                }
+                let before_distance = pos.line.saturating_sub(before.end.line);
+                let after_distance = after.start.line - pos.line;
+                if before_distance <= after_distance { Some(before) } else { Some(after) }
+            }
+            (before, after) => before.or(after),
+        }
+    }
+
+    /// Find the regions produced by a source line
+    pub fn line_entries(&self, file: &Path, line: usize) -> Vec<&SyncEntry> {
+        let Some(index) = self.files.get(file) else {
+            return Vec::new();
+        };
+        let from = SourcePosition::new(line.saturating_sub(1), 0);
+        let to = SourcePosition::new(line + 1, 0);
+        index
+            .range(from..to)
+            .flat_map(|(_, indices)| indices.iter().map(|&i| &self.entries[i]))
+            .filter(|entry| entry.start.line <= line && entry.end.line >= line)
+            .collect()
+    }
+
+    /// Bounding box of the region produced by a source line on the first page
+    /// it appears on, or of the closest region when the line produced nothing
+    pub fn line_region(&self, file: &Path, line: usize) -> Option<(usize, PreviewRect)> {
+        let entries = self.line_entries(file, line);
+        let Some(page) = entries.iter().map(|entry| entry.page).min() else {
+            let entry = self.source_to_preview_lookup(file, SourcePosition::new(line, 0))?;
+            return Some((entry.page, entry.rect));
+        };
+        entries
+            .iter()
+            .filter(|entry| entry.page == page)
+            .map(|entry| entry.rect)
+            .reduce(|a, b| a.union(&b))
+            .map(|rect| (page, rect))
+    }
+
+    /// Find the region closest to a point on a page
+    pub fn preview_to_source_lookup(&self, page: usize, x: f32, y: f32) -> Option<&SyncEntry> {
+        self.pages
+            .get(&page)
+            .and_then(|index| index.nearest(&self.entries, x, y))
+            .map(|i| &self.entries[i])
     }
 
     /// Clear all mappings
     pub fn clear(&mut self) {
-        self.source_to_preview.clear();
-        self.preview_to_source.clear();
+        self.entries.clear();
+        self.pages.clear();
+        self.files.clear();
     }
 }
 
-impl Default for SourceMapping {
-    fn default() -> Self {
-        Self::new()
+/// A region briefly highlighted after a forward search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncHighlight {
+    pub page: usize,
+    pub rect: PreviewRect,
+    pub started: Instant,
+}
+
+impl SyncHighlight {
+    pub fn is_expired(&self, now: Instant) -> bool {
+        now.duration_since(self.started) >= HIGHLIGHT_DURATION
+    }
+
+    /// Opacity of the highlight, fading out over the second half of its lifetime
+    pub fn opacity(&self, now: Instant) -> f32 {
+        let progress = now.duration_since(self.started).as_secs_f32() / HIGHLIGHT_DURATION.as_secs_f32();
+        ((1.0 - progress) * 2.0).clamp(0.0, 1.0)
     }
 }
 
 /// Manages synchronization between source and preview
 pub struct SyncManager {
     mapping: SourceMapping,
+    highlight: Option<SyncHighlight>,
 }
 
 impl SyncManager {
     pub fn new() -> Self {
         Self {
             mapping: SourceMapping::new(),
+            highlight: None,
         }
     }
 
     /// Update mappings from compilation result
     pub fn update_mapping(&mut self, mapping: SourceMapping) {
         self.mapping = mapping;
+        self.highlight = None;
+    }
+
+    pub fn mapping(&self) -> &SourceMapping {
+        &self.mapping
     }
 
     /// Sync from source to preview
-    pub fn sync_to_preview(&self, file: &PathBuf, pos: SourcePosition) -> Option<PreviewPosition> {
-        self.mapping
-            .source_to_preview_lookup(file, pos)
-            .and_then(|positions| positions.first())
-            .copied()
+    pub fn sync_to_preview(&self, file: &Path, pos: SourcePosition) -> Option<PreviewPosition> {
+        self.mapping.source_to_preview_lookup(file, pos).map(SyncEntry::preview_position)
     }
 
     /// Sync from preview to source
     pub fn sync_to_source(&self, page: usize, x: f32, y: f32) -> Option<(PathBuf, SourcePosition)> {
         self.mapping
             .preview_to_source_lookup(page, x, y)
-            .and_then(|positions| positions.first())
-            .cloned()
+            .map(|entry| (entry.file.clone(), entry.start))
+    }
+
+    /// Forward search: scroll the viewport to the region produced by a source
+    /// line and highlight it
+    pub fn reveal_line(&mut self, file: &Path, line: usize, viewport: &mut Viewport) -> Option<SyncHighlight> {
+        let (page, rect) = self.mapping.line_region(file, line)?;
+        viewport.reveal(page, &rect);
+        let highlight = SyncHighlight {
+            page,
+            rect,
+            started: Instant::now(),
+        };
+        self.highlight = Some(highlight);
+        Some(highlight)
+    }
+
+    /// Inverse search: find the source position under a point of the viewport
+    pub fn source_at(&self, viewport: &Viewport, x: f32, y: f32) -> Option<(PathBuf, SourcePosition)> {
+        let pos = viewport.viewport_to_page(x, y)?;
+        self.sync_to_source(pos.page, pos.x, pos.y)
+    }
+
+    /// The active highlight, if it has not expired yet
+    pub fn highlight(&self, now: Instant) -> Option<&SyncHighlight> {
+        self.highlight.as_ref().filter(|highlight| !highlight.is_expired(now))
+    }
+
+    pub fn clear_expired_highlight(&mut self, now: Instant) {
+        if self.highlight.is_some_and(|highlight| highlight.is_expired(now)) {
+            self.highlight = None;
+        }
     }
 }
 
@@ -127,3 +446,134 @@ impl Default for SyncManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        file: &str,
+        start: (usize, usize),
+        end: (usize, usize),
+        page: usize,
+        rect: PreviewRect
+    ) -> SyncEntry {
+        SyncEntry {
+            file: PathBuf::from(file),
+            start: SourcePosition::new(start.0, start.1),
+            end: SourcePosition::new(end.0, end.1),
+            page,
+            rect,
+        }
+    }
+
+    fn mapping(entries: Vec<SyncEntry>) -> SourceMapping {
+        let mut mapping = SourceMapping::new();
+        for entry in entries {
+            mapping.add_entry(entry);
+        }
+        mapping
+    }
+
+    /// Index of the entry found at a point of page 0
+    fn lookup(mapping: &SourceMapping, x: f32, y: f32) -> Option<usize> {
+        let found = mapping.preview_to_source_lookup(0, x, y)?;
+        mapping.entries().iter().position(|entry| std::ptr::eq(entry, found))
+    }
+
+    #[test]
+    fn ring_cells_surround_the_center() {
+        assert_eq!(ring_cells(0).collect::<Vec<_>>(), [(0, 0)]);
+        let ring: Vec<_> = ring_cells(2).collect();
+        assert_eq!(ring.len(), 16);
+        assert!(ring.iter().all(|(dx, dy)| dx.abs().max(dy.abs()) == 2));
+    }
+
+    #[test]
+    fn nearest_prefers_the_smallest_containing_region() {
+        let mapping = mapping(vec![
+            entry("a.typ", (0, 0), (5, 0), 0, PreviewRect::new(0.0, 0.0, 200.0, 100.0)),
+            entry("a.typ", (1, 0), (1, 4), 0, PreviewRect::new(10.0, 10.0, 20.0, 10.0)),
+            entry("a.typ", (9, 0), (9, 4), 0, PreviewRect::new(1000.0, 1000.0, 10.0, 10.0)),
+        ]);
+        assert_eq!(lookup(&mapping, 15.0, 15.0), Some(1));
+        assert_eq!(lookup(&mapping, 150.0, 50.0), Some(0));
+        // Found across many empty rings of cells, and from outside every cell
+        assert_eq!(lookup(&mapping, 900.0, 980.0), Some(2));
+        assert_eq!(lookup(&mapping, -500.0, -500.0), Some(0));
+        assert_eq!(lookup(&mapping, 1e5, 1e5), Some(2));
+        assert_eq!(lookup(&mapping, -1e5, 50.0), Some(0));
+        assert!(mapping.preview_to_source_lookup(1, 15.0, 15.0).is_none());
+        assert!(SourceMapping::new().preview_to_source_lookup(0, 0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn nearest_matches_a_linear_search() {
+        // Deterministic pseudo-random rectangles spread over a few pages' worth of cells
+        let mut seed = 0x2545_f491_u32;
+        let mut random = move |max: f32| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            (seed % 10_000) as f32 / 10_000.0 * max
+        };
+        let entries: Vec<SyncEntry> = (0..200)
+            .map(|line| {
+                let rect = PreviewRect::new(random(600.0), random(800.0), random(80.0), random(20.0));
+                entry("a.typ", (line, 0), (line, 1), 0, rect)
+            })
+            .collect();
+        let mapping = mapping(entries.clone());
+
+        for _ in 0..500 {
+            let (x, y) = (random(800.0) - 100.0, random(1000.0) - 100.0);
+            let found = lookup(&mapping, x, y).unwrap();
+            let closest = entries
+                .iter()
+                .map(|entry| entry.rect.distance_to(x, y))
+                .fold(f32::INFINITY, f32::min);
+            assert_eq!(entries[found].rect.distance_to(x, y), closest, "at ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn source_lookup_prefers_the_innermost_region_then_the_line() {
+        let rect = PreviewRect::new(0.0, 0.0, 10.0, 10.0);
+        let mapping = mapping(vec![
+            entry("a.typ", (2, 0), (2, 40), 0, rect),
+            entry("a.typ", (2, 10), (2, 15), 0, rect),
+            entry("a.typ", (4, 8), (4, 12), 1, rect),
+            entry("a.typ", (10, 0), (10, 5), 2, rect),
+        ]);
+        let start = |file: &str, line, column| {
+            let found = mapping.source_to_preview_lookup(Path::new(file), SourcePosition::new(line, column))?;
+            Some((found.start.line, found.start.column))
+        };
+        assert_eq!(start("a.typ", 2, 12), Some((2, 10)));
+        assert_eq!(start("a.typ", 2, 30), Some((2, 0)));
+        // Before any region on its line
+        assert_eq!(start("a.typ", 4, 0), Some((4, 8)));
+        assert_eq!(start("a.typ", 6, 0), Some((4, 8)));
+        assert_eq!(start("a.typ", 9, 0), Some((10, 0)));
+        assert_eq!(start("a.typ", 0, 0), Some((2, 0)));
+        assert_eq!(start("b.typ", 2, 0), None);
+    }
+
+    #[test]
+    fn line_region_covers_the_line_on_its_first_page() {
+        let mapping = mapping(vec![
+            entry("a.typ", (3, 0), (3, 5), 1, PreviewRect::new(10.0, 100.0, 50.0, 12.0)),
+            entry("a.typ", (3, 6), (3, 9), 1, PreviewRect::new(70.0, 102.0, 20.0, 12.0)),
+            // The rest of a paragraph broken across pages
+            entry("a.typ", (3, 10), (4, 2), 2, PreviewRect::new(10.0, 20.0, 80.0, 30.0)),
+            entry("a.typ", (8, 0), (8, 4), 2, PreviewRect::new(10.0, 300.0, 40.0, 12.0)),
+        ]);
+        let region = |line| mapping.line_region(Path::new("a.typ"), line);
+
+        assert_eq!(region(3), Some((1, PreviewRect::new(10.0, 100.0, 80.0, 14.0))));
+        assert_eq!(region(4), Some((2, PreviewRect::new(10.0, 20.0, 80.0, 30.0))));
+        // Lines without output fall back to the closest region
+        assert_eq!(region(7), Some((2, PreviewRect::new(10.0, 300.0, 40.0, 12.0))));
+        assert_eq!(mapping.line_region(Path::new("b.typ"), 3), None);
+    }
+}
@@ -0,0 +1,294 @@
+//! Text laid out on compiled pages, for selection and copy
+
+use crate::sync::PreviewRect;
+use crate::viewport::Viewport;
+use serde::{ Deserialize, Serialize };
+use std::ops::Range;
+
+/// A glyph cluster and the part of its run's text it renders
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GlyphBox {
+    pub rect: PreviewRect,
+    /// Byte range into the run's text; ligatures cover several characters
+    pub range: Range<usize>,
+}
+
+/// A run of shaped text sharing font and baseline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextRun {
+    pub text: String,
+    pub baseline: f32,
+    pub glyphs: Vec<GlyphBox>,
+}
+
+impl TextRun {
+    pub fn bounds(&self) -> Option<PreviewRect> {
+        self.glyphs
+            .iter()
+            .map(|glyph| glyph.rect)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Text of the glyphs in a range, without repeating shared clusters
+    fn text_between(&self, glyphs: Range<usize>) -> &str {
+        let Some(first) = self.glyphs.get(glyphs.start) else {
+            return "";
+        };
+        let last = &self.glyphs[glyphs.end.saturating_sub(1).max(glyphs.start)];
+        let start = first.range.start.min(last.range.start);
+        let end = first.range.end.max(last.range.end);
+        self.text.get(start..end).unwrap_or("")
+    }
+}
+
+/// Text runs of one page in reading order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PageText {
+    pub runs: Vec<TextRun>,
+}
+
+/// Text of all pages of a compiled document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentText {
+    pub pages: Vec<PageText>,
+}
+
+/// A caret position between glyphs: before glyph `glyph` of run `run`, or
+/// after the last glyph when `glyph` equals the run's glyph count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct TextPosition {
+    pub page: usize,
+    pub run: usize,
+    pub glyph: usize,
+}
+
+impl DocumentText {
+    pub fn is_empty(&self) -> bool {
+        self.pages.iter().all(|page| page.runs.is_empty())
+    }
+
+    /// Caret position closest to a point on a page
+    pub fn hit_test(&self, page: usize, x: f32, y: f32) -> Option<TextPosition> {
+        let runs = &self.pages.get(page)?.runs;
+        let (run, _) = runs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, run)| Some((index, run.bounds()?.distance_to(x, y))))
+            .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+        // Before the first glyph whose center lies right of the point
+        let glyph = runs[run].glyphs
+            .iter()
+            .position(|glyph| x < glyph.rect.x + glyph.rect.width / 2.0)
+            .unwrap_or(runs[run].glyphs.len());
+        Some(TextPosition { page, run, glyph })
+    }
+
+    /// Glyph ranges of every run covered by a selection, in document order
+    fn selected_runs(&self, selection: &TextSelection) -> Vec<(usize, usize, Range<usize>)> {
+        let (start, end) = selection.ordered();
+        let mut runs = Vec::new();
+        for page in start.page..=end.page.min(self.pages.len().saturating_sub(1)) {
+            let Some(page_text) = self.pages.get(page) else {
+                continue;
+            };
+            for (index, run) in page_text.runs.iter().enumerate() {
+                let here = TextPosition { page, run: index, glyph: 0 };
+                let after = TextPosition { page, run: index, glyph: run.glyphs.len() };
+                if after < start || here > end {
+                    continue;
+                }
+                let from = if (start.page, start.run) == (page, index) { start.glyph } else { 0 };
+                let to = if (end.page, end.run) == (page, index) { end.glyph } else { run.glyphs.len() };
+                if from < to {
+                    runs.push((page, index, from..to));
+                }
+            }
+        }
+        runs
+    }
+}
+
+/// A text selection between two caret positions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextSelection {
+    pub anchor: TextPosition,
+    pub head: TextPosition,
+}
+
+impl TextSelection {
+    pub fn new(position: TextPosition) -> Self {
+        Self {
+            anchor: position,
+            head: position,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    fn ordered(&self) -> (TextPosition, TextPosition) {
+        if self.anchor <= self.head { (self.anchor, self.head) } else { (self.head, self.anchor) }
+    }
+
+    /// Selected text, with line breaks between runs on different baselines
+    pub fn text(&self, document: &DocumentText) -> String {
+        let mut text = String::new();
+        let mut previous: Option<(usize, f32, f32)> = None;
+        for (page, index, glyphs) in document.selected_runs(self) {
+            let run = &document.pages[page].runs[index];
+            let run_text = run.text_between(glyphs.clone());
+            let left = run.glyphs[glyphs.start].rect.x;
+
+            if let Some((previous_page, baseline, right)) = previous {
+                let same_line = previous_page == page && (baseline - run.baseline).abs() < 1.0;
+                if !same_line {
+                    text.push('\n');
+                } else if left - right > 1.0 && !text.ends_with(' ') && !run_text.starts_with(' ') {
+                    text.push(' ');
+                }
+            }
+
+            text.push_str(run_text);
+            previous = Some((page, run.baseline, run.glyphs[glyphs.end - 1].rect.right()));
+        }
+        text
+    }
+
+    /// Selection rectangles per page, in page coordinates
+    pub fn page_rects(&self, document: &DocumentText) -> Vec<(usize, PreviewRect)> {
+        document
+            .selected_runs(self)
+            .into_iter()
+            .filter_map(|(page, index, glyphs)| {
+                let run = &document.pages[page].runs[index];
+                run.glyphs[glyphs]
+                    .iter()
+                    .map(|glyph| glyph.rect)
+                    .reduce(|a, b| a.union(&b))
+                    .map(|rect| (page, rect))
+            })
+            .collect()
+    }
+
+    /// Selection rectangles per page, in viewport coordinates
+    pub fn viewport_rects(&self, document: &DocumentText, viewport: &Viewport) -> Vec<(usize, PreviewRect)> {
+        self.page_rects(document)
+            .into_iter()
+            .filter_map(|(page, rect)| {
+                viewport.page_rect_to_viewport(page, &rect).map(|rect| (page, rect))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A run with one glyph per character, ten points apart, starting at `x`
+    fn run(text: &str, x: f32, baseline: f32) -> TextRun {
+        let glyphs = text
+            .char_indices()
+            .enumerate()
+            .map(|(index, (offset, c))| GlyphBox {
+                rect: PreviewRect::new(x + index as f32 * 10.0, baseline - 10.0, 10.0, 12.0),
+                range: offset..offset + c.len_utf8(),
+            })
+            .collect();
+        TextRun { text: text.to_string(), baseline, glyphs }
+    }
+
+    fn page(lines: &[&str]) -> PageText {
+        let runs = lines
+            .iter()
+            .enumerate()
+            .map(|(index, text)| run(text, 0.0, 20.0 + index as f32 * 20.0))
+            .collect();
+        PageText { runs }
+    }
+
+    fn select(anchor: (usize, usize, usize), head: (usize, usize, usize)) -> TextSelection {
+        let position = |(page, run, glyph)| TextPosition { page, run, glyph };
+        TextSelection { anchor: position(anchor), head: position(head) }
+    }
+
+    #[test]
+    fn copies_part_of_a_run() {
+        let document = DocumentText { pages: vec![page(&["hello world"])] };
+        assert_eq!(select((0, 0, 0), (0, 0, 5)).text(&document), "hello");
+        assert_eq!(select((0, 0, 6), (0, 0, 11)).text(&document), "world");
+        assert_eq!(select((0, 0, 3), (0, 0, 3)).text(&document), "");
+    }
+
+    #[test]
+    fn breaks_lines_between_baselines_and_pages() {
+        let document = DocumentText { pages: vec![page(&["first", "second"]), page(&["third"])] };
+        assert_eq!(select((0, 0, 2), (0, 1, 3)).text(&document), "rst\nsec");
+        assert_eq!(select((0, 1, 0), (1, 0, 5)).text(&document), "second\nthird");
+    }
+
+    #[test]
+    fn reversed_selections_copy_the_same_text() {
+        let document = DocumentText { pages: vec![page(&["first", "second"])] };
+        let forward = select((0, 0, 2), (0, 1, 3));
+        let backward = select((0, 1, 3), (0, 0, 2));
+        assert_eq!(backward.text(&document), forward.text(&document));
+        assert_eq!(backward.page_rects(&document), forward.page_rects(&document));
+    }
+
+    #[test]
+    fn spaces_runs_apart_only_across_gaps() {
+        let spaced = DocumentText {
+            pages: vec![PageText { runs: vec![run("one", 0.0, 20.0), run("two", 40.0, 20.0)] }],
+        };
+        assert_eq!(select((0, 0, 0), (0, 1, 3)).text(&spaced), "one two");
+
+        let touching = DocumentText {
+            pages: vec![PageText { runs: vec![run("ab", 0.0, 20.0), run("cd", 20.0, 20.0)] }],
+        };
+        assert_eq!(select((0, 0, 0), (0, 1, 2)).text(&touching), "abcd");
+    }
+
+    #[test]
+    fn copies_ligatures_once() {
+        // One glyph renders the "ffi" ligature
+        let mut ligature = run("x", 30.0, 20.0);
+        ligature.text = "ffix".to_string();
+        ligature.glyphs[0].range = 3..4;
+        ligature.glyphs.insert(0, GlyphBox { rect: PreviewRect::new(0.0, 10.0, 30.0, 12.0), range: 0..3 });
+        let document = DocumentText { pages: vec![PageText { runs: vec![ligature] }] };
+        assert_eq!(select((0, 0, 0), (0, 0, 1)).text(&document), "ffi");
+        assert_eq!(select((0, 0, 0), (0, 0, 2)).text(&document), "ffix");
+        assert_eq!(select((0, 0, 1), (0, 0, 2)).text(&document), "x");
+    }
+
+    #[test]
+    fn selection_rects_cover_the_selected_glyphs_of_each_run() {
+        let document = DocumentText { pages: vec![page(&["abc", "de"]), page(&["fg"])] };
+        assert_eq!(
+            select((0, 0, 1), (1, 0, 1)).page_rects(&document),
+            vec![
+                (0, PreviewRect::new(10.0, 10.0, 20.0, 12.0)),
+                (0, PreviewRect::new(0.0, 30.0, 20.0, 12.0)),
+                (1, PreviewRect::new(0.0, 10.0, 10.0, 12.0)),
+            ]
+        );
+        assert!(select((0, 0, 1), (0, 0, 1)).page_rects(&document).is_empty());
+    }
+
+    #[test]
+    fn hit_test_places_the_caret_by_glyph_centers_on_the_nearest_run() {
+        let document = DocumentText { pages: vec![page(&["abc", "de"]), PageText::default()] };
+        let at = |run, glyph| Some(TextPosition { page: 0, run, glyph });
+        assert_eq!(document.hit_test(0, 14.0, 15.0), at(0, 1));
+        assert_eq!(document.hit_test(0, 16.0, 15.0), at(0, 2));
+        assert_eq!(document.hit_test(0, -5.0, 15.0), at(0, 0));
+        // Just right of the second line, which is closer than the first
+        assert_eq!(document.hit_test(0, 25.0, 38.0), at(1, 2));
+        assert_eq!(document.hit_test(0, 2.0, 90.0), at(1, 0));
+        assert_eq!(document.hit_test(1, 5.0, 15.0), None);
+        assert_eq!(document.hit_test(2, 5.0, 15.0), None);
+    }
+}
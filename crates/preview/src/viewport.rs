@@ -1,11 +1,23 @@
 //! Viewport management for preview
 
+use crate::sync::{ PreviewPosition, PreviewRect };
 use serde::{ Deserialize, Serialize };
 
-/// Zoom level for preview
+/// Vertical gap between consecutive pages, in pixels
+pub const PAGE_GAP: f32 = 16.0;
+
+/// Size of a compiled page, in points
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PageSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Zoom level for preview
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
 pub enum ZoomLevel {
     /// Fit width to viewport
+    #[default]
     FitWidth,
     /// Fit entire page to viewport
     FitPage,
@@ -37,12 +49,6 @@ impl ZoomLevel {
     }
 }
 
-impl Default for ZoomLevel {
-    fn default() -> Self {
-        ZoomLevel::FitWidth
-    }
-}
-
 /// Viewport for preview display
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Viewport {
@@ -56,6 +62,9 @@ pub struct Viewport {
     pub scroll_y: f32,
     /// Zoom level
     pub zoom: ZoomLevel,
+    /// Pages of the displayed document, stacked vertically
+    #[serde(default)]
+    pub pages: Vec<PageSize>,
 }
 
 impl Viewport {
@@ -66,9 +75,15 @@ impl Viewport {
             scroll_x: 0.0,
             scroll_y: 0.0,
             zoom: ZoomLevel::default(),
+            pages: Vec::new(),
         }
     }
 
+    /// Set the pages of the displayed document
+    pub fn set_pages(&mut self, pages: Vec<PageSize>) {
+        self.pages = pages;
+    }
+
     /// Set viewport size
     pub fn set_size(&mut self, width: f32, height: f32) {
         self.width = width;
@@ -116,9 +131,113 @@ impl Viewport {
         self.zoom.to_scale(self.width, self.height, page_width, page_height)
     }
 
+    /// Scale factor shared by all pages, fitted to the largest page
+    pub fn document_scale(&self) -> f32 {
+        let width = self.pages.iter().map(|page| page.width).fold(0.0, f32::max);
+        let height = self.pages.iter().map(|page| page.height).fold(0.0, f32::max);
+        if width > 0.0 && height > 0.0 {
+            self.current_scale(width, height)
+        } else if let ZoomLevel::Custom(scale) = self.zoom {
+            scale
+        } else {
+            1.0
+        }
+    }
+
+    /// Offset of a page's top edge from the top of the document, in pixels
+    pub fn page_top(&self, page: usize) -> Option<f32> {
+        if page >= self.pages.len() {
+            return None;
+        }
+        let scale = self.document_scale();
+        Some(
+            self.pages[..page]
+                .iter()
+                .map(|size| size.height * scale + PAGE_GAP)
+                .sum()
+        )
+    }
+
+    /// Offset of a page's left edge from the left of the document, in pixels
+    fn page_left(&self, page: usize) -> Option<f32> {
+        let size = self.pages.get(page)?;
+        Some(((self.width - size.width * self.document_scale()) / 2.0).max(0.0))
+    }
+
+    /// Total height of all pages including gaps, in pixels
+    pub fn document_height(&self) -> f32 {
+        let scale = self.document_scale();
+        let pages: f32 = self.pages
+            .iter()
+            .map(|size| size.height * scale)
+            .sum();
+        pages + PAGE_GAP * (self.pages.len().saturating_sub(1) as f32)
+    }
+
+    /// Convert a rectangle on a page (in points) to viewport pixels
+    pub fn page_rect_to_viewport(&self, page: usize, rect: &PreviewRect) -> Option<PreviewRect> {
+        let scale = self.document_scale();
+        let left = self.page_left(page)?;
+        let top = self.page_top(page)?;
+        Some(
+            PreviewRect::new(
+                left + rect.x * scale - self.scroll_x,
+                top + rect.y * scale - self.scroll_y,
+                rect.width * scale,
+                rect.height * scale
+            )
+        )
+    }
+
+    /// Convert a point in the viewport (pixels from its top-left corner) to a
+    /// point on the page under it, or on the closest page when between pages
+    pub fn viewport_to_page(&self, x: f32, y: f32) -> Option<PreviewPosition> {
+        let scale = self.document_scale();
+        let document_y = y + self.scroll_y;
+
+        let mut top = 0.0;
+        for (page, size) in self.pages.iter().enumerate() {
+            let bottom = top + size.height * scale;
+            let is_last = page + 1 == self.pages.len();
+            if document_y < bottom + PAGE_GAP / 2.0 || is_last {
+                let left = self.page_left(page)?;
+                return Some(PreviewPosition {
+                    page,
+                    x: ((x + self.scroll_x - left) / scale).clamp(0.0, size.width),
+                    y: ((document_y - top) / scale).clamp(0.0, size.height),
+                });
+            }
+            top = bottom + PAGE_GAP;
+        }
+        None
+    }
+
+    /// Scroll so that a point on a page is at the top of the viewport
+    pub fn scroll_to_page_point(&mut self, page: usize, y: f32) {
+        let Some(top) = self.page_top(page) else {
+            return;
+        };
+        self.scroll_y = top + y * self.document_scale();
+        self.clamp_scroll();
+    }
+
+    /// Scroll so that a rectangle on a page is vertically centered
+    pub fn reveal(&mut self, page: usize, rect: &PreviewRect) {
+        let Some(top) = self.page_top(page) else {
+            return;
+        };
+        let scale = self.document_scale();
+        let center = top + (rect.y + rect.height / 2.0) * scale;
+        self.scroll_y = center - self.height / 2.0;
+        self.clamp_scroll();
+    }
+
+    /// Keep the scroll position between the top and the end of the document
     fn clamp_scroll(&mut self) {
-        self.scroll_x = self.scroll_x.max(0.0);
-        self.scroll_y = self.scroll_y.max(0.0);
+        let scale = self.document_scale();
+        let width = self.pages.iter().map(|page| page.width * scale).fold(0.0, f32::max);
+        self.scroll_x = self.scroll_x.min(width - self.width).max(0.0);
+        self.scroll_y = self.scroll_y.min(self.document_height() - self.height).max(0.0);
     }
 }
 
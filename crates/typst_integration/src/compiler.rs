@@ -1,17 +1,154 @@
-// Stub compiler implementation - to be fully implemented in Phase 6
-use anyhow::Result;
-use std::path::Path;
+use crate::diagnostics::{ CompileError, Diagnostic };
+use crate::export::export_png;
+use crate::links::{ extract_links, extract_outline };
+use crate::source_mapping::build_source_mapping;
+use crate::text_extraction::extract_text;
+use crate::world::{ StudioWorld, WorldResources };
+use anyhow::{ anyhow, bail, Result };
+use preview::{ DocumentLinks, DocumentText, OutlineItem, PageSize, SourceMapping };
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, OnceLock };
+use std::time::{ Duration, Instant };
+use typst::diag::Warned;
+use typst::layout::PagedDocument;
 
-pub struct TypstCompiler;
+/// Result of a successful compilation
+pub struct CompiledDocument {
+    pub document: PagedDocument,
+    pub source_mapping: SourceMapping,
+    pub text: DocumentText,
+    pub links: DocumentLinks,
+    pub outline: Vec<OutlineItem>,
+    pub warnings: Vec<Diagnostic>,
+    pub duration: Duration,
+}
+
+impl CompiledDocument {
+    /// Sizes of the compiled pages, in points
+    pub fn page_sizes(&self) -> Vec<PageSize> {
+        self.document.pages
+            .iter()
+            .map(|page| PageSize {
+                width: page.frame.width().to_pt() as f32,
+                height: page.frame.height().to_pt() as f32,
+            })
+            .collect()
+    }
+
+    /// Render a page to PNG at a resolution in dots per inch
+    pub fn render_page(&self, page: usize, dpi: f32) -> Result<Vec<u8>> {
+        let page = self.document.pages
+            .get(page)
+            .ok_or_else(|| anyhow!("The document has no page {}", page + 1))?;
+        export_png(page, dpi)
+    }
+}
+
+#[derive(Clone)]
+pub struct TypstCompiler {
+    /// Font discovery is slow, so it happens on first compilation
+    resources: Arc<OnceLock<Arc<WorldResources>>>,
+    /// `sys.inputs` of every compiled document
+    inputs: Arc<Vec<(String, String)>>,
+}
 
 impl TypstCompiler {
     pub fn new() -> Self {
-        Self
+        Self {
+            resources: Arc::new(OnceLock::new()),
+            inputs: Arc::new(Vec::new()),
+        }
+    }
+
+    /// A compiler taking a project's `compiler_args`
+    ///
+    /// Only `--input key=value`, as accepted by the `typst` command line, is
+    /// supported; any other argument is an error.
+    pub fn with_args(args: &[String]) -> Result<Self> {
+        Ok(Self {
+            resources: Arc::new(OnceLock::new()),
+            inputs: Arc::new(parse_inputs(args)?),
+        })
+    }
+
+    fn resources(&self) -> Arc<WorldResources> {
+        self.resources
+            .get_or_init(|| Arc::new(WorldResources::with_inputs(&self.inputs)))
+            .clone()
+    }
+
+    /// Create the compilation environment for a main file inside a project root
+    pub fn world(&self, root: &Path, main: &Path) -> Result<StudioWorld> {
+        StudioWorld::new(root, main, self.resources())
+    }
+
+    /// Compile a file, using its directory as the project root
+    pub async fn compile(&self, path: &Path) -> Result<CompiledDocument> {
+        let root = path
+            .parent()
+            .ok_or_else(|| anyhow!("{} has no parent directory", path.display()))?;
+        self.compile_in(root, path).await
     }
 
-    pub async fn compile(&self, _path: &Path) -> Result<Vec<u8>> {
-        // Placeholder - returns empty PDF
-        Ok(Vec::new())
+    /// Compile a main file of a project on a blocking thread
+    pub async fn compile_in(&self, root: &Path, main: &Path) -> Result<CompiledDocument> {
+        let compiler = self.clone();
+        let root: PathBuf = root.to_path_buf();
+        let main: PathBuf = main.to_path_buf();
+        tokio::task::spawn_blocking(move || compiler.compile_blocking(&root, &main)).await?
+    }
+
+    /// Compile a main file of a project on the current thread
+    ///
+    /// Errors are reported as a [`CompileError`] carrying every diagnostic.
+    pub fn compile_blocking(&self, root: &Path, main: &Path) -> Result<CompiledDocument> {
+        self.compile_sources(root, main, HashMap::new())
+    }
+
+    /// Like [`TypstCompiler::compile_blocking`], reading the files in `sources`
+    /// from memory, such as the unsaved contents of open editors
+    pub fn compile_sources(
+        &self,
+        root: &Path,
+        main: &Path,
+        sources: HashMap<PathBuf, String>
+    ) -> Result<CompiledDocument> {
+        let world = self.world(root, main)?.with_overlay(sources);
+        let start = Instant::now();
+        let Warned { output, warnings } = typst::compile::<PagedDocument>(&world);
+        let duration = start.elapsed();
+
+        let warnings: Vec<Diagnostic> = warnings
+            .iter()
+            .map(|warning| Diagnostic::from_source(warning, &world))
+            .collect();
+
+        match output {
+            Ok(document) => {
+                let source_mapping = build_source_mapping(&document, &world);
+                let text = extract_text(&document);
+                let links = extract_links(&document);
+                let outline = extract_outline(&document);
+                Ok(CompiledDocument {
+                    document,
+                    source_mapping,
+                    text,
+                    links,
+                    outline,
+                    warnings,
+                    duration,
+                })
+            }
+            Err(errors) => {
+                let diagnostics = errors
+                    .iter()
+                    .map(|error| Diagnostic::from_source(error, &world))
+                    .chain(warnings)
+                    .collect();
+                Err(CompileError { diagnostics }.into())
+            }
+        }
     }
 }
 
@@ -21,3 +158,47 @@ impl Default for TypstCompiler {
     }
 }
 
+/// The `key=value` pairs of `--input key=value` and `--input=key=value` arguments
+fn parse_inputs(args: &[String]) -> Result<Vec<(String, String)>> {
+    let mut inputs = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let input = match arg.strip_prefix("--input") {
+            Some("") => args.next().ok_or_else(|| anyhow!("--input needs a key=value pair"))?,
+            Some(rest) if rest.starts_with('=') => &rest[1..],
+            _ => bail!("Unsupported compiler argument '{}'", arg),
+        };
+        let (key, value) = input
+            .split_once('=')
+            .ok_or_else(|| anyhow!("--input {} is not a key=value pair", input))?;
+        inputs.push((key.to_string(), value.to_string()));
+    }
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn parses_inputs_in_both_forms() {
+        let inputs = parse_inputs(&args(&["--input", "draft=true", "--input=query=a=b"])).unwrap();
+        assert_eq!(inputs, vec![
+            ("draft".to_string(), "true".to_string()),
+            ("query".to_string(), "a=b".to_string())
+        ]);
+        assert!(parse_inputs(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_other_arguments() {
+        assert!(parse_inputs(&args(&["--input"])).is_err());
+        assert!(parse_inputs(&args(&["--input", "draft"])).is_err());
+        assert!(parse_inputs(&args(&["--inputs=draft=true"])).is_err());
+        assert!(parse_inputs(&args(&["--font-path", "fonts"])).is_err());
+    }
+}
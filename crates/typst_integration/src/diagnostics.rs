@@ -0,0 +1,83 @@
+//! Compiler diagnostics resolved to file positions
+
+use crate::world::StudioWorld;
+use serde::{ Deserialize, Serialize };
+use std::path::PathBuf;
+use typst::diag::SourceDiagnostic;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A compiler error or warning with zero-based line and column positions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub path: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+    pub hints: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn from_source(diagnostic: &SourceDiagnostic, world: &StudioWorld) -> Self {
+        let severity = match diagnostic.severity {
+            typst::diag::Severity::Error => Severity::Error,
+            typst::diag::Severity::Warning => Severity::Warning,
+        };
+
+        let resolved = world.resolve_span(diagnostic.span);
+        let start = resolved.as_ref().map(|span| span.start());
+        let end = resolved.as_ref().map(|span| span.end());
+
+        Self {
+            severity,
+            message: diagnostic.message.to_string(),
+            path: resolved.map(|span| span.path),
+            line: start.map(|(line, _)| line),
+            column: start.map(|(_, column)| column),
+            end_line: end.map(|(line, _)| line),
+            end_column: end.map(|(_, column)| column),
+            hints: diagnostic.hints
+                .iter()
+                .map(|hint| hint.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match (&self.path, self.line, self.column) {
+            (Some(path), Some(line), Some(column)) => {
+                write!(f, "{}:{}:{}: {}: {}", path.display(), line + 1, column + 1, severity, self.message)?;
+            }
+            _ => write!(f, "{}: {}", severity, self.message)?,
+        }
+        for hint in &self.hints {
+            write!(f, "\n  hint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compilation failed with at least one error
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Compilation failed with {} error(s)", .diagnostics.iter().filter(|d| d.is_error()).count())]
+pub struct CompileError {
+    pub diagnostics: Vec<Diagnostic>,
+}
@@ -0,0 +1,348 @@
+//! Export of compiled documents to PDF, PNG and SVG files
+
+use crate::compiler::CompiledDocument;
+use anyhow::{ anyhow, bail, Context, Result };
+use chrono::{ Datelike, Timelike, Utc };
+use editor_core::Project;
+use serde::{ Deserialize, Serialize };
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::path::{ Component, Path, PathBuf };
+use std::str::FromStr;
+use typst::foundations::{ Datetime, Smart };
+use typst::layout::{ Page, PageRanges, PagedDocument };
+use typst_pdf::{ PdfOptions, PdfStandard, PdfStandards, Timestamp };
+
+/// Resolution used for PNG export when none is given
+pub const DEFAULT_DPI: f32 = 144.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Pdf,
+    Png,
+    Svg,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Png => "png",
+            ExportFormat::Svg => "svg",
+        }
+    }
+
+    /// Whether every page is written to its own file
+    pub fn is_per_page(&self) -> bool {
+        !matches!(self, ExportFormat::Pdf)
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "pdf" => Ok(ExportFormat::Pdf),
+            "png" => Ok(ExportFormat::Png),
+            "svg" => Ok(ExportFormat::Svg),
+            _ => bail!("Unknown export format '{}', expected pdf, png or svg", s),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// A selection of one-based, inclusive page ranges such as `1-3,5,8-`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSelection {
+    ranges: Vec<(usize, Option<usize>)>,
+}
+
+impl PageSelection {
+    pub fn contains(&self, page: usize) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(start, end)| page >= start && end.is_none_or(|end| page <= end))
+    }
+
+    /// Zero-based indices of the selected pages of a document with `count` pages
+    pub fn indices(&self, count: usize) -> Vec<usize> {
+        (0..count).filter(|index| self.contains(index + 1)).collect()
+    }
+
+    fn to_page_ranges(&self) -> PageRanges {
+        PageRanges::new(
+            self.ranges
+                .iter()
+                .map(|&(start, end)| NonZeroUsize::new(start)..=end.and_then(NonZeroUsize::new))
+                .collect()
+        )
+    }
+}
+
+impl FromStr for PageSelection {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |page: &str| -> Result<usize> {
+            match page.trim().parse::<usize>() {
+                Ok(page) if page > 0 => Ok(page),
+                _ => Err(anyhow!("Invalid page number '{}' in page range '{}'", page.trim(), s)),
+            }
+        };
+
+        let mut ranges = Vec::new();
+        for part in s.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let range = match part.split_once('-') {
+                Some((start, end)) => {
+                    let start = if start.trim().is_empty() { 1 } else { parse(start)? };
+                    let end = if end.trim().is_empty() { None } else { Some(parse(end)?) };
+                    if end.is_some_and(|end| end < start) {
+                        bail!("Page range '{}' ends before it starts", part);
+                    }
+                    (start, end)
+                }
+                None => {
+                    let page = parse(part)?;
+                    (page, Some(page))
+                }
+            };
+            ranges.push(range);
+        }
+
+        if ranges.is_empty() {
+            bail!("Page range '{}' selects no pages", s);
+        }
+        Ok(Self { ranges })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Pages to export; all pages when `None`
+    pub pages: Option<PageSelection>,
+    /// Resolution of PNG export, in dots per inch
+    pub dpi: f32,
+    /// Produce a PDF/A-2b conforming file for archiving
+    pub pdf_a: bool,
+    /// File name without extension; supports `{name}`, `{page}`, `{0page}` and `{total}`
+    pub filename_template: Option<String>,
+}
+
+impl ExportOptions {
+    pub fn new(format: ExportFormat) -> Self {
+        Self {
+            format,
+            pages: None,
+            dpi: DEFAULT_DPI,
+            pdf_a: false,
+            filename_template: None,
+        }
+    }
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self::new(ExportFormat::default())
+    }
+}
+
+/// Writes compiled documents into an output directory
+pub struct Exporter {
+    directory: PathBuf,
+}
+
+impl Exporter {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Export into the project's output directory, or its root when none is set
+    ///
+    /// A relative output directory is resolved against the project root.
+    pub fn for_project(project: &Project) -> Self {
+        let directory = match &project.settings.output_directory {
+            Some(directory) => project.root.join(directory),
+            None => project.root.clone(),
+        };
+        Self::new(directory)
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Export a compiled document whose main file is `main`, returning the written files
+    pub fn export(&self, compiled: &CompiledDocument, main: &Path, options: &ExportOptions) -> Result<Vec<PathBuf>> {
+        let name = main
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "document".to_string());
+        self.export_document(&compiled.document, &name, options)
+    }
+
+    pub fn export_document(&self, document: &PagedDocument, name: &str, options: &ExportOptions) -> Result<Vec<PathBuf>> {
+        let total = document.pages.len();
+        let indices = match &options.pages {
+            Some(selection) => selection.indices(total),
+            None => (0..total).collect(),
+        };
+        if indices.is_empty() {
+            bail!("No pages selected for export, the document has {} page(s)", total);
+        }
+
+        std::fs::create_dir_all(&self.directory)
+            .with_context(|| format!("Failed to create output directory {}", self.directory.display()))?;
+
+        if !options.format.is_per_page() {
+            let path = self.output_path(name, None, total, options)?;
+            write(&path, &export_pdf(document, options)?)?;
+            return Ok(vec![path]);
+        }
+
+        let mut written = Vec::with_capacity(indices.len());
+        for index in indices {
+            let page = &document.pages[index];
+            let bytes = match options.format {
+                ExportFormat::Png => export_png(page, options.dpi)?,
+                _ => typst_svg::svg(page).into_bytes(),
+            };
+            let path = self.output_path(name, Some(index + 1), total, options)?;
+            write(&path, &bytes)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /// Path of an exported file inside the output directory
+    ///
+    /// Expanded templates that could point elsewhere are rejected.
+    fn output_path(&self, name: &str, page: Option<usize>, total: usize, options: &ExportOptions) -> Result<PathBuf> {
+        let mut template = options.filename_template.clone().unwrap_or_else(|| "{name}".to_string());
+        // Without a page placeholder every page would overwrite the previous one
+        if page.is_some() && !template.contains("{page}") && !template.contains("{0page}") {
+            template.push_str("-{page}");
+        }
+
+        let width = total.to_string().len();
+        let page = page.unwrap_or(1);
+        let filename = template
+            .replace("{name}", name)
+            .replace("{0page}", &format!("{:0width$}", page, width = width))
+            .replace("{page}", &page.to_string())
+            .replace("{total}", &total.to_string());
+        // One plain file name; `\\` separates directories on Windows
+        let mut components = Path::new(&filename).components();
+        let is_file_name = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+        if !is_file_name || filename.contains(['/', '\\']) {
+            bail!("Invalid export file name '{}': it must be a file name without directories", filename);
+        }
+        Ok(self.directory.join(format!("{}.{}", filename, options.format.extension())))
+    }
+}
+
+fn export_pdf(document: &PagedDocument, options: &ExportOptions) -> Result<Vec<u8>> {
+    let standards = if options.pdf_a {
+        PdfStandards::new(&[PdfStandard::A_2b]).map_err(|err| anyhow!("{}", err))?
+    } else {
+        PdfStandards::default()
+    };
+
+    let pdf_options = PdfOptions {
+        ident: Smart::Auto,
+        timestamp: now(),
+        page_ranges: options.pages.as_ref().map(PageSelection::to_page_ranges),
+        standards,
+        ..PdfOptions::default()
+    };
+
+    typst_pdf::pdf(document, &pdf_options).map_err(|errors| {
+        let messages: Vec<String> = errors
+            .iter()
+            .map(|error| error.message.to_string())
+            .collect();
+        anyhow!("PDF export failed: {}", messages.join("; "))
+    })
+}
+
+pub(crate) fn export_png(page: &Page, dpi: f32) -> Result<Vec<u8>> {
+    if dpi.is_nan() || dpi <= 0.0 {
+        bail!("Invalid PNG resolution {} dpi", dpi);
+    }
+    let pixmap = typst_render::render(page, dpi / 72.0);
+    pixmap.encode_png().map_err(|err| anyhow!("PNG encoding failed: {}", err))
+}
+
+/// Creation time embedded into exported PDFs
+fn now() -> Option<Timestamp> {
+    let now = Utc::now();
+    let datetime = Datetime::from_ymd_hms(
+        now.year(),
+        now.month() as u8,
+        now.day() as u8,
+        now.hour() as u8,
+        now.minute() as u8,
+        now.second() as u8
+    )?;
+    Some(Timestamp::new_utc(datetime))
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<()> {
+    std::fs::write(path, bytes).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(format: ExportFormat, template: Option<&str>) -> ExportOptions {
+        ExportOptions {
+            filename_template: template.map(ToString::to_string),
+            ..ExportOptions::new(format)
+        }
+    }
+
+    #[test]
+    fn expands_file_name_templates() {
+        let exporter = Exporter::new("out");
+        let pdf = options(ExportFormat::Pdf, None);
+        assert_eq!(exporter.output_path("thesis", None, 12, &pdf).unwrap(), Path::new("out/thesis.pdf"));
+
+        let png = options(ExportFormat::Png, Some("{name}-{0page}-of-{total}"));
+        assert_eq!(
+            exporter.output_path("thesis", Some(3), 12, &png).unwrap(),
+            Path::new("out/thesis-03-of-12.png")
+        );
+        // Pages never overwrite each other
+        let svg = options(ExportFormat::Svg, Some("slides"));
+        assert_eq!(exporter.output_path("talk", Some(2), 5, &svg).unwrap(), Path::new("out/slides-2.svg"));
+    }
+
+    #[test]
+    fn rejects_file_names_leaving_the_output_directory() {
+        let exporter = Exporter::new("out");
+        for template in ["../{name}", "sub/{name}", "sub\\{name}", "/{name}", "{name}/", "..", ".", ""] {
+            let options = options(ExportFormat::Pdf, Some(template));
+            assert!(exporter.output_path("thesis", None, 1, &options).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn accepts_dots_within_file_names() {
+        let exporter = Exporter::new("out");
+        let options = options(ExportFormat::Pdf, Some("{name}..v2"));
+        assert_eq!(exporter.output_path("draft", None, 1, &options).unwrap(), Path::new("out/draft..v2.pdf"));
+        let options = options(ExportFormat::Pdf, Some("..{name}"));
+        assert_eq!(exporter.output_path("draft", None, 1, &options).unwrap(), Path::new("out/..draft.pdf"));
+    }
+}
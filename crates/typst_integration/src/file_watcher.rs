@@ -0,0 +1,129 @@
+//! Debounced watching of a project directory for external changes
+
+use anyhow::Result;
+use notify::event::{ ModifyKind, RenameMode };
+use notify::{ Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher };
+use std::collections::{ HashMap, HashSet };
+use std::path::{ Path, PathBuf };
+use std::sync::mpsc::{ self, Receiver, RecvTimeoutError, Sender };
+use std::time::Duration;
+
+/// Quiet time after the last event before a batch of changes is reported
+pub const DEBOUNCE_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+/// Watches a directory tree and reports batches of changed files
+///
+/// Rapid successive events, such as an editor writing a temp file and
+/// renaming it, are collapsed into one change per path.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    watched: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Start watching `root` recursively; batches arrive on the returned receiver
+    pub fn new(root: &Path) -> Result<(Self, Receiver<Vec<FileChange>>)> {
+        let (event_tx, event_rx) = mpsc::channel();
+        let (change_tx, change_rx) = mpsc::channel();
+
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            match event {
+                Ok(event) => {
+                    let _ = event_tx.send(event);
+                }
+                Err(err) => tracing::warn!("File watcher error: {}", err),
+            }
+        })?;
+        std::thread::Builder::new()
+            .name("file-watcher".to_string())
+            .spawn(move || debounce(event_rx, change_tx))?;
+
+        let mut watcher = Self {
+            watcher,
+            watched: HashSet::new(),
+        };
+        watcher.watch(root, RecursiveMode::Recursive)?;
+        Ok((watcher, change_rx))
+    }
+
+    /// Additionally watch a path outside the root, such as an asset or local package
+    pub fn watch(&mut self, path: &Path, mode: RecursiveMode) -> Result<()> {
+        if self.watched.insert(path.to_path_buf()) {
+            self.watcher.watch(path, mode)?;
+        }
+        Ok(())
+    }
+
+    pub fn unwatch(&mut self, path: &Path) -> Result<()> {
+        if self.watched.remove(path) {
+            self.watcher.unwatch(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collect raw events until things settle down, then send one batch
+fn debounce(events: Receiver<Event>, changes: Sender<Vec<FileChange>>) {
+    // Ends when the watcher, and with it the event sender, is dropped
+    while let Ok(first) = events.recv() {
+        let mut pending: HashMap<PathBuf, ChangeKind> = HashMap::new();
+        record(&mut pending, first);
+        loop {
+            match events.recv_timeout(DEBOUNCE_DELAY) {
+                Ok(event) => record(&mut pending, event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        if pending.is_empty() {
+            continue;
+        }
+        let mut batch: Vec<FileChange> = pending
+            .into_iter()
+            .map(|(path, kind)| FileChange { path, kind })
+            .collect();
+        batch.sort_by(|a, b| a.path.cmp(&b.path));
+        if changes.send(batch).is_err() {
+            return;
+        }
+    }
+}
+
+fn record(pending: &mut HashMap<PathBuf, ChangeKind>, event: Event) {
+    for path in event.paths {
+        if path.components().any(|component| component.as_os_str() == ".git") {
+            continue;
+        }
+        let kind = match event.kind {
+            EventKind::Create(_) => ChangeKind::Created,
+            EventKind::Remove(_) => ChangeKind::Removed,
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => ChangeKind::Removed,
+            EventKind::Modify(ModifyKind::Name(_)) if !path.exists() => ChangeKind::Removed,
+            EventKind::Modify(ModifyKind::Name(_)) => ChangeKind::Created,
+            EventKind::Modify(_) | EventKind::Any => ChangeKind::Modified,
+            EventKind::Access(_) | EventKind::Other => continue,
+        };
+        let merged = match (pending.get(&path), kind) {
+            // A file created within the batch is new to listeners, even if written since
+            (Some(ChangeKind::Created), ChangeKind::Modified) => ChangeKind::Created,
+            // Deleted and recreated, as atomic saves do, is a modification
+            (Some(ChangeKind::Removed), ChangeKind::Created) => ChangeKind::Modified,
+            _ => kind,
+        };
+        pending.insert(path, merged);
+    }
+}
@@ -0,0 +1,36 @@
+//! Traversal of compiled frames
+
+use preview::sync::PreviewRect;
+use typst::layout::{ Frame, FrameItem, Point, Size, Transform };
+
+/// Visit every non-group item of a frame with its position and the transform
+/// from the item's frame to page coordinates
+pub(crate) fn walk_frame(frame: &Frame, ts: Transform, visit: &mut dyn FnMut(Point, &FrameItem, Transform)) {
+    for (pos, item) in frame.items() {
+        match item {
+            FrameItem::Group(group) => {
+                let ts = ts
+                    .pre_concat(Transform::translate(pos.x, pos.y))
+                    .pre_concat(group.transform);
+                walk_frame(&group.frame, ts, visit);
+            }
+            item => visit(*pos, item, ts),
+        }
+    }
+}
+
+/// Page-space bounding box of a rectangle after applying a transform, in points
+pub(crate) fn transformed_rect(pos: Point, size: Size, ts: Transform) -> Option<PreviewRect> {
+    let corners = [
+        pos,
+        Point::new(pos.x + size.x, pos.y),
+        Point::new(pos.x, pos.y + size.y),
+        Point::new(pos.x + size.x, pos.y + size.y),
+    ];
+    PreviewRect::from_points(
+        corners.into_iter().map(|corner| {
+            let point = corner.transform(ts);
+            (point.x.to_pt() as f32, point.y.to_pt() as f32)
+        })
+    )
+}
@@ -1,6 +1,15 @@
-// Typst integration module - to be implemented in later phases
 pub mod compiler;
+pub mod diagnostics;
+pub mod export;
+pub mod file_watcher;
+mod frames;
+pub mod links;
 pub mod lsp_client;
+pub mod source_mapping;
+pub mod text_extraction;
+pub mod world;
 
-pub use compiler::TypstCompiler;
-
+pub use compiler::{ CompiledDocument, TypstCompiler };
+pub use diagnostics::{ CompileError, Diagnostic };
+pub use export::{ ExportFormat, ExportOptions, Exporter, PageSelection };
+pub use world::StudioWorld;
@@ -0,0 +1,67 @@
+//! Extraction of links and the heading outline from compiled documents
+
+use crate::frames::{ transformed_rect, walk_frame };
+use preview::links::{ build_outline, DocumentLinks, LinkTarget, OutlineItem, PreviewLink };
+use preview::sync::PreviewPosition;
+use typst::foundations::{ NativeElement, Selector, StyleChain };
+use typst::introspection::Introspector;
+use typst::layout::{ FrameItem, PagedDocument, Position, Transform };
+use typst::model::{ Destination, HeadingElem };
+
+/// Collect the clickable areas of every page, resolving internal destinations
+pub fn extract_links(document: &PagedDocument) -> DocumentLinks {
+    let mut links = Vec::new();
+    for (page, content) in document.pages.iter().enumerate() {
+        walk_frame(&content.frame, Transform::identity(), &mut |pos, item, ts| {
+            let FrameItem::Link(destination, size) = item else {
+                return;
+            };
+            let target = match destination {
+                Destination::Url(url) => LinkTarget::Url(url.as_str().to_string()),
+                Destination::Position(position) => LinkTarget::Position(preview_position(*position)),
+                Destination::Location(location) => {
+                    LinkTarget::Position(preview_position(document.introspector.position(*location)))
+                }
+            };
+            if let Some(rect) = transformed_rect(pos, *size, ts) {
+                links.push(PreviewLink { page, rect, target });
+            }
+        });
+    }
+    DocumentLinks::new(links)
+}
+
+/// Build the tree of outlined headings
+pub fn extract_outline(document: &PagedDocument) -> Vec<OutlineItem> {
+    let introspector: &Introspector = &document.introspector;
+    let styles = StyleChain::default();
+
+    let headings = introspector
+        .query(&Selector::Elem(HeadingElem::ELEM, None))
+        .iter()
+        .filter_map(|content| {
+            let heading = content.to_packed::<HeadingElem>()?;
+            if !heading.outlined.get(styles) {
+                return None;
+            }
+            let position = introspector.position(content.location()?);
+            Some(
+                OutlineItem::new(
+                    heading.body.plain_text().to_string(),
+                    heading.resolve_level(styles).get(),
+                    preview_position(position)
+                )
+            )
+        })
+        .collect();
+
+    build_outline(headings)
+}
+
+fn preview_position(position: Position) -> PreviewPosition {
+    PreviewPosition {
+        page: position.page.get() - 1,
+        x: position.point.x.to_pt() as f32,
+        y: position.point.y.to_pt() as f32,
+    }
+}
@@ -0,0 +1,107 @@
+//! Source mapping generation from compiled frames
+
+use crate::frames::{ transformed_rect, walk_frame };
+use crate::world::StudioWorld;
+use preview::sync::{ SourceMapping, SourcePosition, SyncEntry };
+use typst::layout::{ Abs, FrameItem, PagedDocument, Point, Size, Transform };
+use typst::syntax::Span;
+use typst::text::TextItem;
+
+/// Build a source mapping from the spans attached to the items of every page
+pub fn build_source_mapping(document: &PagedDocument, world: &StudioWorld) -> SourceMapping {
+    let mut mapping = SourceMapping::new();
+    for (page, content) in document.pages.iter().enumerate() {
+        let mut collector = Collector {
+            world,
+            page,
+            mapping: &mut mapping,
+        };
+        walk_frame(&content.frame, Transform::identity(), &mut |pos, item, ts| collector.item(pos, item, ts));
+    }
+    mapping
+}
+
+struct Collector<'a> {
+    world: &'a StudioWorld,
+    page: usize,
+    mapping: &'a mut SourceMapping,
+}
+
+impl Collector<'_> {
+    fn item(&mut self, pos: Point, item: &FrameItem, ts: Transform) {
+        match item {
+            FrameItem::Text(text) => self.text(pos, text, ts),
+            FrameItem::Shape(shape, span) => {
+                self.region(*span, 0, 0, pos, shape.geometry.bbox_size(), ts);
+            }
+            FrameItem::Image(_, size, span) => self.region(*span, 0, 0, pos, *size, ts),
+            _ => {}
+        }
+    }
+
+    /// Add one entry per run of consecutive glyphs that share a span
+    fn text(&mut self, pos: Point, text: &TextItem, ts: Transform) {
+        let ascender = text.font.metrics().ascender.at(text.size);
+        let descender = text.font.metrics().descender.at(text.size);
+        let top = pos.y - ascender;
+        let height = ascender - descender;
+
+        let mut x = pos.x;
+        let mut run: Option<(Span, u16, u16, Abs)> = None;
+        for glyph in &text.glyphs {
+            let (span, offset) = glyph.span;
+            if let Some(current) = run.as_mut().filter(|current| current.0 == span) {
+                current.2 = offset;
+            } else {
+                if let Some((span, first, last, start)) = run.take() {
+                    self.text_run(span, first, last, start, x, top, height, ts);
+                }
+                run = Some((span, offset, offset, x));
+            }
+            x += glyph.x_advance.at(text.size);
+        }
+        if let Some((span, first, last, start)) = run {
+            self.text_run(span, first, last, start, x, top, height, ts);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn text_run(
+        &mut self,
+        span: Span,
+        first: u16,
+        last: u16,
+        start: Abs,
+        end: Abs,
+        top: Abs,
+        height: Abs,
+        ts: Transform
+    ) {
+        let size = Size::new(end - start, height);
+        self.region(span, first as usize, last as usize, Point::new(start, top), size, ts);
+    }
+
+    fn region(&mut self, span: Span, first: usize, last: usize, pos: Point, size: Size, ts: Transform) {
+        if span.is_detached() {
+            return;
+        }
+        let Some(resolved) = self.world.resolve_span(span) else {
+            return;
+        };
+
+        let (start_line, start_column) = resolved.at_offset(first);
+        let (end_line, end_column) = if last > first { resolved.at_offset(last) } else { resolved.end() };
+
+        let Some(rect) = transformed_rect(pos, size, ts) else {
+            return;
+        };
+
+        self.mapping.add_entry(SyncEntry {
+            file: resolved.path,
+            start: SourcePosition::new(start_line, start_column),
+            end: SourcePosition::new(end_line, end_column),
+            page: self.page,
+            rect,
+        });
+    }
+}
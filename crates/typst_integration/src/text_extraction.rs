@@ -0,0 +1,42 @@
+//! Extraction of positioned text from compiled frames
+
+use crate::frames::{ transformed_rect, walk_frame };
+use preview::text::{ DocumentText, GlyphBox, PageText, TextRun };
+use typst::layout::{ FrameItem, PagedDocument, Point, Size, Transform };
+
+/// Collect the glyph boxes and text of every text item, page by page
+pub fn extract_text(document: &PagedDocument) -> DocumentText {
+    let pages = document.pages
+        .iter()
+        .map(|page| {
+            let mut runs = Vec::new();
+            walk_frame(&page.frame, Transform::identity(), &mut |pos, item, ts| {
+                if let FrameItem::Text(text) = item {
+                    let ascender = text.font.metrics().ascender.at(text.size);
+                    let descender = text.font.metrics().descender.at(text.size);
+                    let height = ascender - descender;
+
+                    let mut x = pos.x;
+                    let mut glyphs = Vec::with_capacity(text.glyphs.len());
+                    for glyph in &text.glyphs {
+                        let advance = glyph.x_advance.at(text.size);
+                        let origin = Point::new(x, pos.y - ascender);
+                        if let Some(rect) = transformed_rect(origin, Size::new(advance, height), ts) {
+                            glyphs.push(GlyphBox { rect, range: glyph.range() });
+                        }
+                        x += advance;
+                    }
+
+                    runs.push(TextRun {
+                        text: text.text.to_string(),
+                        baseline: pos.transform(ts).y.to_pt() as f32,
+                        glyphs,
+                    });
+                }
+            });
+            PageText { runs }
+        })
+        .collect();
+
+    DocumentText { pages }
+}
@@ -0,0 +1,205 @@
+//! Filesystem-backed `typst::World` implementation
+
+use anyhow::{ anyhow, Result };
+use chrono::{ DateTime, Datelike, Duration, Local };
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{ Path, PathBuf };
+use std::sync::Arc;
+use typst::diag::{ FileError, FileResult };
+use typst::foundations::{ Bytes, Datetime, Dict, Value };
+use typst::syntax::{ FileId, Source, Span, VirtualPath };
+use typst::text::{ Font, FontBook };
+use typst::utils::LazyHash;
+use typst::{ Library, LibraryExt, World };
+use typst_kit::download::{ Downloader, ProgressSink };
+use typst_kit::fonts::{ FontSearcher, FontSlot };
+use typst_kit::package::PackageStorage;
+
+/// Fonts, standard library and package storage shared by all worlds of a compiler
+pub struct WorldResources {
+    library: LazyHash<Library>,
+    book: LazyHash<FontBook>,
+    fonts: Vec<FontSlot>,
+    packages: PackageStorage,
+}
+
+impl WorldResources {
+    /// Search system and embedded fonts and set up the package cache
+    pub fn new() -> Self {
+        Self::with_inputs(&[])
+    }
+
+    /// Like [`WorldResources::new`], with `sys.inputs` set to the key-value pairs
+    pub fn with_inputs(inputs: &[(String, String)]) -> Self {
+        let inputs: Dict = inputs
+            .iter()
+            .map(|(key, value)| (key.as_str().into(), Value::Str(value.as_str().into())))
+            .collect();
+        let fonts = FontSearcher::new().include_system_fonts(true).search();
+        Self {
+            library: LazyHash::new(Library::builder().with_inputs(inputs).build()),
+            book: LazyHash::new(fonts.book),
+            fonts: fonts.fonts,
+            packages: PackageStorage::new(None, None, Downloader::new("typst-studio")),
+        }
+    }
+}
+
+impl Default for WorldResources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The compilation environment of one main file inside a project root
+pub struct StudioWorld {
+    root: PathBuf,
+    main: FileId,
+    resources: Arc<WorldResources>,
+    /// Contents of open editor buffers, used instead of the files on disk
+    overlay: HashMap<PathBuf, String>,
+    sources: Mutex<HashMap<FileId, FileResult<Source>>>,
+    files: Mutex<HashMap<FileId, FileResult<Bytes>>>,
+    now: DateTime<Local>,
+}
+
+impl StudioWorld {
+    pub fn new(root: &Path, main: &Path, resources: Arc<WorldResources>) -> Result<Self> {
+        let vpath = VirtualPath::within_root(main, root).ok_or_else(|| {
+            anyhow!("{} is outside of the project root {}", main.display(), root.display())
+        })?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            main: FileId::new(None, vpath),
+            resources,
+            overlay: HashMap::new(),
+            sources: Mutex::new(HashMap::new()),
+            files: Mutex::new(HashMap::new()),
+            now: Local::now(),
+        })
+    }
+
+    /// Read the given files from memory instead of disk, keyed by absolute path
+    pub fn with_overlay(mut self, overlay: HashMap<PathBuf, String>) -> Self {
+        self.overlay = overlay;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve a file id to a path on disk, downloading its package if needed
+    pub fn path(&self, id: FileId) -> FileResult<PathBuf> {
+        let root = match id.package() {
+            Some(spec) => self.resources.packages.prepare_package(spec, &mut ProgressSink)?,
+            None => self.root.clone(),
+        };
+        id.vpath().resolve(&root).ok_or(FileError::AccessDenied)
+    }
+
+    /// Resolve a span to its file, source and byte range
+    pub fn resolve_span(&self, span: Span) -> Option<ResolvedSpan> {
+        let id = span.id()?;
+        let source = self.source(id).ok()?;
+        let range = source.range(span)?;
+        Some(ResolvedSpan {
+            path: self.path(id).ok()?,
+            range,
+            source,
+        })
+    }
+
+    fn read(&self, id: FileId) -> FileResult<Vec<u8>> {
+        let path = self.path(id)?;
+        if let Some(text) = self.overlay.get(&path) {
+            return Ok(text.clone().into_bytes());
+        }
+        if path.is_dir() {
+            return Err(FileError::IsDirectory);
+        }
+        std::fs::read(&path).map_err(|err| FileError::from_io(err, &path))
+    }
+}
+
+impl World for StudioWorld {
+    fn library(&self) -> &LazyHash<Library> {
+        &self.resources.library
+    }
+
+    fn book(&self) -> &LazyHash<FontBook> {
+        &self.resources.book
+    }
+
+    fn main(&self) -> FileId {
+        self.main
+    }
+
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        self.sources
+            .lock()
+            .entry(id)
+            .or_insert_with(|| {
+                let bytes = self.read(id)?;
+                let text = String::from_utf8(bytes).map_err(|_| FileError::InvalidUtf8)?;
+                Ok(Source::new(id, text))
+            })
+            .clone()
+    }
+
+    fn file(&self, id: FileId) -> FileResult<Bytes> {
+        self.files
+            .lock()
+            .entry(id)
+            .or_insert_with(|| self.read(id).map(Bytes::new))
+            .clone()
+    }
+
+    fn font(&self, index: usize) -> Option<Font> {
+        self.resources.fonts.get(index)?.get()
+    }
+
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        let date = match offset {
+            None => self.now.date_naive(),
+            Some(hours) => (self.now.naive_utc() + Duration::hours(hours)).date(),
+        };
+        Datetime::from_ymd(date.year(), date.month().try_into().ok()?, date.day().try_into().ok()?)
+    }
+}
+
+/// A span resolved against the world's sources
+pub struct ResolvedSpan {
+    pub path: PathBuf,
+    pub source: Source,
+    pub range: Range<usize>,
+}
+
+impl ResolvedSpan {
+    /// Zero-based line and column of the start of the range
+    pub fn start(&self) -> (usize, usize) {
+        line_column(&self.source, self.range.start)
+    }
+
+    /// Zero-based line and column of the end of the range
+    pub fn end(&self) -> (usize, usize) {
+        line_column(&self.source, self.range.end)
+    }
+
+    /// Zero-based line and column of a byte offset into the range, as used
+    /// by glyph spans that point into the middle of a text node
+    pub fn at_offset(&self, offset: usize) -> (usize, usize) {
+        line_column(&self.source, (self.range.start + offset).min(self.range.end))
+    }
+}
+
+/// Zero-based line and column (in characters) of a byte offset
+pub fn line_column(source: &Source, byte: usize) -> (usize, usize) {
+    let lines = source.lines();
+    let line = lines.byte_to_line(byte).unwrap_or_else(|| lines.len_lines().saturating_sub(1));
+    let column = lines.byte_to_column(byte).unwrap_or(0);
+    (line, column)
+}
@@ -1,39 +1,277 @@
-use crate::theme::Theme;
+use crate::commands::command_key_binding;
+use crate::theme::{ check_contrast, Theme, ThemeRegistry, AA_CONTRAST_RATIO };
 use crate::workspace::MainWindow;
-use editor_core::{ ApplicationState, Config, WorkspaceState };
+use editor_core::session::SESSION_SAVE_INTERVAL;
+use editor_core::config::ConfigWatcher;
+use editor_core::keymap::Binding;
+use editor_core::{
+    AppearanceMonitor,
+    ApplicationState,
+    Config,
+    ConfigChange,
+    ConfigEvent,
+    ConfigService,
+    DesktopAppearance,
+    Keymap,
+    Platform,
+    Session,
+    WorkspaceState,
+};
 use gpui::*;
-use parking_lot::RwLock;
+use parking_lot::{ Mutex, RwLock };
 use std::sync::Arc;
 
+/// Events of the application, for the services it owns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppEvent {
+    /// The language server settings changed; a running server must restart to
+    /// pick them up
+    RestartLanguageServer,
+}
+
 pub struct TypstEditorApp {
     state: Arc<RwLock<ApplicationState>>,
     theme: Arc<RwLock<Theme>>,
+    config_service: Arc<ConfigService>,
+    _config_watcher: Option<ConfigWatcher>,
+    appearance: Arc<AppearanceMonitor>,
 }
 
 impl TypstEditorApp {
-    pub fn new(_cx: &mut Context<Self>) -> Self {
-        let config = Config::load();
-        let theme = if config.appearance.theme == "light" { Theme::light() } else { Theme::dark() };
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        let (config, errors) = Config::load_checked(None);
+        for error in &errors {
+            tracing::warn!("Configuration: {}", error);
+        }
+        let appearance = AppearanceMonitor::new(Arc::new(DesktopAppearance));
+        appearance.set_active(config.appearance.follows_system());
+        if let Err(err) = appearance.watch() {
+            tracing::warn!("Failed to follow the system appearance: {}", err);
+        }
+        let theme = load_theme(config.appearance.theme_for(appearance.current()));
 
         let state = ApplicationState::new(config);
+        let config_service = ConfigService::new(state.config.clone(), None);
+        let config_watcher = config_service
+            .watch()
+            .inspect_err(|err| tracing::warn!("Failed to watch configuration: {}", err))
+            .ok();
 
-        Self {
+        let app = Self {
             state: Arc::new(RwLock::new(state)),
             theme: Arc::new(RwLock::new(theme)),
+            config_service,
+            _config_watcher: config_watcher,
+            appearance,
+        };
+        app.apply_config_changes(cx);
+        app.apply_appearance_changes(cx);
+        app
+    }
+
+    /// Shared configuration, for services that subscribe to its changes
+    pub fn config_service(&self) -> Arc<ConfigService> {
+        self.config_service.clone()
+    }
+
+    /// Apply edits of the configuration files while the editor runs
+    fn apply_config_changes(&self, cx: &mut Context<Self>) {
+        let events = Arc::new(Mutex::new(self.config_service.subscribe()));
+        cx.spawn(async move |this, cx| {
+            loop {
+                let events = events.clone();
+                let event = cx
+                    .background_executor()
+                    .spawn(async move { events.lock().recv().ok() }).await;
+                let Some(event) = event else {
+                    break;
+                };
+                if this.update(cx, |this, cx| this.apply_config_event(event, cx)).is_err() {
+                    break;
+                }
+            }
+        }).detach();
+    }
+
+    fn apply_config_event(&mut self, event: ConfigEvent, cx: &mut Context<Self>) {
+        match event {
+            ConfigEvent::Invalid(errors) => {
+                for error in &errors {
+                    tracing::warn!("Configuration: {}", error);
+                }
+            }
+            ConfigEvent::Changed { config, changes } => {
+                let mut restart_language_server = false;
+                for change in changes {
+                    match change {
+                        ConfigChange::Theme => {
+                            self.appearance.set_active(config.appearance.follows_system());
+                            *self.theme.write() = load_theme(
+                                config.appearance.theme_for(self.appearance.current())
+                            );
+                        }
+                        ConfigChange::Keybindings => {
+                            cx.clear_key_bindings();
+                            bind_keys(&config, cx);
+                        }
+                        // Read from the shared configuration whenever the windows
+                        // render, save or compile, so redrawing them is enough
+                        ConfigChange::Font
+                        | ConfigChange::UiScale
+                        | ConfigChange::SidebarPosition
+                        | ConfigChange::Editor
+                        | ConfigChange::AutoSave
+                        | ConfigChange::Compiler => {}
+                        ConfigChange::LspServer | ConfigChange::Lsp => {
+                            restart_language_server = true;
+                        }
+                        // Nothing lays out bidirectional text yet
+                        ConfigChange::Bidi => {
+                            tracing::debug!("Configuration: {:?} changed", change);
+                        }
+                    }
+                }
+                if restart_language_server {
+                    cx.emit(AppEvent::RestartLanguageServer);
+                }
+                cx.refresh_windows();
+            }
         }
     }
 
+    /// Switch between the light and dark themes as the desktop's preference changes
+    fn apply_appearance_changes(&self, cx: &mut Context<Self>) {
+        let changes = Arc::new(Mutex::new(self.appearance.subscribe()));
+        cx.spawn(async move |this, cx| {
+            loop {
+                let changes = changes.clone();
+                let appearance = cx
+                    .background_executor()
+                    .spawn(async move { changes.lock().recv().ok() }).await;
+                let Some(appearance) = appearance else {
+                    break;
+                };
+                let result = this.update(cx, |this, cx| {
+                    let config = this.config_service.config();
+                    let config = config.read();
+                    if config.appearance.follows_system() {
+                        *this.theme.write() = load_theme(config.appearance.theme_for(appearance));
+                        cx.refresh_windows();
+                    }
+                });
+                if result.is_err() {
+                    break;
+                }
+            }
+        }).detach();
+    }
+
     pub fn open_main_window(&self, cx: &mut Context<Self>) {
         let state = self.state.clone();
         let theme = self.theme.clone();
+        let config_service = self.config_service.clone();
+
+        bind_keys(&self.state.read().config.read(), cx);
+
+        // Reopen the previous session, unsaved edits included
+        let session = Session::default_path().and_then(|path| Session::load(&path).ok());
 
         let window_id = cx
             .open_window(WindowOptions::default(), |window, cx| {
-                let workspace = WorkspaceState::new(0);
+                let workspace = session
+                    .as_ref()
+                    .and_then(|session| session.workspaces.first())
+                    .map(|workspace| workspace.restore(0))
+                    .unwrap_or_else(|| WorkspaceState::new(0));
                 state.write().add_window(0, workspace);
 
-                cx.new(|cx| MainWindow::new(state.clone(), theme.clone(), cx))
+                cx.new(|cx| {
+                    MainWindow::new(state.clone(), theme.clone(), config_service.clone(), window, cx)
+                })
             })
             .unwrap();
+
+        self.persist_session(cx);
+    }
+
+    /// Periodically write the session so a crash never loses unsaved work
+    fn persist_session(&self, cx: &mut Context<Self>) {
+        let Some(path) = Session::default_path() else {
+            return;
+        };
+        let state = self.state.clone();
+        cx.spawn(async move |_this, cx| {
+            let mut last_saved: Option<Session> = None;
+            loop {
+                cx.background_executor().timer(SESSION_SAVE_INTERVAL).await;
+                let session = Session::capture(&state.read());
+                if last_saved.as_ref() == Some(&session) {
+                    continue;
+                }
+
+                let path = path.clone();
+                let to_save = session.clone();
+                let result = cx
+                    .background_executor()
+                    .spawn(async move { to_save.save(&path) }).await;
+                match result {
+                    Ok(()) => {
+                        last_saved = Some(session);
+                    }
+                    Err(err) => tracing::warn!("Failed to save session: {}", err),
+                }
+            }
+        }).detach();
+    }
+}
+
+impl EventEmitter<AppEvent> for TypstEditorApp {}
+
+/// Look a theme up among the built-in ones and the user's theme files
+///
+/// The files are read again on every switch, so new and edited themes apply
+/// without a restart.
+fn load_theme(name: &str) -> Theme {
+    let (registry, errors) = ThemeRegistry::load();
+    for error in &errors {
+        tracing::warn!("Theme: {}", error);
+    }
+    let theme = registry
+        .get(name)
+        .cloned()
+        .unwrap_or_else(|| {
+            tracing::warn!("Theme: unknown theme '{}', using the default", name);
+            Theme::default()
+        });
+    for check in check_contrast(&theme).iter().filter(|check| !check.passes_aa()) {
+        tracing::warn!(
+            "Theme '{}': {} is below the {}:1 contrast WCAG AA requires",
+            theme.name,
+            check,
+            AA_CONTRAST_RATIO
+        );
+    }
+    theme
+}
+
+/// Bind the default keys and the user's overrides from the configuration
+fn bind_keys(config: &Config, cx: &mut App) {
+    let (keymap, errors) = Keymap::from_config(config, Platform::current());
+    for error in &errors {
+        tracing::warn!("Key bindings: {}", error);
+    }
+    for conflict in keymap.conflicts() {
+        tracing::warn!("Key bindings: {}", conflict);
+    }
+    cx.bind_keys(keymap.active_bindings().filter_map(key_binding));
+}
+
+fn key_binding(binding: &Binding) -> Option<KeyBinding> {
+    let keys = binding.keys.to_string();
+    let context = binding.context.as_ref().map(ToString::to_string);
+    let key_binding = command_key_binding(&binding.command, &keys, context.as_deref());
+    if key_binding.is_none() {
+        tracing::warn!("Key bindings: unknown command {} for '{}'", binding.command, keys);
     }
+    key_binding
 }
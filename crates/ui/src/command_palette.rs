@@ -0,0 +1,313 @@
+use crate::commands::{ command_action, command_shortcuts };
+use crate::components::Input;
+use crate::theme::Theme;
+use editor_core::commands::CommandMatch;
+use editor_core::{ ApplicationState, CommandRegistry };
+use gpui::*;
+use gpui::prelude::FluentBuilder;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A query starting with this jumps to a line instead of searching commands
+pub const GO_TO_LINE_PREFIX: &str = ":";
+
+/// Matches listed at once; the list scrolls with the selection
+const MAX_VISIBLE_MATCHES: usize = 12;
+
+/// Searchable list of every registered command
+pub struct CommandPalette {
+    theme: Arc<RwLock<Theme>>,
+    state: Arc<RwLock<ApplicationState>>,
+    commands: Arc<CommandRegistry>,
+    input: Entity<Input>,
+    focus_handle: FocusHandle,
+    /// Focus to return to when the palette closes
+    previous_focus: Option<FocusHandle>,
+    open: bool,
+    selected: usize,
+    shortcuts: HashMap<String, String>,
+}
+
+impl CommandPalette {
+    pub fn new(
+        theme: Arc<RwLock<Theme>>,
+        state: Arc<RwLock<ApplicationState>>,
+        commands: Arc<CommandRegistry>,
+        cx: &mut Context<Self>
+    ) -> Self {
+        Self {
+            theme: theme.clone(),
+            state,
+            commands,
+            input: cx.new(|_cx| Input::new(theme.clone(), "Type a command, or : and a line number")),
+            focus_handle: cx.focus_handle(),
+            previous_focus: None,
+            open: false,
+            selected: 0,
+            shortcuts: HashMap::new(),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Show the palette with an initial query, such as [`GO_TO_LINE_PREFIX`]
+    pub fn open(&mut self, query: &str, window: &mut Window, cx: &mut Context<Self>) {
+        // Read on every open so edited key bindings show up
+        self.shortcuts = command_shortcuts(&self.state.read().config.read());
+        if !self.open {
+            self.previous_focus = window.focused(cx);
+        }
+        self.open = true;
+        self.set_query(query.to_string(), cx);
+        window.focus(&self.focus_handle);
+    }
+
+    pub fn close(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.open = false;
+        if let Some(focus) = self.previous_focus.take() {
+            window.focus(&focus);
+        }
+        cx.notify();
+    }
+
+    fn query(&self, cx: &App) -> String {
+        self.input.read(cx).value().to_string()
+    }
+
+    fn set_query(&mut self, query: String, cx: &mut Context<Self>) {
+        self.input.update(cx, |input, _cx| input.set_value(query));
+        self.selected = 0;
+        cx.notify();
+    }
+
+    fn matches(&self, query: &str) -> Vec<CommandMatch<'_>> {
+        if query.starts_with(GO_TO_LINE_PREFIX) {
+            return Vec::new();
+        }
+        self.commands.search(query)
+    }
+
+    /// Run the selected command, or jump to the typed line
+    fn confirm(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let query = self.query(cx);
+        if let Some(line) = query.strip_prefix(GO_TO_LINE_PREFIX) {
+            let line = line.trim().parse::<usize>().ok();
+            self.close(window, cx);
+            if let Some(line) = line {
+                self.go_to_line(line);
+            }
+            return;
+        }
+
+        let id = self
+            .matches(&query)
+            .get(self.selected)
+            .map(|found| found.command.id);
+        self.close(window, cx);
+        // Dispatched from the restored focus, so editor and preview commands reach their panes
+        if let Some(action) = id.and_then(command_action) {
+            window.dispatch_action(action, cx);
+        }
+    }
+
+    /// Move the active editor's cursor to a one-based line
+    fn go_to_line(&self, line: usize) {
+        let editor = self.state
+            .read()
+            .get_active_workspace()
+            .and_then(|workspace| workspace.read().get_active_editor());
+        if let Some(editor) = editor {
+            editor.write().set_cursor_position(line.saturating_sub(1), 0);
+        }
+    }
+
+    fn active_line_count(&self) -> Option<usize> {
+        let editor = self.state
+            .read()
+            .get_active_workspace()
+            .and_then(|workspace| workspace.read().get_active_editor())?;
+        let count = editor.read().content.lines().count();
+        Some(count.max(1))
+    }
+
+    fn handle_key(&mut self, event: &KeyDownEvent, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let keystroke = &event.keystroke;
+        if !self.open || keystroke.modifiers.control || keystroke.modifiers.platform {
+            return false;
+        }
+
+        let mut query = self.query(cx);
+        match keystroke.key.as_str() {
+            "escape" => {
+                self.close(window, cx);
+                return true;
+            }
+            "enter" => {
+                self.confirm(window, cx);
+                return true;
+            }
+            "up" | "down" => {
+                let count = self.matches(&query).len();
+                if count > 0 {
+                    self.selected = if keystroke.key == "up" {
+                        (self.selected + count - 1) % count
+                    } else {
+                        (self.selected + 1) % count
+                    };
+                    cx.notify();
+                }
+                return true;
+            }
+            "backspace" => {
+                query.pop();
+            }
+            _ => {
+                let Some(text) = keystroke.key_char.as_ref() else {
+                    return false;
+                };
+                query.push_str(text);
+            }
+        }
+        self.set_query(query, cx);
+        true
+    }
+}
+
+impl Focusable for CommandPalette {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for CommandPalette {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        if !self.open {
+            return div();
+        }
+
+        let theme = self.theme.read();
+        let panel_color = theme.parse_color(&theme.background.panel);
+        let fg_color = theme.parse_color(&theme.foreground.panel);
+        let border_color = theme.parse_color(&theme.ui.border);
+        let selected_color = theme.parse_color(&theme.ui.button_hover);
+        let match_color = theme.parse_color(&theme.semantic.info);
+
+        let query = self.query(cx);
+        let hint = query.strip_prefix(GO_TO_LINE_PREFIX).map(|line| {
+            let lines = self.active_line_count();
+            match (line.trim().parse::<usize>(), lines) {
+                (_, None) => "No document is open".to_string(),
+                (Ok(line), Some(lines)) => format!("Go to line {} of {}", line, lines),
+                (Err(_), Some(lines)) => format!("Type a line number between 1 and {}", lines),
+            }
+        });
+
+        let matches = self.matches(&query);
+        let first_visible = (self.selected + 1).saturating_sub(MAX_VISIBLE_MATCHES);
+        let rows: Vec<_> = matches
+            .iter()
+            .enumerate()
+            .skip(first_visible)
+            .take(MAX_VISIBLE_MATCHES)
+            .map(|(index, found)| {
+                let shortcut = self.shortcuts.get(found.command.id).cloned();
+                div()
+                    .id(("command-palette-item", index))
+                    .w_full()
+                    .px_3()
+                    .py_1()
+                    .flex()
+                    .flex_row()
+                    .justify_between()
+                    .items_center()
+                    .cursor_pointer()
+                    .when(index == self.selected, |this| this.bg(selected_color))
+                    .hover(move |style| style.bg(selected_color))
+                    .on_click(
+                        cx.listener(move |this, _event, window, cx| {
+                            this.selected = index;
+                            this.confirm(window, cx);
+                        })
+                    )
+                    .child(highlighted_title(found, match_color))
+                    .when_some(shortcut, |this, shortcut| {
+                        this.child(div().text_xs().opacity(0.7).child(shortcut))
+                    })
+            })
+            .collect();
+        let no_matches = hint.is_none() && rows.is_empty();
+
+        // Full-window backdrop; clicking outside the list closes the palette
+        div()
+            .absolute()
+            .top_0()
+            .left_0()
+            .size_full()
+            .flex()
+            .flex_col()
+            .items_center()
+            .pt_16()
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(|this, _event, window, cx| this.close(window, cx))
+            )
+            .child(
+                div()
+                    .w_96()
+                    .flex()
+                    .flex_col()
+                    .p_1()
+                    .gap_1()
+                    .bg(panel_color)
+                    .text_color(fg_color)
+                    .border_1()
+                    .border_color(border_color)
+                    .rounded_md()
+                    .shadow_lg()
+                    .text_sm()
+                    .track_focus(&self.focus_handle)
+                    .on_mouse_down(MouseButton::Left, |_event, _window, cx| cx.stop_propagation())
+                    .on_key_down(
+                        cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                            if this.handle_key(event, window, cx) {
+                                cx.stop_propagation();
+                            }
+                        })
+                    )
+                    .child(self.input.clone())
+                    .when_some(hint, |this, hint| {
+                        this.child(div().px_3().py_1().opacity(0.7).child(hint))
+                    })
+                    .when(no_matches, |this| {
+                        this.child(div().px_3().py_1().opacity(0.7).child("No matching commands"))
+                    })
+                    .children(rows)
+            )
+    }
+}
+
+/// A command's palette title with the characters matching the query emphasized
+fn highlighted_title(found: &CommandMatch, match_color: Hsla) -> Div {
+    let mut runs: Vec<(bool, String)> = Vec::new();
+    for (index, c) in found.command.palette_title().chars().enumerate() {
+        let matched = found.positions.contains(&index);
+        match runs.last_mut() {
+            Some((run_matched, text)) if *run_matched == matched => text.push(c),
+            _ => runs.push((matched, c.to_string())),
+        }
+    }
+
+    div()
+        .flex()
+        .flex_row()
+        .children(
+            runs.into_iter().map(|(matched, text)| {
+                div()
+                    .when(matched, |this| this.font_weight(FontWeight::BOLD).text_color(match_color))
+                    .child(text)
+            })
+        )
+}
@@ -0,0 +1,69 @@
+use crate::components::{ UseCrLfLineEndings, UseLfLineEndings };
+use crate::preview_pane::{ CopySelection, FindInPreview, FindNext, FindPrevious, RevealInPreview };
+use editor_core::{ Config, Keymap, Platform };
+use gpui::*;
+use std::collections::HashMap;
+
+actions!(file, [NewFile, OpenFile, OpenFolder, SaveFile, SaveFileAs, ExportPdf]);
+actions!(edit, [GoToLine]);
+actions!(
+    view,
+    [ToggleCommandPalette, ToggleSidebar, TogglePreview, ToggleConsole, ZoomIn, ZoomOut, ResetZoom]
+);
+actions!(help, [OpenDocumentation]);
+
+/// Maps the ids of `editor_core::CommandRegistry` to their actions
+macro_rules! command_actions {
+    ($($id:literal => $action:expr),* $(,)?) => {
+        /// The action dispatched when a command runs
+        pub fn command_action(id: &str) -> Option<Box<dyn Action>> {
+            match id {
+                $($id => Some(Box::new($action)),)*
+                _ => None,
+            }
+        }
+
+        /// Bind keys to a command's action
+        pub fn command_key_binding(id: &str, keys: &str, context: Option<&str>) -> Option<KeyBinding> {
+            match id {
+                $($id => Some(KeyBinding::new(keys, $action, context)),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+command_actions! {
+    "file.new" => NewFile,
+    "file.open" => OpenFile,
+    "file.open_folder" => OpenFolder,
+    "file.save" => SaveFile,
+    "file.save_as" => SaveFileAs,
+    "file.export" => ExportPdf,
+    "edit.go_to_line" => GoToLine,
+    "view.command_palette" => ToggleCommandPalette,
+    "view.toggle_sidebar" => ToggleSidebar,
+    "view.toggle_preview" => TogglePreview,
+    "view.toggle_console" => ToggleConsole,
+    "view.zoom_in" => ZoomIn,
+    "view.zoom_out" => ZoomOut,
+    "view.reset_zoom" => ResetZoom,
+    "preview.reveal" => RevealInPreview,
+    "preview.copy" => CopySelection,
+    "preview.find" => FindInPreview,
+    "preview.find_next" => FindNext,
+    "preview.find_previous" => FindPrevious,
+    "document.use_lf_line_endings" => UseLfLineEndings,
+    "document.use_crlf_line_endings" => UseCrLfLineEndings,
+    "help.documentation" => OpenDocumentation,
+}
+
+/// The first key sequence bound to each command, as shown in menus
+pub fn command_shortcuts(config: &Config) -> HashMap<String, String> {
+    let (keymap, _errors) = Keymap::from_config(config, Platform::current());
+    let mut shortcuts = HashMap::new();
+    for binding in keymap.active_bindings() {
+        shortcuts.entry(binding.command.clone()).or_insert_with(|| binding.keys.to_string());
+    }
+    shortcuts
+}
@@ -17,6 +17,6 @@ pub use icon::{ Icon, IconSize, IconType };
 pub use input::Input;
 pub use scrollbar::Scrollbar;
 pub use splitter::{ SplitDirection, Splitter };
-pub use status_bar::StatusBar;
+pub use status_bar::{ StatusBar, UseCrLfLineEndings, UseLfLineEndings };
 pub use tabs::{ Tab, Tabs };
 pub use tooltip::Tooltip;
@@ -68,6 +68,10 @@ impl ContextMenu {
         self.items.push(item);
     }
 
+    pub fn set_items(&mut self, items: Vec<MenuItem>) {
+        self.items = items;
+    }
+
     pub fn show(&mut self) {
         self.visible = true;
     }
@@ -1,26 +1,49 @@
 use crate::theme::Theme;
+use editor_core::document::LineEnding;
+use editor_core::ApplicationState;
 use gpui::*;
+use gpui::prelude::FluentBuilder;
 use parking_lot::RwLock;
 use std::sync::Arc;
 
+actions!(document, [UseLfLineEndings, UseCrLfLineEndings]);
+
 pub struct StatusBar {
     theme: Arc<RwLock<Theme>>,
+    state: Arc<RwLock<ApplicationState>>,
     left_items: Vec<String>,
-    right_items: Vec<String>,
+}
+
+/// Encoding and line endings of the active document
+struct DocumentFormat {
+    encoding: &'static str,
+    line_ending: LineEnding,
+    mixed: bool,
 }
 
 impl StatusBar {
-    pub fn new(theme: Arc<RwLock<Theme>>) -> Self {
+    pub fn new(theme: Arc<RwLock<Theme>>, state: Arc<RwLock<ApplicationState>>) -> Self {
         Self {
             theme,
+            state,
             left_items: vec!["Typst".to_string(), "Line 1, Col 1".to_string()],
-            right_items: vec!["UTF-8".to_string(), "LF".to_string()],
         }
     }
 
     pub fn set_position(&mut self, line: usize, col: usize) {
         self.left_items[1] = format!("Line {}, Col {}", line + 1, col + 1);
     }
+
+    fn document_format(&self) -> Option<DocumentFormat> {
+        let workspace = self.state.read().get_active_workspace()?;
+        let editor = workspace.read().get_active_editor()?;
+        let document = &editor.read().document;
+        Some(DocumentFormat {
+            encoding: document.encoding.label(),
+            line_ending: document.line_ending,
+            mixed: document.mixed_line_endings,
+        })
+    }
 }
 
 impl Render for StatusBar {
@@ -28,6 +51,8 @@ impl Render for StatusBar {
         let theme = self.theme.read();
         let bg_color = theme.parse_color(&theme.background.panel);
         let fg_color = theme.parse_color(&theme.foreground.panel);
+        let warning_color = theme.parse_color(&theme.semantic.warning);
+        let format = self.document_format();
 
         div()
             .h_6()
@@ -52,7 +77,29 @@ impl Render for StatusBar {
                     .flex()
                     .flex_row()
                     .gap_4()
-                    .children(self.right_items.iter().map(|item| div().child(item.clone())))
+                    .when_some(format, |this, format| {
+                        let label = if format.mixed {
+                            format!("Mixed ({})", format.line_ending.label())
+                        } else {
+                            format.line_ending.label().to_string()
+                        };
+                        // Clicking unifies mixed endings, otherwise switches between LF and CRLF
+                        let use_crlf = (format.line_ending == LineEnding::CrLf) == format.mixed;
+                        this.child(div().child(format.encoding)).child(
+                            div()
+                                .id("line-ending")
+                                .cursor_pointer()
+                                .when(format.mixed, |this| this.text_color(warning_color))
+                                .on_click(move |_event, window, cx| {
+                                    if use_crlf {
+                                        window.dispatch_action(Box::new(UseCrLfLineEndings), cx);
+                                    } else {
+                                        window.dispatch_action(Box::new(UseLfLineEndings), cx);
+                                    }
+                                })
+                                .child(label)
+                        )
+                    })
             )
     }
 }
@@ -1,5 +1,7 @@
 use crate::theme::Theme;
+use editor_core::keymap::EDITOR_CONTEXT;
 use editor_core::ApplicationState;
+use gpui::prelude::FluentBuilder;
 use gpui::*;
 use parking_lot::RwLock;
 use std::sync::Arc;
@@ -7,25 +9,42 @@ use std::sync::Arc;
 pub struct EditorPanel {
     theme: Arc<RwLock<Theme>>,
     state: Arc<RwLock<ApplicationState>>,
+    focus_handle: FocusHandle,
 }
 
 impl EditorPanel {
     pub fn new(
         theme: Arc<RwLock<Theme>>,
         state: Arc<RwLock<ApplicationState>>,
-        _cx: &mut Context<Self>
+        cx: &mut Context<Self>
     ) -> Self {
-        Self { theme, state }
+        Self {
+            theme,
+            state,
+            focus_handle: cx.focus_handle(),
+        }
+    }
+}
+
+impl Focusable for EditorPanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
     }
 }
 
 impl Render for EditorPanel {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = self.theme.read();
         let bg_color = theme.parse_color(&theme.background.editor);
         let fg_color = theme.parse_color(&theme.foreground.editor);
         let gutter_bg = theme.parse_color(&theme.background.gutter);
         let gutter_fg = theme.parse_color(&theme.foreground.gutter);
+        let (font_family, font_size, line_height, line_numbers) = {
+            let state = self.state.read();
+            let config = state.config.read();
+            let editor = &config.editor;
+            (editor.font_family.clone(), editor.font_size, editor.line_height, editor.line_numbers)
+        };
 
         // Get active document content
         let content = if let Some(workspace) = self.state.read().get_active_workspace() {
@@ -48,28 +67,37 @@ impl Render for EditorPanel {
             .flex_1()
             .flex()
             .flex_row()
+            .track_focus(&self.focus_handle)
+            .key_context(EDITOR_CONTEXT)
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(|this, _event, window, _cx| window.focus(&this.focus_handle))
+            )
             .bg(bg_color)
             .text_color(fg_color)
             // Line numbers gutter
-            .child(
-                div()
-                    .w_12()
-                    .h_full()
-                    .bg(gutter_bg)
-                    .text_color(gutter_fg)
-                    .flex()
-                    .flex_col()
-                    .p_2()
-                    .text_xs()
-                    .children((1..=20).map(|i| div().child(format!("{}", i))))
-            )
+            .when(line_numbers, |this| {
+                this.child(
+                    div()
+                        .w_12()
+                        .h_full()
+                        .bg(gutter_bg)
+                        .text_color(gutter_fg)
+                        .flex()
+                        .flex_col()
+                        .p_2()
+                        .text_xs()
+                        .children((1..=20).map(|i| div().child(format!("{}", i))))
+                )
+            })
             // Editor content
             .child(
                 div()
                     .flex_1()
                     .p_2()
-                    .font_family("monospace")
-                    .text_sm()
+                    .font_family(font_family)
+                    .text_size(px(font_size as f32))
+                    .line_height(relative(line_height))
                     //TODO: add scroll on overflow
                     .child(div().whitespace_normal().child(content))
             )
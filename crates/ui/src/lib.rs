@@ -1,4 +1,6 @@
 pub mod app;
+pub mod commands;
+pub mod command_palette;
 pub mod theme;
 pub mod components;
 pub mod workspace;
@@ -8,6 +10,6 @@ pub mod sidebar;
 pub mod navbar;
 pub mod console;
 
-pub use app::TypstEditorApp;
+pub use app::{ AppEvent, TypstEditorApp };
 pub use theme::Theme;
 
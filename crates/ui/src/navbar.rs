@@ -1,32 +1,130 @@
-use crate::{ components::{ Button, ButtonVariant }, theme::Theme };
+use crate::commands::{ command_action, command_shortcuts, ToggleCommandPalette };
+use crate::{ components::{ Button, ButtonVariant, ContextMenu, MenuItem }, theme::Theme };
+use editor_core::{ ApplicationState, CommandCategory, CommandRegistry };
 use gpui::*;
+use gpui::prelude::FluentBuilder;
 use parking_lot::RwLock;
 use std::sync::Arc;
 
+/// Categories with a menu in the bar, in display order
+const MENUS: [CommandCategory; 4] = [
+    CommandCategory::File,
+    CommandCategory::Edit,
+    CommandCategory::View,
+    CommandCategory::Help,
+];
+
 pub struct NavBar {
     theme: Arc<RwLock<Theme>>,
-    file_button: Entity<Button>,
-    edit_button: Entity<Button>,
-    view_button: Entity<Button>,
-    help_button: Entity<Button>,
+    state: Arc<RwLock<ApplicationState>>,
+    commands: Arc<CommandRegistry>,
+    menu_buttons: Vec<(CommandCategory, Entity<Button>)>,
+    menu: Entity<ContextMenu>,
+    open_menu: Option<CommandCategory>,
 }
 
 impl NavBar {
-    pub fn new(theme: Arc<RwLock<Theme>>, cx: &mut Context<Self>) -> Self {
-        let file_button = cx.new(|_cx| Button::new("File", ButtonVariant::Primary, theme.clone()));
-        let edit_button = cx.new(|_cx| Button::new("Edit", ButtonVariant::Primary, theme.clone()));
-        let view_button = cx.new(|_cx| Button::new("View", ButtonVariant::Primary, theme.clone()));
-        let help_button = cx.new(|_cx| Button::new("Help", ButtonVariant::Primary, theme.clone()));
-        Self { theme, file_button, edit_button, view_button, help_button }
+    pub fn new(
+        theme: Arc<RwLock<Theme>>,
+        state: Arc<RwLock<ApplicationState>>,
+        commands: Arc<CommandRegistry>,
+        cx: &mut Context<Self>
+    ) -> Self {
+        let menu_buttons = MENUS.iter()
+            .map(|&category| {
+                let button = cx.new(|_cx| {
+                    Button::new(category.label(), ButtonVariant::Primary, theme.clone())
+                });
+                (category, button)
+            })
+            .collect();
+        let menu = cx.new(|_cx| ContextMenu::new(theme.clone()));
+        Self { theme, state, commands, menu_buttons, menu, open_menu: None }
+    }
+
+    fn toggle_menu(&mut self, category: CommandCategory, cx: &mut Context<Self>) {
+        if self.open_menu == Some(category) {
+            self.close_menu(cx);
+            return;
+        }
+
+        let shortcuts = command_shortcuts(&self.state.read().config.read());
+        let items = self.commands
+            .in_category(category)
+            .map(|command| {
+                let id = command.id;
+                let item = MenuItem::new(command.title).on_select(move |cx| {
+                    // Runs once the click is handled, when the window can take the action
+                    cx.defer(move |cx| {
+                        if let Some(action) = command_action(id) {
+                            cx.dispatch_action(action.as_ref());
+                        }
+                    });
+                });
+                match shortcuts.get(id) {
+                    Some(shortcut) => item.shortcut(shortcut.clone()),
+                    None => item,
+                }
+            })
+            .collect();
+        self.menu.update(cx, |menu, _cx| {
+            menu.set_items(items);
+            menu.show();
+        });
+        self.open_menu = Some(category);
+        cx.notify();
+    }
+
+    fn close_menu(&mut self, cx: &mut Context<Self>) {
+        if self.open_menu.take().is_some() {
+            self.menu.update(cx, |menu, _cx| menu.hide());
+            cx.notify();
+        }
     }
 }
 
 impl Render for NavBar {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = self.theme.read();
         let bg_color = theme.parse_color(&theme.background.titlebar);
         let fg_color = theme.parse_color(&theme.foreground.titlebar);
 
+        let menus = self.menu_buttons.iter().map(|(category, button)| {
+            let category = *category;
+            div()
+                .relative()
+                .on_mouse_down_out(
+                    cx.listener(move |this, _event, _window, cx| {
+                        // Another menu may have opened by the same click
+                        if this.open_menu == Some(category) {
+                            this.close_menu(cx);
+                        }
+                    })
+                )
+                .on_mouse_down(
+                    MouseButton::Left,
+                    cx.listener(move |this, _event, _window, cx| this.toggle_menu(category, cx))
+                )
+                .child(button.clone())
+                .when(self.open_menu == Some(category), |this| {
+                    this.child(
+                        div()
+                            .absolute()
+                            .top_6()
+                            .left_0()
+                            // Choosing an item closes the menu
+                            .on_mouse_down(
+                                MouseButton::Left,
+                                cx.listener(|this, _event, _window, cx| {
+                                    cx.stop_propagation();
+                                    this.close_menu(cx);
+                                })
+                            )
+                            .child(self.menu.clone())
+                    )
+                })
+        });
+
         div()
             .h_10()
             .w_full()
@@ -46,17 +144,7 @@ impl Render for NavBar {
                     .gap_4()
                     .items_center()
                     .child(div().font_weight(FontWeight::BOLD).text_lg().child("Typst Studio"))
-                    .child(
-                        div()
-                            .flex()
-                            .flex_row()
-                            .gap_2()
-                            .text_sm()
-                            .child(self.file_button.clone())
-                            .child(self.edit_button.clone())
-                            .child(self.view_button.clone())
-                            .child(self.help_button.clone())
-                    )
+                    .child(div().flex().flex_row().gap_2().text_sm().children(menus))
             )
 
             .child(
@@ -65,7 +153,15 @@ impl Render for NavBar {
                     .flex_row()
                     .gap_2()
                     .text_sm()
-                    .child(div().child("🔍"))
+                    .child(
+                        div()
+                            .id("open-command-palette")
+                            .cursor_pointer()
+                            .on_click(|_event, window, cx| {
+                                window.dispatch_action(Box::new(ToggleCommandPalette), cx);
+                            })
+                            .child("🔍")
+                    )
                     .child(div().child("⚙️"))
             )
     }
@@ -1,40 +1,462 @@
+use crate::components::Input;
 use crate::theme::Theme;
+use editor_core::keymap::PREVIEW_CONTEXT;
+use editor_core::ApplicationState;
 use gpui::*;
+use gpui::prelude::FluentBuilder;
 use parking_lot::RwLock;
+use preview::links::{ DocumentLinks, LinkTarget, OutlineItem };
+use preview::search::{ PreviewSearch, SearchQuery };
+use preview::sync::{ PreviewPosition, PreviewRect, SyncManager, HIGHLIGHT_DURATION };
+use preview::text::{ DocumentText, TextPosition, TextSelection };
+use preview::Viewport;
+use std::cell::Cell;
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Instant;
+use typst_integration::CompiledDocument;
+
+actions!(preview, [RevealInPreview, CopySelection, FindInPreview, FindNext, FindPrevious]);
 
 pub struct PreviewPane {
     theme: Arc<RwLock<Theme>>,
+    state: Arc<RwLock<ApplicationState>>,
+    sync: SyncManager,
+    viewport: Viewport,
+    /// Rendered pages of the displayed document
+    pages: Vec<Arc<Image>>,
+    text: DocumentText,
+    links: DocumentLinks,
+    outline: Vec<OutlineItem>,
+    selection: Option<TextSelection>,
+    is_selecting: bool,
+    search: PreviewSearch,
+    find_open: bool,
+    find_input: Entity<Input>,
+    focus_handle: FocusHandle,
+    bounds: Rc<Cell<Bounds<Pixels>>>,
 }
 
 impl PreviewPane {
-    pub fn new(theme: Arc<RwLock<Theme>>, cx: &mut Context<Self>) -> Self {
-        Self { theme }
+    pub fn new(
+        theme: Arc<RwLock<Theme>>,
+        state: Arc<RwLock<ApplicationState>>,
+        cx: &mut Context<Self>
+    ) -> Self {
+        Self {
+            theme: theme.clone(),
+            state,
+            sync: SyncManager::new(),
+            viewport: Viewport::default(),
+            pages: Vec::new(),
+            text: DocumentText::default(),
+            links: DocumentLinks::default(),
+            outline: Vec::new(),
+            selection: None,
+            is_selecting: false,
+            search: PreviewSearch::new(),
+            find_open: false,
+            find_input: cx.new(|_cx| Input::new(theme.clone(), "Find in preview")),
+            focus_handle: cx.focus_handle(),
+            bounds: Rc::new(Cell::new(Bounds::default())),
+        }
+    }
+
+    /// Show a newly compiled document and its pages rendered to PNG
+    pub fn set_document(&mut self, compiled: &CompiledDocument, pages: Vec<Vec<u8>>, cx: &mut Context<Self>) {
+        self.sync.update_mapping(compiled.source_mapping.clone());
+        self.viewport.set_pages(compiled.page_sizes());
+        self.pages = pages
+            .into_iter()
+            .map(|png| Arc::new(Image::from_bytes(ImageFormat::Png, png)))
+            .collect();
+        self.text = compiled.text.clone();
+        self.links = compiled.links.clone();
+        self.outline = compiled.outline.clone();
+        self.selection = None;
+        self.search.refresh(&self.text);
+        cx.notify();
+    }
+
+    /// Lay the pages out for the pane's size, as measured while painting
+    fn set_viewport_size(&mut self, size: Size<Pixels>, cx: &mut Context<Self>) {
+        let (width, height) = (f32::from(size.width), f32::from(size.height));
+        if (self.viewport.width, self.viewport.height) != (width, height) {
+            self.viewport.set_size(width, height);
+            // The overlays of this frame were placed for the previous size
+            cx.notify();
+        }
+    }
+
+    /// Convert a window position to a point relative to the pane
+    fn local_point(&self, position: Point<Pixels>) -> (f32, f32) {
+        let origin = self.bounds.get().origin;
+        (f32::from(position.x - origin.x), f32::from(position.y - origin.y))
+    }
+
+    /// Heading tree of the displayed document
+    pub fn outline(&self) -> &[OutlineItem] {
+        &self.outline
+    }
+
+    /// Scroll to a position in the document, such as an outline entry
+    pub fn go_to(&mut self, position: PreviewPosition, cx: &mut Context<Self>) {
+        self.viewport.scroll_to_page_point(position.page, position.y);
+        cx.notify();
+    }
+
+    /// Follow the link under a point, if any
+    fn follow_link_at(&mut self, position: Point<Pixels>, cx: &mut Context<Self>) -> bool {
+        let (x, y) = self.local_point(position);
+        let Some(pos) = self.viewport.viewport_to_page(x, y) else {
+            return false;
+        };
+        let Some(link) = self.links.hit_test(pos.page, pos.x, pos.y) else {
+            return false;
+        };
+        match link.target.clone() {
+            LinkTarget::Url(url) => cx.open_url(&url),
+            LinkTarget::Position(target) => self.go_to(target, cx),
+        }
+        true
+    }
+
+    fn text_position_at(&self, position: Point<Pixels>) -> Option<TextPosition> {
+        let (x, y) = self.local_point(position);
+        let pos = self.viewport.viewport_to_page(x, y)?;
+        self.text.hit_test(pos.page, pos.x, pos.y)
+    }
+
+    fn start_selection(&mut self, position: Point<Pixels>) {
+        self.selection = self.text_position_at(position).map(TextSelection::new);
+        self.is_selecting = self.selection.is_some();
+    }
+
+    fn extend_selection(&mut self, position: Point<Pixels>) -> bool {
+        let Some(head) = self.text_position_at(position) else {
+            return false;
+        };
+        match self.selection.as_mut() {
+            Some(selection) if selection.head != head => {
+                selection.head = head;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Text of the current selection, if any
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection
+            .filter(|selection| !selection.is_empty())
+            .map(|selection| selection.text(&self.text))
+    }
+
+    fn copy_selection(&mut self, _: &CopySelection, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(text) = self.selected_text() {
+            cx.write_to_clipboard(ClipboardItem::new_string(text));
+        }
+    }
+
+    fn open_find(&mut self, _: &FindInPreview, window: &mut Window, cx: &mut Context<Self>) {
+        self.find_open = true;
+        window.focus(&self.focus_handle);
+        cx.notify();
+    }
+
+    fn close_find(&mut self, cx: &mut Context<Self>) {
+        self.find_open = false;
+        self.search.clear();
+        self.find_input.update(cx, |input, _cx| input.set_value(String::new()));
+        cx.notify();
+    }
+
+    fn find_next(&mut self, _: &FindNext, _window: &mut Window, cx: &mut Context<Self>) {
+        self.search.next(&mut self.viewport);
+        cx.notify();
+    }
+
+    fn find_previous(&mut self, _: &FindPrevious, _window: &mut Window, cx: &mut Context<Self>) {
+        self.search.previous(&mut self.viewport);
+        cx.notify();
+    }
+
+    /// Search for a new query and jump to its first match
+    pub fn set_search_query(&mut self, query: String, cx: &mut Context<Self>) {
+        self.search.search(&self.text, SearchQuery::new(query.clone()));
+        self.search.next(&mut self.viewport);
+        self.find_input.update(cx, |input, _cx| input.set_value(query));
+        cx.notify();
+    }
+
+    /// Edit the find query while the find bar is open
+    fn handle_find_key(&mut self, event: &KeyDownEvent, cx: &mut Context<Self>) -> bool {
+        let keystroke = &event.keystroke;
+        if !self.find_open || keystroke.modifiers.control || keystroke.modifiers.platform {
+            return false;
+        }
+
+        let mut query = self.search.query().text.clone();
+        match keystroke.key.as_str() {
+            "escape" => {
+                self.close_find(cx);
+                return true;
+            }
+            "enter" => {
+                if keystroke.modifiers.shift {
+                    self.search.previous(&mut self.viewport);
+                } else {
+                    self.search.next(&mut self.viewport);
+                }
+                cx.notify();
+                return true;
+            }
+            "backspace" => {
+                query.pop();
+            }
+            _ => {
+                let Some(text) = keystroke.key_char.as_ref() else {
+                    return false;
+                };
+                query.push_str(text);
+            }
+        }
+        self.set_search_query(query, cx);
+        true
+    }
+
+    /// Scroll to and briefly highlight the region produced by a source line
+    pub fn reveal_line(&mut self, file: &Path, line: usize, cx: &mut Context<Self>) {
+        if self.sync.reveal_line(file, line, &mut self.viewport).is_none() {
+            return;
+        }
+        cx.notify();
+        cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(HIGHLIGHT_DURATION).await;
+            this.update(cx, |this, cx| {
+                this.sync.clear_expired_highlight(Instant::now());
+                cx.notify();
+            }).ok();
+        }).detach();
+    }
+
+    /// Open the source position under a point of the preview
+    fn open_source_at(&mut self, position: Point<Pixels>) -> bool {
+        let (x, y) = self.local_point(position);
+        let Some((path, pos)) = self.sync.source_at(&self.viewport, x, y) else {
+            return false;
+        };
+
+        let Some(workspace) = self.state.read().get_active_workspace() else {
+            return false;
+        };
+        let result = workspace.write().open_file_at(&path, pos.line, pos.column);
+        if let Err(err) = result {
+            tracing::warn!("Failed to open {}: {}", path.display(), err);
+            return false;
+        }
+        true
+    }
+}
+
+impl Focusable for PreviewPane {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
     }
 }
 
 impl Render for PreviewPane {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = self.theme.read();
         let bg_color = theme.parse_color(&theme.background.preview);
         let fg_color = theme.parse_color(&theme.foreground.preview);
+        let highlight_color = theme.parse_color(&theme.semantic.info);
+        let selection_color = theme.parse_color(&theme.ui.selection_background);
+        let warning_color = theme.parse_color(&theme.semantic.warning);
+        let panel_color = theme.parse_color(&theme.background.panel);
+        let border_color = theme.parse_color(&theme.ui.border);
+
+        let now = Instant::now();
+        let highlight = self.sync.highlight(now).and_then(|highlight| {
+            self.viewport
+                .page_rect_to_viewport(highlight.page, &highlight.rect)
+                .map(|rect| (rect, highlight.opacity(now)))
+        });
+        let selection_rects = self.selection
+            .map(|selection| selection.viewport_rects(&self.text, &self.viewport))
+            .unwrap_or_default();
+        let page_images: Vec<(PreviewRect, Arc<Image>)> = self.viewport.pages
+            .iter()
+            .zip(&self.pages)
+            .enumerate()
+            .filter_map(|(page, (size, image))| {
+                let rect = PreviewRect::new(0.0, 0.0, size.width, size.height);
+                let rect = self.viewport.page_rect_to_viewport(page, &rect)?;
+                let is_visible = rect.y + rect.height > 0.0 && rect.y < self.viewport.height;
+                is_visible.then(|| (rect, image.clone()))
+            })
+            .collect();
+        let link_rects: Vec<PreviewRect> = self.links.links
+            .iter()
+            .filter_map(|link| self.viewport.page_rect_to_viewport(link.page, &link.rect))
+            .collect();
+        let current_match = self.search.current_index();
+        let match_rects: Vec<(PreviewRect, bool)> = self.search
+            .matches()
+            .iter()
+            .enumerate()
+            .flat_map(|(index, found)| {
+                let is_current = Some(index) == current_match;
+                found.rects.iter().map(move |(page, rect)| (*page, *rect, is_current))
+            })
+            .filter_map(|(page, rect, is_current)| {
+                self.viewport.page_rect_to_viewport(page, &rect).map(|rect| (rect, is_current))
+            })
+            .collect();
+        let match_count = match (current_match, self.search.matches().len()) {
+            (_, 0) => "No results".to_string(),
+            (Some(index), total) => format!("{} of {}", index + 1, total),
+            (None, total) => format!("{} results", total),
+        };
+        let bounds = self.bounds.clone();
+        let pane = cx.entity().downgrade();
 
         div()
             .flex_1()
+            .relative()
+            .overflow_hidden()
+            .track_focus(&self.focus_handle)
+            .key_context(PREVIEW_CONTEXT)
+            .on_action(cx.listener(Self::copy_selection))
+            .on_action(cx.listener(Self::open_find))
+            .on_action(cx.listener(Self::find_next))
+            .on_action(cx.listener(Self::find_previous))
+            .on_key_down(
+                cx.listener(|this, event: &KeyDownEvent, _window, cx| {
+                    if this.handle_find_key(event, cx) {
+                        cx.stop_propagation();
+                    }
+                })
+            )
             .bg(bg_color)
             .text_color(fg_color)
             .flex()
             .flex_col()
             .items_center()
             .justify_center()
+            .on_mouse_down(
+                MouseButton::Left,
+                cx.listener(|this, event: &MouseDownEvent, window, cx| {
+                    window.focus(&this.focus_handle);
+                    if event.modifiers.secondary() {
+                        if this.open_source_at(event.position) {
+                            window.refresh();
+                        }
+                    } else if !this.follow_link_at(event.position, cx) {
+                        this.start_selection(event.position);
+                        cx.notify();
+                    }
+                })
+            )
+            .on_mouse_move(
+                cx.listener(|this, event: &MouseMoveEvent, _window, cx| {
+                    let dragging = this.is_selecting && event.pressed_button == Some(MouseButton::Left);
+                    if dragging && this.extend_selection(event.position) {
+                        cx.notify();
+                    }
+                })
+            )
+            .on_mouse_up(
+                MouseButton::Left,
+                cx.listener(|this, _event: &MouseUpEvent, _window, _cx| {
+                    this.is_selecting = false;
+                })
+            )
+            .on_scroll_wheel(
+                cx.listener(|this, event: &ScrollWheelEvent, window, cx| {
+                    let delta = event.delta.pixel_delta(window.line_height());
+                    this.viewport.scroll_by(-f32::from(delta.x), -f32::from(delta.y));
+                    cx.notify();
+                })
+            )
             .child(
-                div()
-                    .flex()
-                    .flex_col()
-                    .items_center()
-                    .gap_4()
-                    .child(div().text_2xl().child("Preview"))
-                    .child(div().text_sm().opacity(0.7).child("PDF preview will appear here"))
+                canvas(
+                    move |element_bounds, _window, cx| {
+                        bounds.set(element_bounds);
+                        pane.update(cx, |pane, cx| pane.set_viewport_size(element_bounds.size, cx)).ok();
+                    },
+                    |_, _, _, _| {}
+                )
+                    .absolute()
+                    .size_full()
+            )
+            .when(self.viewport.pages.is_empty(), |this| {
+                this.child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .items_center()
+                        .gap_4()
+                        .child(div().text_2xl().child("Preview"))
+                        .child(div().text_sm().opacity(0.7).child("PDF preview will appear here"))
+                )
+            })
+            .children(
+                page_images.into_iter().map(|(rect, image)| {
+                    img(image)
+                        .absolute()
+                        .left(px(rect.x))
+                        .top(px(rect.y))
+                        .w(px(rect.width))
+                        .h(px(rect.height))
+                })
+            )
+            .children(link_rects.into_iter().map(|rect| overlay(&rect).cursor_pointer()))
+            .children(
+                match_rects.into_iter().map(|(rect, is_current)| {
+                    let opacity = if is_current { 0.6 } else { 0.3 };
+                    overlay(&rect).bg(warning_color.opacity(opacity))
+                })
             )
+            .children(
+                selection_rects
+                    .into_iter()
+                    .map(|(_, rect)| overlay(&rect).bg(selection_color.opacity(0.5)))
+            )
+            .when_some(highlight, |this, (rect, opacity)| {
+                this.child(overlay(&rect).rounded_sm().bg(highlight_color.opacity(0.35 * opacity)))
+            })
+            .when(self.find_open, |this| {
+                this.child(
+                    div()
+                        .absolute()
+                        .top_2()
+                        .right_2()
+                        .w_64()
+                        .flex()
+                        .flex_row()
+                        .items_center()
+                        .gap_2()
+                        .p_1()
+                        .bg(panel_color)
+                        .border_1()
+                        .border_color(border_color)
+                        .rounded_md()
+                        .text_xs()
+                        .child(div().flex_1().child(self.find_input.clone()))
+                        .child(div().opacity(0.7).child(match_count))
+                )
+            })
     }
 }
+
+/// Absolutely positioned box covering a rectangle in viewport coordinates
+fn overlay(rect: &PreviewRect) -> Div {
+    div()
+        .absolute()
+        .left(px(rect.x))
+        .top(px(rect.y))
+        .w(px(rect.width))
+        .h(px(rect.height))
+}
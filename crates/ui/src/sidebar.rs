@@ -1,9 +1,13 @@
+use crate::preview_pane::PreviewPane;
 use crate::theme::Theme;
 use editor_core::ApplicationState;
 use gpui::*;
+use gpui::prelude::FluentBuilder;
 use parking_lot::RwLock;
+use preview::links::OutlineItem;
 use std::sync::Arc;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum SidebarView {
     FileExplorer,
     Outline,
@@ -12,6 +16,7 @@ enum SidebarView {
 pub struct Sidebar {
     theme: Arc<RwLock<Theme>>,
     state: Arc<RwLock<ApplicationState>>,
+    preview: Entity<PreviewPane>,
     active_view: SidebarView,
 }
 
@@ -19,21 +24,104 @@ impl Sidebar {
     pub fn new(
         theme: Arc<RwLock<Theme>>,
         state: Arc<RwLock<ApplicationState>>,
+        preview: Entity<PreviewPane>,
         cx: &mut Context<Self>
     ) -> Self {
+        cx.observe(&preview, |_this, _preview, cx| cx.notify()).detach();
         Self {
             theme,
             state,
+            preview,
             active_view: SidebarView::FileExplorer,
         }
     }
+
+    fn view_tab(&self, label: &'static str, view: SidebarView, cx: &mut Context<Self>) -> impl IntoElement {
+        let is_active = self.active_view == view;
+        div()
+            .id(label)
+            .text_sm()
+            .cursor_pointer()
+            .when(is_active, |this| this.font_weight(FontWeight::BOLD))
+            .when(!is_active, |this| this.opacity(0.7))
+            .on_click(
+                cx.listener(move |this, _event, _window, cx| {
+                    this.active_view = view;
+                    cx.notify();
+                })
+            )
+            .child(label)
+    }
+
+    fn file_explorer(&self) -> Div {
+        div()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .text_sm()
+            .child(div().child("📄 untitled.typ"))
+            .child(div().opacity(0.6).child("No files open"))
+    }
+
+    fn outline(&self, cx: &mut Context<Self>) -> Div {
+        let mut rows = Vec::new();
+        flatten_outline(self.preview.read(cx).outline(), 0, &mut rows);
+
+        if rows.is_empty() {
+            return div().text_sm().child(div().opacity(0.6).child("No headings"));
+        }
+
+        let hover_color = {
+            let theme = self.theme.read();
+            theme.parse_color(&theme.ui.button_hover)
+        };
+        div()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .text_sm()
+            .children(
+                rows.into_iter().enumerate().map(|(index, (depth, item))| {
+                    let position = item.position;
+                    let preview = self.preview.clone();
+                    div()
+                        .id(("outline-item", index))
+                        .pl(px(12.0 * (depth as f32)))
+                        .cursor_pointer()
+                        .rounded_sm()
+                        .hover(move |style| style.bg(hover_color))
+                        .on_click(move |_event, _window, cx| {
+                            preview.update(cx, |preview, cx| preview.go_to(position, cx));
+                        })
+                        .child(item.title.clone())
+                })
+            )
+    }
+}
+
+/// Depth-first list of outline items with their nesting depth
+fn flatten_outline<'a>(items: &'a [OutlineItem], depth: usize, rows: &mut Vec<(usize, &'a OutlineItem)>) {
+    for item in items {
+        rows.push((depth, item));
+        flatten_outline(&item.children, depth + 1, rows);
+    }
 }
 
 impl Render for Sidebar {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
-        let theme = self.theme.read();
-        let bg_color = theme.parse_color(&theme.background.sidebar);
-        let fg_color = theme.parse_color(&theme.foreground.sidebar);
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let (bg_color, fg_color, border_color) = {
+            let theme = self.theme.read();
+            (
+                theme.parse_color(&theme.background.sidebar),
+                theme.parse_color(&theme.foreground.sidebar),
+                theme.parse_color(&theme.ui.border),
+            )
+        };
+
+        let content = match self.active_view {
+            SidebarView::FileExplorer => self.file_explorer(),
+            SidebarView::Outline => self.outline(cx),
+        };
 
         div()
             .w_64()
@@ -43,7 +131,7 @@ impl Render for Sidebar {
             .flex()
             .flex_col()
             .border_r_1()
-            .border_color(theme.parse_color(&theme.ui.border))
+            .border_color(border_color)
             // Sidebar tabs
             .child(
                 div()
@@ -55,8 +143,9 @@ impl Render for Sidebar {
                     .px_2()
                     .gap_2()
                     .border_b_1()
-                    .border_color(theme.parse_color(&theme.ui.border))
-                    .child(div().text_sm().font_weight(FontWeight::BOLD).child("Explorer"))
+                    .border_color(border_color)
+                    .child(self.view_tab("Explorer", SidebarView::FileExplorer, cx))
+                    .child(self.view_tab("Outline", SidebarView::Outline, cx))
             )
             // Content
             .child(
@@ -64,15 +153,7 @@ impl Render for Sidebar {
                     .flex_1()
                     .p_2()
                     //TODO: add scroll on overflow
-                    .child(
-                        div()
-                            .flex()
-                            .flex_col()
-                            .gap_1()
-                            .text_sm()
-                            .child(div().child("📄 untitled.typ"))
-                            .child(div().opacity(0.6).child("No files open"))
-                    )
+                    .child(content)
             )
     }
 }
@@ -1,5 +1,18 @@
-use gpui::{ Hsla, Rgba };
+use gpui::Hsla;
+use parking_lot::Mutex;
 use serde::{ Deserialize, Serialize };
+use std::collections::HashMap;
+use std::sync::Arc;
+
+mod color;
+mod contrast;
+mod registry;
+mod vscode;
+
+pub use color::{ parse_color, ColorError };
+pub use contrast::{ check_contrast, contrast_ratio, relative_luminance, ContrastCheck, AA_CONTRAST_RATIO };
+pub use registry::{ ThemeError, ThemeRegistry, ThemeSource };
+pub use vscode::{ import_vscode_theme, import_vscode_theme_file, ImportReport, VsCodeImport };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Theme {
@@ -9,8 +22,14 @@ pub struct Theme {
     pub semantic: SemanticColors,
     pub ui: UiColors,
     pub syntax: SyntaxColors,
+    #[serde(skip)]
+    color_cache: ColorCache,
 }
 
+/// Colors already parsed by [`Theme::parse_color`], shared by clones of a theme
+#[derive(Debug, Clone, Default)]
+struct ColorCache(Arc<Mutex<HashMap<String, Hsla>>>);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThemeColors {
     pub editor: String,
@@ -121,6 +140,7 @@ impl Theme {
                 link: "#3794ff".to_string(),
                 code: "#ce9178".to_string(),
             },
+            color_cache: ColorCache::default(),
         }
     }
 
@@ -180,24 +200,56 @@ impl Theme {
                 link: "#0000ff".to_string(),
                 code: "#a31515".to_string(),
             },
+            color_cache: ColorCache::default(),
         }
     }
 
+    /// Pure black and white theme, with saturated accents
+    pub fn high_contrast_dark() -> Self {
+        Self::from_asset(include_str!("../../../assets/themes/high_contrast_dark.json"))
+    }
+
+    /// Pure white and black theme, with dark accents
+    pub fn high_contrast_light() -> Self {
+        Self::from_asset(include_str!("../../../assets/themes/high_contrast_light.json"))
+    }
+
+    fn from_asset(json: &str) -> Self {
+        serde_json::from_str(json).expect("built-in themes are valid")
+    }
+
+    /// Convert a theme color for drawing, caching the result
+    ///
+    /// Invalid colors are transparent; [`Theme::color_errors`] reports them
+    /// when the theme is loaded.
     pub fn parse_color(&self, color_str: &str) -> Hsla {
-        // Parse hex color string to GPUI color
-        if let Some(stripped) = color_str.strip_prefix('#') {
-            let r = (u8::from_str_radix(&stripped[0..2], 16).unwrap_or(0) as f32) / 255.0;
-            let g = (u8::from_str_radix(&stripped[2..4], 16).unwrap_or(0) as f32) / 255.0;
-            let b = (u8::from_str_radix(&stripped[4..6], 16).unwrap_or(0) as f32) / 255.0;
-            let a = if stripped.len() > 6 {
-                (u8::from_str_radix(&stripped[6..8], 16).unwrap_or(255) as f32) / 255.0
-            } else {
-                1.0
-            };
-            Hsla::from(Rgba { r, g, b, a })
-        } else {
-            Hsla::default()
+        let mut cache = self.color_cache.0.lock();
+        if let Some(color) = cache.get(color_str) {
+            return *color;
+        }
+        let color = parse_color(color_str).map(Hsla::from).unwrap_or_default();
+        cache.insert(color_str.to_string(), color);
+        color
+    }
+
+    /// Every color that fails to parse, by its dotted key such as `ui.border`
+    pub fn color_errors(&self) -> Vec<(String, ColorError)> {
+        let mut errors = Vec::new();
+        if let Ok(serde_json::Value::Object(groups)) = serde_json::to_value(self) {
+            for (group, colors) in groups {
+                let serde_json::Value::Object(colors) = colors else {
+                    continue;
+                };
+                for (key, value) in colors {
+                    if let serde_json::Value::String(color) = value {
+                        if let Err(error) = parse_color(&color) {
+                            errors.push((format!("{}.{}", group, key), error));
+                        }
+                    }
+                }
+            }
         }
+        errors
     }
 }
 
@@ -0,0 +1,399 @@
+use gpui::Rgba;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid color '{input}': {reason}")]
+pub struct ColorError {
+    pub input: String,
+    pub reason: String,
+}
+
+impl ColorError {
+    fn new(input: &str, reason: impl Into<String>) -> Self {
+        Self {
+            input: input.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Parse a CSS-style color: `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`,
+/// `rgb()`/`rgba()`, `hsl()`/`hsla()` or a named color such as `teal`
+///
+/// Functions take their arguments separated by commas or spaces, with an
+/// optional alpha after a `/` in the space-separated form.
+pub fn parse_color(input: &str) -> Result<Rgba, ColorError> {
+    let color = input.trim();
+    if color.is_empty() {
+        return Err(ColorError::new(input, "empty color"));
+    }
+    if let Some(hex) = color.strip_prefix('#') {
+        return parse_hex(hex).ok_or_else(|| {
+            ColorError::new(input, "expected #rgb, #rgba, #rrggbb or #rrggbbaa")
+        });
+    }
+
+    let lowercase = color.to_ascii_lowercase();
+    if let Some((function, rest)) = lowercase.split_once('(') {
+        let arguments = rest
+            .strip_suffix(')')
+            .ok_or_else(|| ColorError::new(input, "missing ')'"))?;
+        return parse_function(function.trim(), arguments).map_err(|reason| ColorError::new(input, reason));
+    }
+
+    named_color(&lowercase).ok_or_else(|| ColorError::new(input, "unknown color name"))
+}
+
+fn parse_hex(hex: &str) -> Option<Rgba> {
+    if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |index: usize| u8::from_str_radix(&hex[index..index + 1], 16).ok();
+    let pair = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+    // A single digit d stands for dd
+    let short = |index: usize| digit(index).map(|value| value * 17);
+
+    let [r, g, b, a] = match hex.len() {
+        3 => [short(0)?, short(1)?, short(2)?, 255],
+        4 => [short(0)?, short(1)?, short(2)?, short(3)?],
+        6 => [pair(0)?, pair(2)?, pair(4)?, 255],
+        8 => [pair(0)?, pair(2)?, pair(4)?, pair(6)?],
+        _ => {
+            return None;
+        }
+    };
+    Some(rgb8(r, g, b, a))
+}
+
+fn parse_function(function: &str, arguments: &str) -> Result<Rgba, String> {
+    let (channels, alpha) = split_arguments(arguments)?;
+    let alpha = match alpha {
+        Some(alpha) => parse_fraction(alpha, 1.0)?,
+        None => 1.0,
+    };
+
+    match function {
+        "rgb" | "rgba" => {
+            let [r, g, b] = channels;
+            Ok(Rgba {
+                r: parse_fraction(r, 255.0)?,
+                g: parse_fraction(g, 255.0)?,
+                b: parse_fraction(b, 255.0)?,
+                a: alpha,
+            })
+        }
+        "hsl" | "hsla" => {
+            let [h, s, l] = channels;
+            let hue = h.strip_suffix("deg").unwrap_or(h);
+            let hue = parse_number(hue)?.rem_euclid(360.0) / 360.0;
+            // Saturation and lightness are percentages, with or without the sign
+            let percent = |value: &str| parse_number(value.strip_suffix('%').unwrap_or(value));
+            let saturation = (percent(s)? / 100.0).clamp(0.0, 1.0);
+            let lightness = (percent(l)? / 100.0).clamp(0.0, 1.0);
+            Ok(hsl_to_rgb(hue, saturation, lightness, alpha))
+        }
+        _ => Err(format!("unknown color function '{}'", function)),
+    }
+}
+
+/// Three channels and an optional alpha, from `a, b, c[, d]` or `a b c[ / d]`
+fn split_arguments(arguments: &str) -> Result<([&str; 3], Option<&str>), String> {
+    let (channels, slash_alpha) = match arguments.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha.trim())),
+        None => (arguments, None),
+    };
+    let parts: Vec<&str> = if channels.contains(',') {
+        channels.split(',').map(str::trim).collect()
+    } else {
+        channels.split_whitespace().collect()
+    };
+
+    match (parts.as_slice(), slash_alpha) {
+        ([a, b, c], alpha) => Ok(([a, b, c], alpha)),
+        ([a, b, c, alpha], None) => Ok(([a, b, c], Some(alpha))),
+        _ => Err(format!("expected 3 or 4 values, found '{}'", arguments.trim())),
+    }
+}
+
+/// A value in `0..=max`, or a percentage of it, as a fraction in `0..=1`
+fn parse_fraction(value: &str, max: f32) -> Result<f32, String> {
+    let fraction = match value.strip_suffix('%') {
+        Some(percent) => parse_number(percent)? / 100.0,
+        None => parse_number(value)? / max,
+    };
+    Ok(fraction.clamp(0.0, 1.0))
+}
+
+fn parse_number(value: &str) -> Result<f32, String> {
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("'{}' is not a number", value.trim()))
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Rgba {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let channel = |offset: f32| {
+        let k = (offset + hue * 12.0) % 12.0;
+        lightness - chroma / 2.0 * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    Rgba {
+        r: channel(0.0),
+        g: channel(8.0),
+        b: channel(4.0),
+        a: alpha,
+    }
+}
+
+fn rgb8(r: u8, g: u8, b: u8, a: u8) -> Rgba {
+    Rgba {
+        r: (r as f32) / 255.0,
+        g: (g as f32) / 255.0,
+        b: (b as f32) / 255.0,
+        a: (a as f32) / 255.0,
+    }
+}
+
+fn named_color(name: &str) -> Option<Rgba> {
+    if name == "transparent" {
+        return Some(rgb8(0, 0, 0, 0));
+    }
+    let hex = NAMED_COLORS.iter().find(|(named, _)| *named == name)?.1;
+    let [_, r, g, b] = hex.to_be_bytes();
+    Some(rgb8(r, g, b, 255))
+}
+
+/// The CSS named colors
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `input` parses to the 8-bit channels, within rounding
+    fn parses_to(input: &str, [r, g, b, a]: [u8; 4]) -> bool {
+        let color = parse_color(input).unwrap_or_else(|err| panic!("{}", err));
+        let expected = rgb8(r, g, b, a);
+        [(color.r, expected.r), (color.g, expected.g), (color.b, expected.b), (color.a, expected.a)]
+            .iter()
+            .all(|(actual, expected)| (actual - expected).abs() < 1.0 / 255.0)
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert!(parses_to("#1e1e1e", [0x1e, 0x1e, 0x1e, 255]));
+        assert!(parses_to("#f0a", [0xff, 0x00, 0xaa, 255]));
+        assert!(parses_to("#f0a8", [0xff, 0x00, 0xaa, 0x88]));
+        assert!(parses_to("#264F7880", [0x26, 0x4f, 0x78, 0x80]));
+        assert!(parses_to("  #ffffff  ", [255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn parses_rgb_functions() {
+        assert!(parses_to("rgb(255, 128, 0)", [255, 128, 0, 255]));
+        assert!(parses_to("rgba(255, 128, 0, 0.5)", [255, 128, 0, 128]));
+        assert!(parses_to("rgb(255 128 0 / 50%)", [255, 128, 0, 128]));
+        assert!(parses_to("RGB(100% 0% 0% / 0.25)", [255, 0, 0, 64]));
+        // Out of range values are clamped
+        assert!(parses_to("rgb(300, -5, 0)", [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn parses_hsl_functions() {
+        assert!(parses_to("hsl(0, 100%, 50%)", [255, 0, 0, 255]));
+        assert!(parses_to("hsl(120deg 100% 25%)", [0, 128, 0, 255]));
+        assert!(parses_to("hsla(240, 100%, 50%, 0.5)", [0, 0, 255, 128]));
+        assert!(parses_to("hsl(0 0% 100% / 0)", [255, 255, 255, 0]));
+        // Hues wrap around the circle in both directions
+        assert!(parses_to("hsl(360, 100%, 50%)", [255, 0, 0, 255]));
+        assert!(parses_to("hsl(480, 100%, 50%)", [0, 255, 0, 255]));
+        assert!(parses_to("hsl(-120, 100%, 50%)", [0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn parses_named_colors() {
+        assert!(parses_to("teal", [0, 128, 128, 255]));
+        assert!(parses_to("RebeccaPurple", [0x66, 0x33, 0x99, 255]));
+        assert!(parses_to("transparent", [0, 0, 0, 0]));
+        assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn rejects_invalid_colors() {
+        for input in [
+            "",
+            "   ",
+            "#",
+            "#12",
+            "#12345",
+            "#1234567",
+            "#gggggg",
+            "#ééé",
+            "rgb(1, 2)",
+            "rgb(1, 2, 3, 4, 5)",
+            "rgb(1 2 3 / 4 / 5)",
+            "rgb(1, 2, 3",
+            "rgb(a, b, c)",
+            "rgb(inf, 0, 0)",
+            "hsl(x, 50%, 50%)",
+            "cmyk(0, 0, 0)",
+            "notacolor",
+        ] {
+            assert!(parse_color(input).is_err(), "{:?} parsed", input);
+        }
+        let error = parse_color("rgb(1, 2)").unwrap_err();
+        assert_eq!(error.input, "rgb(1, 2)");
+        assert_eq!(error.to_string(), "invalid color 'rgb(1, 2)': expected 3 or 4 values, found '1, 2'");
+    }
+}
@@ -0,0 +1,180 @@
+use super::{ parse_color, Theme };
+use gpui::Rgba;
+use serde_json::Value;
+use std::fmt;
+
+/// Lowest contrast ratio WCAG 2 level AA allows for normal text
+pub const AA_CONTRAST_RATIO: f32 = 4.5;
+
+/// Text colors checked against the background they are drawn on, by dotted key
+const CONTRAST_PAIRS: [(&str, &str); 11] = [
+    ("foreground.editor", "background.editor"),
+    ("foreground.gutter", "background.gutter"),
+    ("foreground.sidebar", "background.sidebar"),
+    ("foreground.preview", "background.preview"),
+    ("foreground.titlebar", "background.titlebar"),
+    ("foreground.panel", "background.panel"),
+    ("ui.selection_foreground", "ui.selection_background"),
+    ("semantic.error", "background.editor"),
+    ("semantic.warning", "background.editor"),
+    ("semantic.info", "background.editor"),
+    ("semantic.hint", "background.editor"),
+];
+
+/// Backdrop of translucent backgrounds
+const BACKDROP_KEY: &str = "background.editor";
+
+/// Contrast of a theme's text color with its background
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContrastCheck {
+    pub foreground: &'static str,
+    pub background: &'static str,
+    /// From 1 for identical colors to 21 for black on white
+    pub ratio: f32,
+}
+
+impl ContrastCheck {
+    pub fn passes_aa(&self) -> bool {
+        self.ratio >= AA_CONTRAST_RATIO
+    }
+}
+
+impl fmt::Display for ContrastCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on {}: {:.2}:1", self.foreground, self.background, self.ratio)
+    }
+}
+
+/// WCAG contrast ratios of every text color of `theme` with its background
+///
+/// Translucent colors are blended over what they are drawn on first. Pairs
+/// with an invalid color are left out; [`Theme::color_errors`] reports those.
+pub fn check_contrast(theme: &Theme) -> Vec<ContrastCheck> {
+    let Ok(value) = serde_json::to_value(theme) else {
+        return Vec::new();
+    };
+    let color = |key: &str| {
+        let pointer = format!("/{}", key.replace('.', "/"));
+        match value.pointer(&pointer) {
+            Some(Value::String(color)) => parse_color(color).ok(),
+            _ => None,
+        }
+    };
+    let opaque_black = Rgba { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+    let Some(backdrop) = color(BACKDROP_KEY).map(|backdrop| blend(backdrop, opaque_black)) else {
+        return Vec::new();
+    };
+
+    CONTRAST_PAIRS.iter()
+        .filter_map(|&(foreground, background)| {
+            let background_color = blend(color(background)?, backdrop);
+            let foreground_color = blend(color(foreground)?, background_color);
+            Some(ContrastCheck {
+                foreground,
+                background,
+                ratio: contrast_ratio(foreground_color, background_color),
+            })
+        })
+        .collect()
+}
+
+/// The WCAG contrast ratio of two opaque colors, in either order
+pub fn contrast_ratio(a: Rgba, b: Rgba) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// WCAG relative luminance of an opaque sRGB color
+pub fn relative_luminance(color: Rgba) -> f32 {
+    let linear = |channel: f32| {
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+}
+
+/// Draw `color` over an opaque `backdrop`
+fn blend(color: Rgba, backdrop: Rgba) -> Rgba {
+    let mix = |top: f32, bottom: f32| top * color.a + bottom * (1.0 - color.a);
+    Rgba {
+        r: mix(color.r, backdrop.r),
+        g: mix(color.g, backdrop.g),
+        b: mix(color.b, backdrop.b),
+        a: 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(hex: &str) -> Rgba {
+        parse_color(hex).unwrap()
+    }
+
+    fn ratio(checks: &[ContrastCheck], foreground: &str) -> f32 {
+        checks.iter().find(|check| check.foreground == foreground).unwrap().ratio
+    }
+
+    #[test]
+    fn relative_luminance_spans_black_to_white() {
+        assert_eq!(relative_luminance(rgb("#000000")), 0.0);
+        assert!((relative_luminance(rgb("#ffffff")) - 1.0).abs() < 1e-6);
+        // Green weighs most, blue least
+        let (red, green, blue) = (rgb("#ff0000"), rgb("#00ff00"), rgb("#0000ff"));
+        assert!((relative_luminance(red) - 0.2126).abs() < 1e-6);
+        assert!(relative_luminance(green) > relative_luminance(red));
+        assert!(relative_luminance(red) > relative_luminance(blue));
+        // Channels are linearized before weighing
+        assert!((relative_luminance(rgb("#808080")) - 0.2159).abs() < 1e-3);
+    }
+
+    #[test]
+    fn contrast_ratio_matches_wcag() {
+        let (black, white) = (rgb("#000000"), rgb("#ffffff"));
+        assert!((contrast_ratio(black, white) - 21.0).abs() < 1e-4);
+        assert_eq!(contrast_ratio(black, white), contrast_ratio(white, black));
+        assert_eq!(contrast_ratio(white, white), 1.0);
+        // #767676 is the lightest gray passing AA on white
+        assert!(contrast_ratio(rgb("#767676"), white) >= AA_CONTRAST_RATIO);
+        assert!(contrast_ratio(rgb("#777777"), white) < AA_CONTRAST_RATIO);
+    }
+
+    #[test]
+    fn checks_blend_translucent_colors_first() {
+        let mut theme = Theme::dark();
+        theme.background.editor = "#000000".to_string();
+        theme.foreground.editor = "#ffffff".to_string();
+        theme.semantic.error = "#ffffff80".to_string();
+        theme.semantic.warning = "not a color".to_string();
+        let checks = check_contrast(&theme);
+
+        assert!((ratio(&checks, "foreground.editor") - 21.0).abs() < 1e-4);
+        assert!(checks.iter().find(|check| check.foreground == "foreground.editor").unwrap().passes_aa());
+        // Half-transparent white over black is mid gray
+        let error = ratio(&checks, "semantic.error");
+        assert!(error > 1.0 && error < 21.0 / 2.0);
+        assert!(checks.iter().all(|check| check.foreground != "semantic.warning"));
+    }
+
+    #[test]
+    fn default_themes_meet_aa_for_editor_text() {
+        for theme in [Theme::dark(), Theme::light(), Theme::high_contrast_dark(), Theme::high_contrast_light()] {
+            let checks = check_contrast(&theme);
+            assert_eq!(checks.len(), CONTRAST_PAIRS.len(), "{}", theme.name);
+            assert!(ratio(&checks, "foreground.editor") >= AA_CONTRAST_RATIO, "{}", theme.name);
+        }
+    }
+
+    #[test]
+    fn high_contrast_themes_meet_aa_everywhere() {
+        for theme in [Theme::high_contrast_dark(), Theme::high_contrast_light()] {
+            for check in check_contrast(&theme) {
+                assert!(check.passes_aa(), "{}: {:?}", theme.name, check);
+            }
+        }
+    }
+}
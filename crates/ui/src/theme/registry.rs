@@ -0,0 +1,408 @@
+use super::{ ColorError, Theme };
+use editor_core::Config;
+use serde_json::{ Map, Value };
+use std::collections::BTreeMap;
+use std::path::{ Path, PathBuf };
+use thiserror::Error;
+
+/// Key of a theme file naming the theme it is based on
+const EXTENDS_KEY: &str = "extends";
+
+/// Base of theme files without an `extends` key
+const DEFAULT_BASE: &str = "dark";
+
+/// Parsed theme files by theme name, with the path they were read from
+type ThemeFiles = BTreeMap<String, (PathBuf, Value)>;
+
+#[derive(Debug, Error)]
+pub enum ThemeError {
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{}: {message}", path.display())]
+    Parse {
+        path: PathBuf,
+        message: String,
+    },
+
+    #[error("{}: unknown base theme '{base}'", path.display())]
+    UnknownBase {
+        path: PathBuf,
+        base: String,
+    },
+
+    #[error("{}: {key}: {source}", path.display())]
+    InvalidColor {
+        path: PathBuf,
+        key: String,
+        #[source]
+        source: ColorError,
+    },
+
+    #[error("{}: theme extends itself through '{base}'", path.display())]
+    Cycle {
+        path: PathBuf,
+        base: String,
+    },
+}
+
+/// Where a registered theme comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThemeSource {
+    BuiltIn,
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+struct ThemeEntry {
+    theme: Theme,
+    source: ThemeSource,
+}
+
+/// Available themes, by the name `appearance.theme` selects them with
+///
+/// Names are matched without regard to case. A theme file is named after its
+/// file stem and may set `extends` to inherit every color it leaves out from
+/// another theme; files without it inherit from the default dark theme.
+#[derive(Debug, Clone)]
+pub struct ThemeRegistry {
+    themes: BTreeMap<String, ThemeEntry>,
+}
+
+impl ThemeRegistry {
+    /// Only the themes built into the editor
+    pub fn builtin() -> Self {
+        let mut registry = Self { themes: BTreeMap::new() };
+        registry.insert("dark", Theme::dark(), ThemeSource::BuiltIn);
+        registry.insert("light", Theme::light(), ThemeSource::BuiltIn);
+        registry.insert("high_contrast_dark", Theme::high_contrast_dark(), ThemeSource::BuiltIn);
+        registry.insert("high_contrast_light", Theme::high_contrast_light(), ThemeSource::BuiltIn);
+        registry
+    }
+
+    /// Built-in themes and those in [`Config::themes_dir`], with the files that failed to load
+    pub fn load() -> (Self, Vec<ThemeError>) {
+        let mut registry = Self::builtin();
+        let errors = registry.load_files(&Config::theme_files());
+        (registry, errors)
+    }
+
+    /// Register theme files, replacing themes of the same name
+    ///
+    /// `extends` may name another of the files or an already registered theme.
+    pub fn load_files(&mut self, paths: &[PathBuf]) -> Vec<ThemeError> {
+        let mut errors = Vec::new();
+        let mut files = ThemeFiles::new();
+        for path in paths {
+            let Some(name) = theme_name(path) else {
+                continue;
+            };
+            match read_theme_file(path) {
+                Ok(value) => {
+                    files.insert(name, (path.clone(), value));
+                }
+                Err(error) => errors.push(error),
+            }
+        }
+        errors.extend(self.register(&files));
+        errors
+    }
+
+    /// Register parsed theme files, by theme name
+    ///
+    /// Every problem is reported once: files extending a theme that failed to
+    /// resolve are left out without an error of their own.
+    fn register(&mut self, files: &ThemeFiles) -> Vec<ThemeError> {
+        let mut errors = Vec::new();
+        let mut resolved = BTreeMap::new();
+        for name in files.keys() {
+            self.resolve(name, files, &mut resolved, &mut Vec::new(), &mut errors);
+        }
+        for (name, theme) in resolved {
+            if let Some(theme) = theme {
+                let source = ThemeSource::File(files[&name].0.clone());
+                self.insert(&name, theme, source);
+            }
+        }
+        errors
+    }
+
+    pub fn insert(&mut self, name: &str, theme: Theme, source: ThemeSource) {
+        self.themes.insert(name.to_lowercase(), ThemeEntry { theme, source });
+    }
+
+    /// Names of the available themes, sorted
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.keys().map(String::as_str)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Theme> {
+        self.themes.get(&name.to_lowercase()).map(|entry| &entry.theme)
+    }
+
+    pub fn source(&self, name: &str) -> Option<&ThemeSource> {
+        self.themes.get(&name.to_lowercase()).map(|entry| &entry.source)
+    }
+
+    /// Resolve a file's theme, and the files it extends, into `resolved`,
+    /// where files that failed are `None`
+    fn resolve(
+        &self,
+        name: &str,
+        files: &ThemeFiles,
+        resolved: &mut BTreeMap<String, Option<Theme>>,
+        chain: &mut Vec<String>,
+        errors: &mut Vec<ThemeError>
+    ) -> Option<Theme> {
+        if let Some(theme) = resolved.get(name) {
+            return theme.clone();
+        }
+        let theme = self
+            .resolve_file(name, files, resolved, chain, errors)
+            .unwrap_or_else(|error| {
+                errors.push(error);
+                None
+            });
+        resolved.insert(name.to_string(), theme.clone());
+        theme
+    }
+
+    /// The theme of one file; `None` if the theme it extends failed
+    ///
+    /// Invalid colors are pushed to `warnings` and replaced by the base theme's.
+    fn resolve_file(
+        &self,
+        name: &str,
+        files: &ThemeFiles,
+        resolved: &mut BTreeMap<String, Option<Theme>>,
+        chain: &mut Vec<String>,
+        warnings: &mut Vec<ThemeError>
+    ) -> Result<Option<Theme>, ThemeError> {
+        let (path, value) = &files[name];
+
+        let base = match value.get(EXTENDS_KEY) {
+            None => DEFAULT_BASE.to_string(),
+            Some(Value::String(base)) => base.to_lowercase(),
+            Some(_) => {
+                return Err(ThemeError::Parse {
+                    path: path.clone(),
+                    message: format!("'{}' must be the name of a theme", EXTENDS_KEY),
+                });
+            }
+        };
+        // A file named like a built-in theme extends the built-in one
+        let base_theme = if base != name && files.contains_key(&base) {
+            if chain.contains(&base) {
+                return Err(ThemeError::Cycle { path: path.clone(), base });
+            }
+            chain.push(name.to_string());
+            let base_theme = self.resolve(&base, files, resolved, chain, warnings);
+            chain.pop();
+            match base_theme {
+                Some(theme) => theme,
+                None => return Ok(None),
+            }
+        } else {
+            match self.get(&base) {
+                Some(theme) => theme.clone(),
+                None => {
+                    return Err(ThemeError::UnknownBase { path: path.clone(), base });
+                }
+            }
+        };
+
+        let mut overrides = value.clone();
+        if let Value::Object(overrides) = &mut overrides {
+            overrides.remove(EXTENDS_KEY);
+            // Without a name of its own, the theme would take its base's
+            overrides.entry("name").or_insert_with(|| Value::String(file_stem(path)));
+        }
+        let base_value = serde_json::to_value(&base_theme).expect("themes serialize");
+        let mut merged = base_value.clone();
+        merge(&mut merged, overrides);
+        let parse = |value: Value| {
+            serde_json::from_value::<Theme>(value).map_err(|err| ThemeError::Parse {
+                path: path.clone(),
+                message: err.to_string(),
+            })
+        };
+        let mut theme = parse(merged.clone())?;
+
+        let color_errors = theme.color_errors();
+        if !color_errors.is_empty() {
+            for (key, source) in color_errors {
+                let pointer = format!("/{}", key.replace('.', "/"));
+                if let (Some(color), Some(fallback)) = (merged.pointer_mut(&pointer), base_value.pointer(&pointer)) {
+                    *color = fallback.clone();
+                }
+                warnings.push(ThemeError::InvalidColor { path: path.clone(), key, source });
+            }
+            theme = parse(merged)?;
+        }
+
+        Ok(Some(theme))
+    }
+}
+
+impl Default for ThemeRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+/// Read a TOML or JSON theme file into a JSON value
+fn read_theme_file(path: &Path) -> Result<Value, ThemeError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ThemeError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let parse_error = |message: String| ThemeError::Parse { path: path.to_path_buf(), message };
+
+    let value = if path.extension().and_then(|s| s.to_str()) == Some("toml") {
+        let table: toml::Table = toml::from_str(&contents).map_err(|err| parse_error(err.to_string()))?;
+        serde_json::to_value(table).map_err(|err| parse_error(err.to_string()))?
+    } else {
+        serde_json::from_str(&contents).map_err(|err| parse_error(err.to_string()))?
+    };
+    match value {
+        Value::Object(_) => Ok(value),
+        _ => Err(parse_error("expected a table of colors".to_string())),
+    }
+}
+
+/// Lay `overrides` over `base`, merging nested tables key by key
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => merge_objects(base, overrides),
+        (base, overrides) => {
+            *base = overrides;
+        }
+    }
+}
+
+fn merge_objects(base: &mut Map<String, Value>, overrides: Map<String, Value>) {
+    for (key, value) in overrides {
+        match base.get_mut(&key) {
+            Some(existing) => merge(existing, value),
+            None => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn theme_name(path: &Path) -> Option<String> {
+    path.file_stem()?.to_str().map(str::to_lowercase)
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn register(files: &[(&str, Value)]) -> (ThemeRegistry, Vec<ThemeError>) {
+        let files: ThemeFiles = files
+            .iter()
+            .map(|(name, value)| {
+                (name.to_string(), (PathBuf::from(format!("themes/{}.json", name)), value.clone()))
+            })
+            .collect();
+        let mut registry = ThemeRegistry::builtin();
+        let errors = registry.register(&files);
+        (registry, errors)
+    }
+
+    #[test]
+    fn files_inherit_what_they_leave_out() {
+        let (registry, errors) = register(&[
+            ("ocean", json!({ "extends": "Light", "background": { "editor": "#102030" } })),
+            ("deep", json!({ "extends": "ocean", "foreground": { "editor": "#eeeeee" } })),
+            ("plain", json!({ "background": { "panel": "#000000" } })),
+        ]);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let light = Theme::light();
+        let ocean = registry.get("OCEAN").unwrap();
+        assert_eq!(ocean.name, "ocean");
+        assert_eq!(ocean.background.editor, "#102030");
+        assert_eq!(ocean.foreground.editor, light.foreground.editor);
+        let deep = registry.get("deep").unwrap();
+        assert_eq!(deep.background.editor, "#102030");
+        assert_eq!(deep.foreground.editor, "#eeeeee");
+        assert_eq!(deep.background.sidebar, light.background.sidebar);
+        // Without `extends`, the default dark theme is the base
+        assert_eq!(registry.get("plain").unwrap().background.editor, Theme::dark().background.editor);
+        assert_eq!(registry.source("deep"), Some(&ThemeSource::File(PathBuf::from("themes/deep.json"))));
+    }
+
+    #[test]
+    fn files_named_like_built_in_themes_extend_them() {
+        let (registry, errors) = register(&[
+            ("light", json!({ "extends": "light", "background": { "editor": "#fafafa" } })),
+        ]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let light = registry.get("light").unwrap();
+        assert_eq!(light.background.editor, "#fafafa");
+        assert_eq!(light.foreground.editor, Theme::light().foreground.editor);
+    }
+
+    #[test]
+    fn unknown_bases_are_reported_once() {
+        let (registry, errors) = register(&[
+            ("broken", json!({ "extends": "missing" })),
+            ("child", json!({ "extends": "broken" })),
+        ]);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(&errors[0], ThemeError::UnknownBase { base, .. } if base == "missing"));
+        assert!(registry.get("broken").is_none());
+        assert!(registry.get("child").is_none());
+    }
+
+    #[test]
+    fn cycles_are_reported_once() {
+        let (registry, errors) = register(&[
+            ("first", json!({ "extends": "second" })),
+            ("second", json!({ "extends": "third" })),
+            ("third", json!({ "extends": "first" })),
+            ("outside", json!({ "extends": "second" })),
+        ]);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0], ThemeError::Cycle { .. }));
+        for name in ["first", "second", "third", "outside"] {
+            assert!(registry.get(name).is_none(), "{}", name);
+        }
+        assert!(registry.get("dark").is_some());
+    }
+
+    #[test]
+    fn invalid_colors_fall_back_to_the_base() {
+        let (registry, errors) = register(&[
+            (
+                "typo",
+                json!({ "extends": "light", "foreground": { "editor": "#12345z", "sidebar": "#123456" } }),
+            ),
+        ]);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(&errors[0], ThemeError::InvalidColor { key, .. } if key == "foreground.editor"));
+        let typo = registry.get("typo").unwrap();
+        assert_eq!(typo.foreground.editor, Theme::light().foreground.editor);
+        assert_eq!(typo.foreground.sidebar, "#123456");
+    }
+
+    #[test]
+    fn extends_must_name_a_theme() {
+        let (registry, errors) = register(&[("numbered", json!({ "extends": 3 }))]);
+        assert!(matches!(errors[..], [ThemeError::Parse { .. }]));
+        assert!(registry.get("numbered").is_none());
+    }
+}
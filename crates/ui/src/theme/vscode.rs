@@ -0,0 +1,550 @@
+use super::{ parse_color, ColorError, Theme };
+use anyhow::{ bail, Context, Result };
+use serde::Deserialize;
+use serde_json::{ Map, Value };
+use std::collections::{ BTreeMap, BTreeSet };
+use std::path::Path;
+
+/// Nesting limit of `include`, guarding against files including each other
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Theme colors and the VS Code `colors` keys they are taken from, first match wins
+const COLOR_MAPPINGS: [(&str, &[&str]); 29] = [
+    ("background.editor", &["editor.background"]),
+    ("background.sidebar", &["sideBar.background"]),
+    ("background.preview", &["editor.background"]),
+    ("background.titlebar", &["titleBar.activeBackground"]),
+    ("background.panel", &["panel.background", "sideBar.background"]),
+    ("background.gutter", &["editorGutter.background", "editor.background"]),
+    ("foreground.editor", &["editor.foreground", "foreground"]),
+    ("foreground.sidebar", &["sideBar.foreground", "foreground"]),
+    ("foreground.preview", &["editor.foreground", "foreground"]),
+    ("foreground.titlebar", &["titleBar.activeForeground", "foreground"]),
+    ("foreground.panel", &["panelTitle.activeForeground", "foreground"]),
+    ("foreground.gutter", &["editorLineNumber.foreground"]),
+    ("semantic.error", &["editorError.foreground", "errorForeground"]),
+    ("semantic.warning", &["editorWarning.foreground"]),
+    ("semantic.info", &["editorInfo.foreground"]),
+    ("semantic.success", &["gitDecoration.addedResourceForeground", "terminal.ansiGreen"]),
+    ("semantic.hint", &["editorHint.foreground", "descriptionForeground"]),
+    ("ui.selection_background", &["editor.selectionBackground"]),
+    ("ui.selection_foreground", &["editor.selectionForeground"]),
+    ("ui.cursor", &["editorCursor.foreground"]),
+    ("ui.line_highlight", &["editor.lineHighlightBackground"]),
+    ("ui.matching_bracket", &["editorBracketMatch.background"]),
+    ("ui.button_background", &["button.background"]),
+    ("ui.button_hover", &["button.hoverBackground"]),
+    ("ui.button_active", &["button.background"]),
+    ("ui.input_background", &["input.background"]),
+    ("ui.input_border", &["input.border", "focusBorder"]),
+    ("ui.border", &["panel.border", "editorGroup.border", "contrastBorder"]),
+    ("ui.divider", &["editorGroup.border", "panel.border", "contrastBorder"]),
+];
+
+/// Syntax colors and the TextMate scopes they are taken from
+const SCOPE_MAPPINGS: [(&str, &[&str]); 14] = [
+    ("syntax.keyword", &["keyword.control", "keyword", "storage"]),
+    ("syntax.function", &["entity.name.function", "support.function"]),
+    ("syntax.variable", &["variable.other", "variable"]),
+    ("syntax.string", &["string.quoted", "string"]),
+    ("syntax.number", &["constant.numeric"]),
+    ("syntax.comment", &["comment.line", "comment"]),
+    ("syntax.type_name", &["entity.name.type", "support.type", "storage.type"]),
+    ("syntax.operator", &["keyword.operator"]),
+    ("syntax.punctuation", &["punctuation.separator", "punctuation"]),
+    ("syntax.heading", &["markup.heading", "entity.name.section"]),
+    ("syntax.emphasis", &["markup.italic"]),
+    ("syntax.strong", &["markup.bold"]),
+    ("syntax.link", &["markup.underline.link", "string.other.link"]),
+    ("syntax.code", &["markup.inline.raw", "markup.raw"]),
+];
+
+/// A theme converted from VS Code, with what could not be carried over
+#[derive(Debug, Clone)]
+pub struct VsCodeImport {
+    pub theme: Theme,
+    pub report: ImportReport,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Valid keys of `colors` no theme color is taken from
+    pub unmapped_colors: Vec<String>,
+    /// `tokenColors` scopes no syntax color is taken from
+    pub unmapped_scopes: Vec<String>,
+    /// Theme colors the VS Code theme does not set, kept from the base theme
+    pub defaults: Vec<String>,
+    /// Colors of the VS Code theme that failed to parse
+    pub invalid: Vec<(String, ColorError)>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VsCodeTheme {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    include: Option<String>,
+    #[serde(default)]
+    colors: BTreeMap<String, Value>,
+    #[serde(default)]
+    token_colors: Vec<TokenColor>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TokenColor {
+    #[serde(default)]
+    scope: Option<Scopes>,
+    #[serde(default)]
+    settings: TokenSettings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Scopes {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Scopes {
+    fn selectors(&self) -> Vec<&str> {
+        match self {
+            Scopes::One(scopes) => scopes.split(',').map(str::trim).collect(),
+            Scopes::Many(scopes) => scopes.iter().map(|scope| scope.trim()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct TokenSettings {
+    #[serde(default)]
+    foreground: Option<String>,
+    #[serde(default)]
+    background: Option<String>,
+}
+
+/// Import a VS Code color theme file, following its `include`
+///
+/// The theme is named after the file unless it sets a `name`.
+pub fn import_vscode_theme_file(path: &Path) -> Result<VsCodeImport> {
+    let vscode = read_theme(path, 0)?;
+    let fallback_name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Imported".to_string());
+    Ok(convert(vscode, &fallback_name))
+}
+
+/// Import the contents of a VS Code color theme; `include` is not supported here
+pub fn import_vscode_theme(json: &str) -> Result<VsCodeImport> {
+    let vscode: VsCodeTheme = serde_json::from_str(&strip_jsonc(json))?;
+    if vscode.include.is_some() {
+        bail!("themes with 'include' must be imported from a file");
+    }
+    Ok(convert(vscode, "Imported"))
+}
+
+fn read_theme(path: &Path, depth: usize) -> Result<VsCodeTheme> {
+    if depth > MAX_INCLUDE_DEPTH {
+        bail!("{}: too many nested includes", path.display());
+    }
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut theme: VsCodeTheme = serde_json::from_str(&strip_jsonc(&contents))
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    // The including theme's settings win over the included one's
+    if let Some(include) = theme.include.take() {
+        let included_path = path.parent().unwrap_or(Path::new(".")).join(include);
+        let included = read_theme(&included_path, depth + 1)?;
+        for (key, color) in included.colors {
+            theme.colors.entry(key).or_insert(color);
+        }
+        let mut token_colors = included.token_colors;
+        token_colors.append(&mut theme.token_colors);
+        theme.token_colors = token_colors;
+        theme.kind = theme.kind.or(included.kind);
+    }
+    Ok(theme)
+}
+
+fn convert(vscode: VsCodeTheme, fallback_name: &str) -> VsCodeImport {
+    let is_light = vscode.kind
+        .as_deref()
+        .is_some_and(|kind| matches!(kind, "light" | "hc-light" | "vs"));
+    let base = if is_light { Theme::light() } else { Theme::dark() };
+    let mut report = ImportReport::default();
+
+    // Only colors that parse are used
+    let mut colors = BTreeMap::new();
+    for (key, value) in &vscode.colors {
+        let Value::String(color) = value else {
+            continue;
+        };
+        match parse_color(color) {
+            Ok(_) => {
+                colors.insert(key.as_str(), color.clone());
+            }
+            Err(error) => report.invalid.push((key.clone(), error)),
+        }
+    }
+
+    let mut values: BTreeMap<&str, String> = BTreeMap::new();
+    let mut used_colors = BTreeSet::new();
+    for (target, sources) in COLOR_MAPPINGS {
+        if let Some(source) = sources.iter().find(|source| colors.contains_key(**source)) {
+            values.insert(target, colors[source].clone());
+            used_colors.insert(*source);
+        }
+    }
+
+    // Rules without a scope set the defaults of all text
+    let global = vscode.token_colors.iter().find(|rule| rule.scope.is_none());
+    if let Some(foreground) = global.and_then(|rule| rule.settings.foreground.clone()) {
+        if parse_color(&foreground).is_ok() {
+            values.entry("foreground.editor").or_insert(foreground);
+        }
+    }
+    if let Some(background) = global.and_then(|rule| rule.settings.background.clone()) {
+        if parse_color(&background).is_ok() {
+            values.entry("background.editor").or_insert(background);
+        }
+    }
+
+    let rules = scope_rules(&vscode.token_colors, &mut report);
+    let mut used_scopes = BTreeSet::new();
+    for (target, scopes) in SCOPE_MAPPINGS {
+        if let Some((selector, color)) = best_rule(&rules, scopes) {
+            values.insert(target, color.to_string());
+            used_scopes.insert(selector);
+        }
+    }
+    // Plain operators and punctuation follow the editor's text by default
+    if let Some(foreground) = values.get("foreground.editor").cloned() {
+        values.entry("syntax.operator").or_insert(foreground.clone());
+        values.entry("syntax.punctuation").or_insert(foreground);
+    }
+    if let Some(string) = values.get("syntax.string").cloned() {
+        values.entry("syntax.code").or_insert(string);
+    }
+
+    report.unmapped_colors = colors
+        .keys()
+        .filter(|key| !used_colors.contains(*key))
+        .map(|key| key.to_string())
+        .collect();
+    let unmapped_scopes: BTreeSet<&str> = rules
+        .iter()
+        .map(|(selector, _)| *selector)
+        .filter(|selector| !used_scopes.contains(selector))
+        .collect();
+    report.unmapped_scopes = unmapped_scopes.into_iter().map(str::to_string).collect();
+
+    let mut theme_value = serde_json::to_value(&base).expect("themes serialize");
+    for (key, group) in theme_groups(&mut theme_value) {
+        for (name, color) in group.iter_mut() {
+            let dotted = format!("{}.{}", key, name);
+            match values.get(dotted.as_str()) {
+                Some(value) => {
+                    *color = Value::String(value.clone());
+                }
+                None => report.defaults.push(dotted),
+            }
+        }
+    }
+    if let Value::Object(fields) = &mut theme_value {
+        let name = vscode.name.unwrap_or_else(|| fallback_name.to_string());
+        fields.insert("name".to_string(), Value::String(name));
+    }
+    let theme = serde_json::from_value(theme_value).expect("only string colors are replaced");
+
+    VsCodeImport { theme, report }
+}
+
+/// Each color group of a serialized theme, such as `background` or `syntax`
+fn theme_groups(theme: &mut Value) -> Vec<(String, &mut Map<String, Value>)> {
+    let Value::Object(fields) = theme else {
+        return Vec::new();
+    };
+    fields
+        .iter_mut()
+        .filter_map(|(key, value)| {
+            match value {
+                Value::Object(group) => Some((key.clone(), group)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Foreground colors of the token rules, by scope selector, in file order
+fn scope_rules<'a>(token_colors: &'a [TokenColor], report: &mut ImportReport) -> Vec<(&'a str, &'a str)> {
+    let mut rules = Vec::new();
+    for rule in token_colors {
+        let Some(foreground) = rule.settings.foreground.as_deref() else {
+            continue;
+        };
+        let Some(scope) = &rule.scope else {
+            continue;
+        };
+        if let Err(error) = parse_color(foreground) {
+            report.invalid.push((scope.selectors().join(", "), error));
+            continue;
+        }
+        rules.extend(
+            scope
+                .selectors()
+                .into_iter()
+                .filter(|selector| !selector.is_empty())
+                .map(|selector| (selector, foreground))
+        );
+    }
+    rules
+}
+
+/// The rule coloring the first of `scopes` any rule applies to
+///
+/// As in TextMate, a selector applies to its scope and every scope below it,
+/// the longest matching selector wins, and later rules win ties. Selectors
+/// with descendant parts such as `source.js keyword` never match.
+fn best_rule<'a>(rules: &[(&'a str, &'a str)], scopes: &[&str]) -> Option<(&'a str, &'a str)> {
+    scopes.iter().find_map(|scope| {
+        rules
+            .iter()
+            .enumerate()
+            .filter(|(_, (selector, _))| selector_matches(selector, scope))
+            .max_by_key(|(index, (selector, _))| (selector.len(), *index))
+            .map(|(_, rule)| *rule)
+    })
+}
+
+fn selector_matches(selector: &str, scope: &str) -> bool {
+    scope == selector || scope.strip_prefix(selector).is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// Remove the comments and trailing commas VS Code allows in theme files
+fn strip_jsonc(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+    // Written once the next token shows it is not trailing
+    let mut pending_comma = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            output.push(c);
+            match c {
+                '\\' => output.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+            }
+            (',', _) => pending_comma = true,
+            (c, _) if c.is_whitespace() => output.push(c),
+            (c, _) => {
+                if pending_comma && c != '}' && c != ']' {
+                    output.push(',');
+                }
+                pending_comma = false;
+                in_string = c == '"';
+                output.push(c);
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn import(value: Value) -> VsCodeImport {
+        import_vscode_theme(&value.to_string()).unwrap()
+    }
+
+    fn color<'a>(theme: &'a Value, key: &str) -> Option<&'a str> {
+        theme.pointer(&format!("/{}", key.replace('.', "/")))?.as_str()
+    }
+
+    #[test]
+    fn mappings_target_theme_colors() {
+        let theme = serde_json::to_value(Theme::dark()).unwrap();
+        for (target, _) in COLOR_MAPPINGS.iter().chain(&SCOPE_MAPPINGS) {
+            assert!(color(&theme, target).is_some(), "{} is not a theme color", target);
+        }
+    }
+
+    #[test]
+    fn colors_come_from_the_first_key_present() {
+        let imported = import(json!({
+            "colors": {
+                "editor.background": "#101010",
+                "sideBar.background": "#202020",
+                "foreground": "#d0d0d0",
+                "editor.foreground": "#e0e0e0",
+            },
+        }));
+        let theme = serde_json::to_value(&imported.theme).unwrap();
+        assert_eq!(color(&theme, "background.panel"), Some("#202020"));
+        assert_eq!(color(&theme, "background.gutter"), Some("#101010"));
+        assert_eq!(color(&theme, "foreground.editor"), Some("#e0e0e0"));
+        assert_eq!(color(&theme, "foreground.sidebar"), Some("#d0d0d0"));
+        // Operators and punctuation follow the text without rules of their own
+        assert_eq!(color(&theme, "syntax.operator"), Some("#e0e0e0"));
+    }
+
+    #[test]
+    fn longest_matching_selector_wins() {
+        let rules = [
+            ("keyword", "#111111"),
+            ("keyword.control", "#222222"),
+            ("keywordish", "#333333"),
+            ("source.js keyword", "#444444"),
+            ("string", "#555555"),
+            ("string", "#666666"),
+        ];
+        assert_eq!(best_rule(&rules, &["keyword.control.flow"]), Some(("keyword.control", "#222222")));
+        assert_eq!(best_rule(&rules, &["keyword.other"]), Some(("keyword", "#111111")));
+        assert_eq!(best_rule(&rules, &["keywordish.other"]), Some(("keywordish", "#333333")));
+        // Later rules win ties
+        assert_eq!(best_rule(&rules, &["string.quoted"]), Some(("string", "#666666")));
+        // Scopes are tried in order until one is colored
+        assert_eq!(best_rule(&rules, &["comment", "keyword"]), Some(("keyword", "#111111")));
+        assert_eq!(best_rule(&rules, &["comment"]), None);
+    }
+
+    #[test]
+    fn token_colors_set_syntax_colors() {
+        let imported = import(json!({
+            "tokenColors": [
+                { "settings": { "foreground": "#abcdef" } },
+                { "scope": "comment", "settings": { "foreground": "#008000" } },
+                { "scope": ["string", "meta.tag"], "settings": { "foreground": "#a31515" } },
+                { "scope": "keyword, storage", "settings": { "foreground": "#0000ff" } },
+            ],
+        }));
+        let theme = serde_json::to_value(&imported.theme).unwrap();
+        assert_eq!(color(&theme, "foreground.editor"), Some("#abcdef"));
+        assert_eq!(color(&theme, "syntax.comment"), Some("#008000"));
+        assert_eq!(color(&theme, "syntax.string"), Some("#a31515"));
+        assert_eq!(color(&theme, "syntax.code"), Some("#a31515"));
+        assert_eq!(color(&theme, "syntax.keyword"), Some("#0000ff"));
+        assert_eq!(imported.report.unmapped_scopes, vec!["meta.tag".to_string()]);
+    }
+
+    #[test]
+    fn reports_what_is_not_carried_over() {
+        let imported = import(json!({
+            "type": "light",
+            "colors": {
+                "editor.background": "#fffffe",
+                "activityBar.background": "#eeeeee",
+                "editor.foreground": "not a color",
+                "editor.selectionForeground": 3,
+            },
+        }));
+        let report = &imported.report;
+        assert_eq!(imported.theme.name, "Imported");
+        assert_eq!(report.unmapped_colors, vec!["activityBar.background".to_string()]);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.invalid[0].0, "editor.foreground");
+        // Everything the theme leaves unset keeps the light base's color
+        assert!(report.defaults.contains(&"foreground.editor".to_string()));
+        assert!(!report.defaults.contains(&"background.editor".to_string()));
+        assert_eq!(imported.theme.foreground.editor, Theme::light().foreground.editor);
+    }
+
+    #[test]
+    fn empty_themes_keep_every_default() {
+        let imported = import(json!({}));
+        let report = &imported.report;
+        assert!(report.unmapped_colors.is_empty());
+        assert!(report.unmapped_scopes.is_empty());
+        assert!(report.invalid.is_empty());
+        let base = serde_json::to_value(Theme::dark()).unwrap();
+        for key in &report.defaults {
+            assert!(color(&base, key).is_some(), "{}", key);
+        }
+        assert_eq!(report.defaults.len(), count_colors(&base));
+    }
+
+    fn count_colors(theme: &Value) -> usize {
+        theme
+            .as_object()
+            .unwrap()
+            .values()
+            .filter_map(Value::as_object)
+            .map(|group| group.values().filter(|value| value.is_string()).count())
+            .sum()
+    }
+
+    #[test]
+    fn strips_comments_and_trailing_commas() {
+        let jsonc = r##"{
+            // The name
+            "name": "Quoted // not a comment, /* nor this */",
+            /* Colors,
+               spanning lines */
+            "colors": { "a": "#000000", "b": "\"}", },
+            "list": [1, 2, 3,],
+        }"##;
+        let value: Value = serde_json::from_str(&strip_jsonc(jsonc)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "name": "Quoted // not a comment, /* nor this */",
+                "colors": { "a": "#000000", "b": "\"}" },
+                "list": [1, 2, 3],
+            })
+        );
+    }
+
+    #[test]
+    fn includes_merge_under_the_including_theme() {
+        let directory = std::env::temp_dir().join(format!("typst-studio-vscode-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let base = json!({
+            "type": "light",
+            "colors": { "editor.background": "#ffffff", "editor.foreground": "#000000" },
+            "tokenColors": [{ "scope": "comment", "settings": { "foreground": "#008000" } }],
+        });
+        std::fs::write(directory.join("base.json"), base.to_string()).unwrap();
+        let child = r##"{
+            "include": "./base.json",
+            "colors": { "editor.background": "#fafafa" }, // overrides the base
+            "tokenColors": [{ "scope": "comment", "settings": { "foreground": "#00aa00" } }],
+        }"##;
+        std::fs::write(directory.join("child.json"), child).unwrap();
+
+        let imported = import_vscode_theme_file(&directory.join("child.json")).unwrap();
+        let theme = &imported.theme;
+        assert_eq!(theme.name, "child");
+        assert_eq!(theme.background.editor, "#fafafa");
+        assert_eq!(theme.foreground.editor, "#000000");
+        assert_eq!(theme.syntax.comment, "#00aa00");
+        // The included theme's type picks the light base
+        assert_eq!(theme.background.titlebar, Theme::light().background.titlebar);
+
+        assert!(import_vscode_theme(child).is_err());
+        std::fs::write(directory.join("loop.json"), r#"{ "include": "loop.json" }"#).unwrap();
+        assert!(import_vscode_theme_file(&directory.join("loop.json")).is_err());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,64 +1,705 @@
-use crate::components::StatusBar;
+use crate::command_palette::{ CommandPalette, GO_TO_LINE_PREFIX };
+use crate::commands::{
+    ExportPdf,
+    GoToLine,
+    NewFile,
+    OpenDocumentation,
+    OpenFile,
+    OpenFolder,
+    ResetZoom,
+    SaveFile,
+    SaveFileAs,
+    ToggleCommandPalette,
+    ToggleConsole,
+    TogglePreview,
+    ToggleSidebar,
+    ZoomIn,
+    ZoomOut,
+};
+use crate::components::{ StatusBar, UseCrLfLineEndings, UseLfLineEndings };
 use crate::console::ConsolePanel;
 use crate::editor::EditorPanel;
 use crate::navbar::NavBar;
-use crate::preview_pane::PreviewPane;
+use crate::preview_pane::{ PreviewPane, RevealInPreview };
 use crate::sidebar::Sidebar;
 use crate::theme::Theme;
-use editor_core::{ ApplicationState, Document };
+use editor_core::document::LineEnding;
+use editor_core::config::{ SidebarPosition, UI_SCALE_RANGE };
+use editor_core::{
+    ApplicationState,
+    AutoSaveTrigger,
+    CommandRegistry,
+    ConfigService,
+    Document,
+    EditorState,
+    ExternalChange,
+    Project,
+    WorkspaceState,
+};
 use gpui::*;
 use gpui::prelude::FluentBuilder;
-use parking_lot::RwLock;
+use parking_lot::{ Mutex, RwLock };
+use std::path::{ Path, PathBuf };
 use std::sync::Arc;
+use std::time::{ Duration, Instant };
+use typst_integration::file_watcher::{ ChangeKind, FileChange, FileWatcher };
+use typst_integration::{ ExportOptions, Exporter, TypstCompiler };
+
+/// How often idle documents are checked for `auto_save = "after_delay"` and
+/// `auto_compile_on_change`
+const AUTO_SAVE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Rem size at a `ui_scale` of 1
+const BASE_REM_SIZE: f32 = 16.0;
+/// Resolution of the pages rendered for the preview
+const PREVIEW_DPI: f32 = 144.0;
+const ZOOM_STEP: f32 = 0.1;
+const DOCUMENTATION_URL: &str = "https://typst.app/docs/";
 
 pub struct MainWindow {
     state: Arc<RwLock<ApplicationState>>,
     theme: Arc<RwLock<Theme>>,
+    config_service: Arc<ConfigService>,
     navbar: Entity<NavBar>,
+    command_palette: Entity<CommandPalette>,
     sidebar: Entity<Sidebar>,
     editor: Entity<EditorPanel>,
     preview: Entity<PreviewPane>,
     console: Entity<ConsolePanel>,
     status_bar: Entity<StatusBar>,
+    project: Option<Project>,
+    watcher: Option<FileWatcher>,
+    compiler: TypstCompiler,
+    /// The running compilation; replacing it drops outdated results
+    compile_task: Option<Task<()>>,
+    /// When the last compilation started, to recompile after newer edits
+    compiled_at: Option<Instant>,
+    /// Open documents changed on disk, waiting for the user to resolve them
+    external_changes: Vec<(ExternalChange, PathBuf)>,
+    /// Zoom of this session, applied on top of `appearance.ui_scale`
+    zoom: f32,
 }
 
 impl MainWindow {
     pub fn new(
         state: Arc<RwLock<ApplicationState>>,
         theme: Arc<RwLock<Theme>>,
+        config_service: Arc<ConfigService>,
+        window: &mut Window,
         cx: &mut Context<Self>
     ) -> Self {
-        let navbar = cx.new(|cx| NavBar::new(theme.clone(), cx));
-        let sidebar = cx.new(|cx| Sidebar::new(theme.clone(), state.clone(), cx));
+        let commands = Arc::new(CommandRegistry::with_builtins());
+        let navbar = cx.new(|cx| NavBar::new(theme.clone(), state.clone(), commands.clone(), cx));
+        let command_palette = cx.new(|cx| {
+            CommandPalette::new(theme.clone(), state.clone(), commands.clone(), cx)
+        });
         let editor = cx.new(|cx| EditorPanel::new(theme.clone(), state.clone(), cx));
-        let preview = cx.new(|cx| PreviewPane::new(theme.clone(), cx));
+        let preview = cx.new(|cx| PreviewPane::new(theme.clone(), state.clone(), cx));
+        let sidebar = cx.new(|cx| Sidebar::new(theme.clone(), state.clone(), preview.clone(), cx));
         let console = cx.new(|cx| ConsolePanel::new(theme.clone(), cx));
-        let status_bar = cx.new(|_cx| StatusBar::new(theme.clone()));
+        let status_bar = cx.new(|_cx| StatusBar::new(theme.clone(), state.clone()));
 
-        // Open a default document
+        // Open a default document unless the session restored some
         if let Some(workspace) = state.read().get_active_workspace() {
             let mut workspace = workspace.write();
-            let doc = Document::new(None);
-            workspace.open_document(doc);
+            if workspace.open_documents.is_empty() {
+                let doc = Document::new(None);
+                workspace.open_document(doc);
+            }
         }
 
-        Self {
+        cx.observe_window_activation(window, |this, window, cx| {
+            if !window.is_window_active() {
+                this.auto_save(AutoSaveTrigger::WindowBlur, cx);
+            }
+        }).detach();
+        let editor_focus = editor.focus_handle(cx);
+        cx.on_focus_out(&editor_focus, window, |this, _event, _window, cx| {
+            this.auto_save(AutoSaveTrigger::FocusChange, cx);
+        }).detach();
+        cx.spawn(async move |this, cx| {
+            loop {
+                cx.background_executor().timer(AUTO_SAVE_POLL_INTERVAL).await;
+                let result = this.update(cx, |this, cx| {
+                    this.auto_save(AutoSaveTrigger::Idle, cx);
+                    this.compile_on_edit(cx);
+                });
+                if result.is_err() {
+                    break;
+                }
+            }
+        }).detach();
+
+        let mut main_window = Self {
             state,
             theme,
+            config_service,
             navbar,
+            command_palette,
             sidebar,
             editor,
             preview,
             console,
             status_bar,
+            project: None,
+            watcher: None,
+            compiler: TypstCompiler::new(),
+            compile_task: None,
+            compiled_at: None,
+            external_changes: Vec::new(),
+            zoom: 1.0,
+        };
+        main_window.check_restored_documents();
+        // A restored workspace reopens its project to watch and compile it
+        let root = main_window.active_workspace().and_then(|workspace| workspace.read().root.clone());
+        if let Some(root) = root {
+            if let Err(err) = main_window.open_project(root.clone(), cx) {
+                tracing::warn!("Failed to reopen {}: {}", root.display(), err);
+            }
+        }
+        main_window
+    }
+
+    /// Files of restored documents may have changed while the editor was closed
+    fn check_restored_documents(&mut self) {
+        let paths: Vec<PathBuf> = self.state
+            .read()
+            .get_active_workspace()
+            .map(|workspace| {
+                workspace
+                    .read()
+                    .open_documents.values()
+                    .filter_map(|editor| editor.read().document.path.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        for path in paths {
+            self.check_external_change(&path);
+        }
+    }
+
+    /// Save edited documents if the configured auto-save mode reacts to `trigger`
+    fn auto_save(&mut self, trigger: AutoSaveTrigger, cx: &mut Context<Self>) {
+        let (mode, delay) = {
+            let state = self.state.read();
+            let config = state.config.read();
+            (config.editor.auto_save, config.editor.auto_save_delay)
+        };
+        if !mode.saves_on(trigger) {
+            return;
+        }
+        let idle = match trigger {
+            AutoSaveTrigger::Idle => Duration::from_millis(delay.into()),
+            AutoSaveTrigger::FocusChange | AutoSaveTrigger::WindowBlur => Duration::ZERO,
+        };
+        let Some(workspace) = self.state.read().get_active_workspace() else {
+            return;
+        };
+
+        let report = workspace.write().auto_save(idle);
+        for (path, err) in &report.failed {
+            tracing::warn!("Failed to auto-save {}: {}", path.display(), err);
+        }
+        // Never overwrite a file changed on disk; ask the user instead
+        for path in &report.conflicts {
+            self.check_external_change(path);
+        }
+        if !report.saved.is_empty() || !report.conflicts.is_empty() {
+            cx.notify();
+        }
+    }
+
+    /// Convert every line of the active document to one line ending
+    fn convert_line_endings(&mut self, line_ending: LineEnding, cx: &mut Context<Self>) {
+        let editor = self.state
+            .read()
+            .get_active_workspace()
+            .and_then(|workspace| workspace.read().get_active_editor());
+        if let Some(editor) = editor {
+            editor.write().document.convert_line_endings(line_ending);
+            cx.notify();
+        }
+    }
+
+    fn use_lf_line_endings(&mut self, _: &UseLfLineEndings, _window: &mut Window, cx: &mut Context<Self>) {
+        self.convert_line_endings(LineEnding::Lf, cx);
+    }
+
+    fn use_crlf_line_endings(&mut self, _: &UseCrLfLineEndings, _window: &mut Window, cx: &mut Context<Self>) {
+        self.convert_line_endings(LineEnding::CrLf, cx);
+    }
+
+    /// Open a project folder, apply its configuration, watch it for changes
+    /// and compile its main file
+    pub fn open_project(&mut self, root: PathBuf, cx: &mut Context<Self>) -> anyhow::Result<()> {
+        let project = Project::discover(root.clone())?;
+        if let Some(workspace) = self.state.read().get_active_workspace() {
+            workspace.write().root = Some(root.clone());
+        }
+        // Subscribers of the service apply what the project file changes
+        if let Err(err) = self.config_service.set_project_root(Some(root.clone())) {
+            tracing::warn!("Failed to watch the project configuration: {}", err);
+        }
+        self.compiler = TypstCompiler::with_args(&project.compiler_args).unwrap_or_else(|err| {
+            tracing::warn!("Ignoring compiler_args of {}: {}", root.display(), err);
+            TypstCompiler::new()
+        });
+
+        let (watcher, changes) = FileWatcher::new(&root)?;
+        let changes = Arc::new(Mutex::new(changes));
+        cx.spawn(async move |this, cx| {
+            loop {
+                let changes = changes.clone();
+                let batch = cx
+                    .background_executor()
+                    .spawn(async move { changes.lock().recv().ok() }).await;
+                let Some(batch) = batch else {
+                    break;
+                };
+                if this.update(cx, |this, cx| this.handle_file_changes(batch, cx)).is_err() {
+                    break;
+                }
+            }
+        }).detach();
+
+        let main = project.main_file.clone();
+        self.project = Some(project);
+        self.watcher = Some(watcher);
+        if let Some(main) = main {
+            self.compile(main, cx);
+        }
+        Ok(())
+    }
+
+    /// Compile a main file in the background and show the result in the preview
+    ///
+    /// Unsaved edits of open documents are compiled instead of their files.
+    fn compile(&mut self, main: PathBuf, cx: &mut Context<Self>) {
+        let Some(root) = self.project.as_ref().map(|project| project.root.clone()) else {
+            return;
+        };
+        let buffers = self
+            .active_workspace()
+            .map(|workspace| workspace.read().unsaved_buffers())
+            .unwrap_or_default();
+        let compiler = self.compiler.clone();
+        let task = cx.background_executor().spawn(async move {
+            let compiled = compiler.compile_sources(&root, &main, buffers)?;
+            let pages = (0..compiled.document.pages.len())
+                .map(|page| compiled.render_page(page, PREVIEW_DPI))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            anyhow::Ok((compiled, pages))
+        });
+        self.compiled_at = Some(Instant::now());
+        self.compile_task = Some(
+            cx.spawn(async move |this, cx| {
+                match task.await {
+                    Ok((compiled, pages)) => {
+                        this.update(cx, |this, cx| {
+                            this.preview.update(cx, |preview, cx| {
+                                preview.set_document(&compiled, pages, cx)
+                            });
+                        }).ok();
+                    }
+                    Err(err) => tracing::warn!("Compilation failed: {}", err),
+                }
+            })
+        );
+    }
+
+    /// Recompile the main file once edits have settled for `compilation_delay`
+    fn compile_on_edit(&mut self, cx: &mut Context<Self>) {
+        let (enabled, delay) = {
+            let state = self.state.read();
+            let config = state.config.read();
+            (config.compiler.auto_compile_on_change, config.compiler.compilation_delay)
+        };
+        if !enabled {
+            return;
+        }
+        let Some(main) = self.project.as_ref().and_then(|project| project.main_file.clone()) else {
+            return;
+        };
+        let Some(edited) = self.active_workspace().and_then(|workspace| workspace.read().last_edited()) else {
+            return;
+        };
+        let is_compiled = self.compiled_at.is_some_and(|compiled| compiled >= edited);
+        if !is_compiled && edited.elapsed() >= Duration::from_millis(delay.into()) {
+            self.compile(main, cx);
+        }
+    }
+
+    /// Update the project and open documents after files changed on disk
+    fn handle_file_changes(&mut self, changes: Vec<FileChange>, cx: &mut Context<Self>) {
+        let mut affected: Vec<PathBuf> = Vec::new();
+        for change in &changes {
+            if let Some(project) = self.project.as_mut() {
+                for main in project.file_changed(&change.path, change.kind != ChangeKind::Removed) {
+                    if !affected.contains(&main) {
+                        affected.push(main);
+                    }
+                }
+            }
+            self.check_external_change(&change.path);
+        }
+
+        if self.state.read().config.read().compiler.auto_compile_on_save {
+            for main in affected {
+                self.compile(main, cx);
+            }
+        }
+        cx.notify();
+    }
+
+    /// Reload documents without edits; queue conflicts for the user to resolve
+    fn check_external_change(&mut self, path: &Path) {
+        let Some(workspace) = self.state.read().get_active_workspace() else {
+            return;
+        };
+        let mut workspace = workspace.write();
+        match workspace.external_change(path) {
+            Some(ExternalChange::Modified(id)) => {
+                if let Err(err) = workspace.reload_document(id) {
+                    tracing::warn!("Failed to reload {}: {}", path.display(), err);
+                }
+            }
+            Some(change) => {
+                self.external_changes.retain(|(_, pending)| pending != path);
+                self.external_changes.push((change, path.to_path_buf()));
+            }
+            None => {}
+        }
+    }
+
+    /// Resolve the oldest external change, either taking the disk version or keeping ours
+    fn resolve_external_change(&mut self, reload: bool, cx: &mut Context<Self>) {
+        if self.external_changes.is_empty() {
+            return;
+        }
+        let (change, path) = self.external_changes.remove(0);
+        if let Some(workspace) = self.state.read().get_active_workspace() {
+            let mut workspace = workspace.write();
+            match (change, reload) {
+                (ExternalChange::Removed(id), true) => workspace.close_document(id),
+                (ExternalChange::Conflict(id), true) => {
+                    if let Err(err) = workspace.reload_document(id) {
+                        tracing::warn!("Failed to reload {}: {}", path.display(), err);
+                    }
+                }
+                (ExternalChange::Conflict(id) | ExternalChange::Removed(id), false) => {
+                    workspace.keep_local_changes(id);
+                }
+                (ExternalChange::Modified(_), _) => {}
+            }
+        }
+        cx.notify();
+    }
+
+    fn external_change_banner(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let (change, path) = self.external_changes.first()?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (message, accept, keep) = match change {
+            ExternalChange::Removed(_) => (format!("{} was deleted on disk.", name), "Close", "Keep Open"),
+            _ => (format!("{} changed on disk and has unsaved changes.", name), "Reload", "Keep Mine"),
+        };
+
+        let theme = self.theme.read();
+        let bg_color = theme.parse_color(&theme.background.panel);
+        let border_color = theme.parse_color(&theme.semantic.warning);
+        let button_color = theme.parse_color(&theme.ui.button_background);
+        let hover_color = theme.parse_color(&theme.ui.button_hover);
+        let button = |id: &'static str, label: &'static str| {
+            div()
+                .id(id)
+                .px_2()
+                .rounded_sm()
+                .cursor_pointer()
+                .bg(button_color)
+                .hover(move |style| style.bg(hover_color))
+                .child(label)
+        };
+
+        Some(
+            div()
+                .w_full()
+                .flex()
+                .flex_row()
+                .items_center()
+                .justify_between()
+                .px_2()
+                .py_1()
+                .text_sm()
+                .bg(bg_color)
+                .border_b_1()
+                .border_color(border_color)
+                .child(message)
+                .child(
+                    div()
+                        .flex()
+                        .flex_row()
+                        .gap_2()
+                        .child(
+                            button("external-change-accept", accept).on_click(
+                                cx.listener(|this, _event, _window, cx| this.resolve_external_change(true, cx))
+                            )
+                        )
+                        .child(
+                            button("external-change-keep", keep).on_click(
+                                cx.listener(|this, _event, _window, cx| this.resolve_external_change(false, cx))
+                            )
+                        )
+                )
+        )
+    }
+
+    fn active_workspace(&self) -> Option<Arc<RwLock<WorkspaceState>>> {
+        self.state.read().get_active_workspace()
+    }
+
+    fn active_editor(&self) -> Option<Arc<RwLock<EditorState>>> {
+        self.active_workspace().and_then(|workspace| workspace.read().get_active_editor())
+    }
+
+    fn toggle_command_palette(&mut self, _: &ToggleCommandPalette, window: &mut Window, cx: &mut Context<Self>) {
+        self.command_palette.update(cx, |palette, cx| {
+            if palette.is_open() {
+                palette.close(window, cx);
+            } else {
+                palette.open("", window, cx);
+            }
+        });
+    }
+
+    fn go_to_line(&mut self, _: &GoToLine, window: &mut Window, cx: &mut Context<Self>) {
+        self.command_palette.update(cx, |palette, cx| palette.open(GO_TO_LINE_PREFIX, window, cx));
+    }
+
+    fn new_file(&mut self, _: &NewFile, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(workspace) = self.active_workspace() {
+            workspace.write().open_document(Document::new(None));
+            cx.notify();
+        }
+    }
+
+    fn open_file(&mut self, _: &OpenFile, _window: &mut Window, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: true,
+            prompt: None,
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            this.update(cx, |this, cx| {
+                let Some(workspace) = this.active_workspace() else {
+                    return;
+                };
+                for path in paths {
+                    if let Err(err) = workspace.write().open_file_at(&path, 0, 0) {
+                        tracing::warn!("Failed to open {}: {}", path.display(), err);
+                    }
+                }
+                cx.notify();
+            }).ok();
+        }).detach();
+    }
+
+    fn open_folder(&mut self, _: &OpenFolder, _window: &mut Window, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: false,
+            directories: true,
+            multiple: false,
+            prompt: None,
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(mut paths))) = paths.await else {
+                return;
+            };
+            let Some(root) = paths.pop() else {
+                return;
+            };
+            this.update(cx, |this, cx| {
+                if let Err(err) = this.open_project(root.clone(), cx) {
+                    tracing::warn!("Failed to open {}: {}", root.display(), err);
+                }
+                cx.notify();
+            }).ok();
+        }).detach();
+    }
+
+    /// Save the active document, asking for a path if it has none yet
+    fn save_file(&mut self, _: &SaveFile, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.active_editor() else {
+            return;
+        };
+        if editor.read().document.path.is_none() {
+            self.save_file_as(&SaveFileAs, window, cx);
+            return;
+        }
+        let result = editor.write().save();
+        if let Err(err) = result {
+            let name = editor.read().document.file_name();
+            report_save_error(&name, &err, window, cx);
+        }
+        cx.notify();
+    }
+
+    fn save_file_as(&mut self, _: &SaveFileAs, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = self.active_editor() else {
+            return;
+        };
+        let directory = editor
+            .read()
+            .document.path.as_ref()
+            .and_then(|path| path.parent().map(Path::to_path_buf))
+            .or_else(|| self.project.as_ref().map(|project| project.root.clone()))
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        let path = cx.prompt_for_new_path(&directory, None);
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(Ok(Some(path))) = path.await else {
+                return;
+            };
+            let result = {
+                let mut editor = editor.write();
+                let content = editor.content.clone();
+                editor.document.save_as(path.clone(), &content)
+            };
+            this.update_in(cx, |_this, window, cx| {
+                if let Err(err) = result {
+                    report_save_error(&path.display().to_string(), &err, window, cx);
+                }
+                cx.notify();
+            }).ok();
+        }).detach();
+    }
+
+    /// Compile the project's main file and write it as a PDF
+    fn export_pdf(&mut self, _: &ExportPdf, _window: &mut Window, cx: &mut Context<Self>) {
+        let Some(project) = self.project.as_ref() else {
+            tracing::warn!("Export needs an open project");
+            return;
+        };
+        let Some(main) = project.main_file.clone() else {
+            tracing::warn!("Export needs a main file in {}", project.root.display());
+            return;
+        };
+        let root = project.root.clone();
+        let exporter = Exporter::for_project(project);
+        let options = ExportOptions {
+            filename_template: project.settings.output_filename.clone(),
+            ..ExportOptions::default()
+        };
+        let compiler = self.compiler.clone();
+        cx.background_executor()
+            .spawn(async move {
+                let result = compiler
+                    .compile_blocking(&root, &main)
+                    .and_then(|compiled| exporter.export(&compiled, &main, &options));
+                match result {
+                    Ok(paths) => {
+                        for path in paths {
+                            tracing::info!("Exported {}", path.display());
+                        }
+                    }
+                    Err(err) => tracing::warn!("Export failed: {}", err),
+                }
+            })
+            .detach();
+    }
+
+    fn toggle_panel(&mut self, cx: &mut Context<Self>, toggle: impl FnOnce(&mut WorkspaceState)) {
+        if let Some(workspace) = self.active_workspace() {
+            toggle(&mut workspace.write());
+            cx.notify();
+        }
+    }
+
+    fn toggle_sidebar(&mut self, _: &ToggleSidebar, _window: &mut Window, cx: &mut Context<Self>) {
+        self.toggle_panel(cx, |workspace| workspace.sidebar_visible = !workspace.sidebar_visible);
+    }
+
+    fn toggle_preview(&mut self, _: &TogglePreview, _window: &mut Window, cx: &mut Context<Self>) {
+        self.toggle_panel(cx, |workspace| workspace.preview_visible = !workspace.preview_visible);
+    }
+
+    fn toggle_console(&mut self, _: &ToggleConsole, _window: &mut Window, cx: &mut Context<Self>) {
+        self.toggle_panel(cx, |workspace| workspace.console_visible = !workspace.console_visible);
+    }
+
+    /// Change the zoom of this session; the configuration is left untouched
+    fn set_zoom(&mut self, zoom: f32, cx: &mut Context<Self>) {
+        let configured = self.state.read().config.read().appearance.ui_scale;
+        // Stop where the interface stops growing or shrinking
+        let ui_scale = (configured * zoom).clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end());
+        self.zoom = ui_scale / configured;
+        cx.notify();
+    }
+
+    /// `appearance.ui_scale` with the zoom of this session applied
+    fn ui_scale(&self) -> f32 {
+        let configured = self.state.read().config.read().appearance.ui_scale;
+        (configured * self.zoom).clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end())
+    }
+
+    fn zoom_in(&mut self, _: &ZoomIn, _window: &mut Window, cx: &mut Context<Self>) {
+        self.set_zoom(self.zoom + ZOOM_STEP, cx);
+    }
+
+    fn zoom_out(&mut self, _: &ZoomOut, _window: &mut Window, cx: &mut Context<Self>) {
+        self.set_zoom(self.zoom - ZOOM_STEP, cx);
+    }
+
+    fn reset_zoom(&mut self, _: &ResetZoom, _window: &mut Window, cx: &mut Context<Self>) {
+        self.set_zoom(1.0, cx);
+    }
+
+    fn open_documentation(&mut self, _: &OpenDocumentation, _window: &mut Window, cx: &mut Context<Self>) {
+        cx.open_url(DOCUMENTATION_URL);
+    }
+
+    /// Reveal the line under the active editor's cursor in the preview
+    fn reveal_in_preview(&mut self, _: &RevealInPreview, _window: &mut Window, cx: &mut Context<Self>) {
+        let target = self.state
+            .read()
+            .get_active_workspace()
+            .and_then(|workspace| workspace.read().get_active_editor())
+            .and_then(|editor| {
+                let editor = editor.read();
+                let path = editor.document.path.clone()?;
+                Some((path, editor.cursor_position().0))
+            });
+
+        if let Some((path, line)) = target {
+            self.preview.update(cx, |preview, cx| preview.reveal_line(&path, line, cx));
         }
     }
 }
 
+/// Tell the user a save failed, so unsaved work is not taken for saved
+fn report_save_error(name: &str, err: &anyhow::Error, window: &mut Window, cx: &mut App) {
+    tracing::warn!("Failed to save {}: {}", name, err);
+    let message = format!("Failed to save {}", name);
+    let detail = format!("{}\n\nUse Save As to write the document elsewhere.", err);
+    // Only acknowledged, so the answer is not awaited
+    drop(window.prompt(PromptLevel::Critical, &message, Some(&detail), &["OK"], cx));
+}
+
 impl Render for MainWindow {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
-        let theme = self.theme.read();
-        let bg_color = theme.parse_color(&theme.background.editor);
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let bg_color = {
+            let theme = self.theme.read();
+            theme.parse_color(&theme.background.editor)
+        };
+        // Follows zooming as well as edits of the configuration
+        window.set_rem_size(px(BASE_REM_SIZE * self.ui_scale()));
+        let sidebar_position = self.state.read().config.read().appearance.sidebar_position;
+        let banner = self.external_change_banner(cx);
 
         let workspace_state = self.state.read();
         let active_workspace = workspace_state.get_active_workspace();
@@ -73,7 +714,26 @@ impl Render for MainWindow {
 
         div()
             .size_full()
+            .relative()
             .bg(bg_color)
+            .on_action(cx.listener(Self::toggle_command_palette))
+            .on_action(cx.listener(Self::go_to_line))
+            .on_action(cx.listener(Self::new_file))
+            .on_action(cx.listener(Self::open_file))
+            .on_action(cx.listener(Self::open_folder))
+            .on_action(cx.listener(Self::save_file))
+            .on_action(cx.listener(Self::save_file_as))
+            .on_action(cx.listener(Self::export_pdf))
+            .on_action(cx.listener(Self::toggle_sidebar))
+            .on_action(cx.listener(Self::toggle_preview))
+            .on_action(cx.listener(Self::toggle_console))
+            .on_action(cx.listener(Self::zoom_in))
+            .on_action(cx.listener(Self::zoom_out))
+            .on_action(cx.listener(Self::reset_zoom))
+            .on_action(cx.listener(Self::open_documentation))
+            .on_action(cx.listener(Self::reveal_in_preview))
+            .on_action(cx.listener(Self::use_lf_line_endings))
+            .on_action(cx.listener(Self::use_crlf_line_endings))
             .flex()
             .flex_col()
             .child(self.navbar.clone())
@@ -82,12 +742,15 @@ impl Render for MainWindow {
                     .flex_1()
                     .flex()
                     .flex_row()
-                    .when(sidebar_visible, |this| this.child(self.sidebar.clone()))
+                    .when(sidebar_visible && sidebar_position == SidebarPosition::Left, |this| {
+                        this.child(self.sidebar.clone())
+                    })
                     .child(
                         div()
                             .flex_1()
                             .flex()
                             .flex_col()
+                            .children(banner)
                             .child(
                                 div()
                                     .flex_1()
@@ -98,7 +761,11 @@ impl Render for MainWindow {
                             )
                             .when(console_visible, |this| this.child(self.console.clone()))
                     )
+                    .when(sidebar_visible && sidebar_position == SidebarPosition::Right, |this| {
+                        this.child(self.sidebar.clone())
+                    })
             )
             .child(self.status_bar.clone())
+            .child(self.command_palette.clone())
     }
 }
@@ -0,0 +1,419 @@
+//! Headless commands sharing the editor's configuration, project and compiler
+
+use anyhow::{ anyhow, Context, Result };
+use clap::{ Args, Parser, Subcommand, ValueEnum };
+use editor_core::{ Config, ConfigLayer, LayeredConfig, Project, ProjectConfig };
+use serde::Serialize;
+use std::path::{ Path, PathBuf };
+use std::process::ExitCode;
+use typst_integration::{
+    CompileError,
+    CompiledDocument,
+    Diagnostic,
+    ExportFormat,
+    ExportOptions,
+    Exporter,
+    PageSelection,
+    TypstCompiler,
+};
+use ui::theme::{
+    check_contrast,
+    import_vscode_theme_file,
+    ThemeRegistry,
+    ThemeSource,
+    VsCodeImport,
+    AA_CONTRAST_RATIO,
+};
+
+#[derive(Parser)]
+#[command(name = "typst-studio", version, about = "Typst editor with live preview")]
+pub struct Cli {
+    /// Configuration file to use instead of the global one; the project file still applies
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Compile a document to PDF
+    Compile(CompileArgs),
+    /// Export a document to PDF, PNG or SVG
+    Export(ExportArgs),
+    /// Compile a document and report diagnostics without writing output
+    Check(CheckArgs),
+    /// Inspect or upgrade configuration files
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// List or import color themes
+    #[command(subcommand)]
+    Theme(ThemeCommand),
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the JSON Schema of the configuration file
+    Schema {
+        /// Write the schema to a file instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Upgrade a configuration file to the current format, keeping TOML comments
+    Migrate {
+        /// File to upgrade; defaults to `--config` or the global configuration file
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ThemeCommand {
+    /// List the built-in themes and those in the themes directory
+    List,
+    /// Convert a VS Code color theme into a theme file
+    Import {
+        /// VS Code theme JSON file
+        file: PathBuf,
+
+        /// Name to select the theme by; defaults to the file's name
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Where to write the theme; defaults to the themes directory
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Report the WCAG contrast of a theme's text colors, failing below level AA
+    Check {
+        /// Name of the theme
+        name: String,
+    },
+}
+
+#[derive(Args)]
+pub struct InputArgs {
+    /// Main file; defaults to the project's main file
+    pub file: Option<PathBuf>,
+
+    /// Project root; defaults to the project containing the file or the current directory
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct OutputArgs {
+    /// Output directory; defaults to the project's output directory
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Pages to export, e.g. `1-3,5,8-`
+    #[arg(long)]
+    pub pages: Option<PageSelection>,
+
+    /// File name template; supports {name}, {page}, {0page} and {total}; defaults to
+    /// the project's `output_filename`
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+#[derive(Args)]
+pub struct CompileArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    /// Produce a PDF/A-2b conforming file
+    #[arg(long)]
+    pub pdf_a: bool,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[arg(short, long, default_value = "pdf")]
+    pub format: ExportFormat,
+
+    /// Resolution of PNG export, in dots per inch
+    #[arg(long, default_value_t = typst_integration::export::DEFAULT_DPI)]
+    pub dpi: f32,
+
+    /// Produce a PDF/A-2b conforming file
+    #[arg(long)]
+    pub pdf_a: bool,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[arg(long, value_enum, default_value_t = ReportFormat::Human)]
+    pub format: ReportFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    Human,
+    Json,
+}
+
+/// Machine-readable result of `check`
+#[derive(Serialize)]
+struct CheckReport {
+    success: bool,
+    file: PathBuf,
+    duration_ms: Option<u128>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Run a headless command, returning the process exit code
+pub fn run(command: Command, config: Option<&Path>) -> Result<ExitCode> {
+    match command {
+        Command::Compile(args) => {
+            let mut options = ExportOptions::new(ExportFormat::Pdf);
+            options.pdf_a = args.pdf_a;
+            export(&args.input, &args.output, options, config)
+        }
+        Command::Export(args) => {
+            let mut options = ExportOptions::new(args.format);
+            options.dpi = args.dpi;
+            options.pdf_a = args.pdf_a;
+            export(&args.input, &args.output, options, config)
+        }
+        Command::Check(args) => check(&args.input, args.format, config),
+        Command::Config(command) => run_config(command, config),
+        Command::Theme(command) => run_theme(command),
+    }
+}
+
+fn run_theme(command: ThemeCommand) -> Result<ExitCode> {
+    match command {
+        ThemeCommand::List => {
+            let (registry, errors) = ThemeRegistry::load();
+            for error in &errors {
+                eprintln!("warning: {}", error);
+            }
+            for name in registry.names() {
+                match registry.source(name) {
+                    Some(ThemeSource::File(path)) => println!("{} ({})", name, path.display()),
+                    _ => println!("{}", name),
+                }
+            }
+        }
+        ThemeCommand::Import { file, name, output } => {
+            let VsCodeImport { theme, report } = import_vscode_theme_file(&file)?;
+            let name = name
+                .or_else(|| file.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+                .ok_or_else(|| anyhow!("No theme name given"))?;
+            let path = match output {
+                Some(path) => path,
+                None => {
+                    let directory = Config::themes_dir()
+                        .ok_or_else(|| anyhow!("No configuration directory"))?;
+                    std::fs::create_dir_all(&directory)?;
+                    directory.join(format!("{}.json", name))
+                }
+            };
+            std::fs::write(&path, serde_json::to_string_pretty(&theme)?)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+
+            for (key, error) in &report.invalid {
+                eprintln!("warning: {}: {}", key, error);
+            }
+            if !report.defaults.is_empty() {
+                eprintln!("kept from the base theme: {}", report.defaults.join(", "));
+            }
+            if !report.unmapped_colors.is_empty() {
+                eprintln!("unmapped colors: {}", report.unmapped_colors.join(", "));
+            }
+            if !report.unmapped_scopes.is_empty() {
+                eprintln!("unmapped scopes: {}", report.unmapped_scopes.join(", "));
+            }
+            eprintln!("Wrote {}; select it with theme = \"{}\"", path.display(), name);
+        }
+        ThemeCommand::Check { name } => {
+            let (registry, errors) = ThemeRegistry::load();
+            for error in &errors {
+                eprintln!("warning: {}", error);
+            }
+            let theme = registry.get(&name).ok_or_else(|| anyhow!("Unknown theme '{}'", name))?;
+            let checks = check_contrast(theme);
+            for check in &checks {
+                let verdict = if check.passes_aa() { "ok" } else { "below AA" };
+                println!("{} ({})", check, verdict);
+            }
+            if checks.iter().any(|check| !check.passes_aa()) {
+                eprintln!("{} fails WCAG AA, which requires {}:1", name, AA_CONTRAST_RATIO);
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_config(command: ConfigCommand, config: Option<&Path>) -> Result<ExitCode> {
+    match command {
+        ConfigCommand::Schema { output } => {
+            let schema = serde_json::to_string_pretty(&Config::json_schema())?;
+            match output {
+                Some(path) => std::fs::write(&path, schema)?,
+                None => println!("{}", schema),
+            }
+        }
+        ConfigCommand::Migrate { file } => {
+            let path = file
+                .or_else(|| config.map(Path::to_path_buf))
+                .or_else(Config::global_config_path)
+                .ok_or_else(|| anyhow!("No configuration file given"))?;
+            let applied = Config::migrate_file(&path)
+                .with_context(|| format!("Failed to migrate {}", path.display()))?;
+            if applied.is_empty() {
+                eprintln!("{} is up to date", path.display());
+            }
+            for step in applied {
+                eprintln!("{}: {}", path.display(), step);
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Load the project's layered configuration, optionally replacing the global file
+///
+/// Invalid settings are reported as warnings and fall back to their defaults.
+fn load_config(path: Option<&Path>, project: &Project) -> Result<Config> {
+    let (mut layers, errors) = LayeredConfig::load_checked(Some(&project.root));
+    if let Some(path) = path {
+        layers
+            .load_layer(ConfigLayer::Global, path)
+            .with_context(|| format!("Failed to load configuration from {}", path.display()))?;
+    }
+    let (config, invalid) = layers.resolve_checked();
+    for error in errors.iter().chain(&invalid) {
+        eprintln!("warning: {}", error);
+    }
+    Ok(config)
+}
+
+/// Resolve the project and its main file from the command line
+fn resolve_input(input: &InputArgs) -> Result<(Project, PathBuf)> {
+    let file = input.file
+        .as_ref()
+        .map(std::path::absolute)
+        .transpose()?;
+
+    let root = match (&input.root, &file) {
+        (Some(root), _) => std::path::absolute(root)?,
+        (None, Some(file)) => {
+            let parent = file
+                .parent()
+                .ok_or_else(|| anyhow!("{} has no parent directory", file.display()))?;
+            // The nearest directory with a project configuration file, so files
+            // in subdirectories can import from the whole project
+            parent
+                .ancestors()
+                .find(|directory| ProjectConfig::find(directory).is_some())
+                .unwrap_or(parent)
+                .to_path_buf()
+        }
+        (None, None) => std::env::current_dir()?,
+    };
+
+    let project = Project::discover(root)?;
+    let main = file
+        .or_else(|| project.main_file.clone())
+        .ok_or_else(|| anyhow!("No input file given and no main file found in {}", project.root.display()))?;
+    Ok((project, main))
+}
+
+/// A compiler taking the project's `compiler_args`
+fn compiler(project: &Project) -> Result<TypstCompiler> {
+    TypstCompiler::with_args(&project.compiler_args)
+        .with_context(|| format!("Invalid compiler_args of {}", project.root.display()))
+}
+
+fn compile(project: &Project, main: &Path) -> Result<Option<CompiledDocument>> {
+    match compiler(project)?.compile_blocking(&project.root, main) {
+        Ok(compiled) => Ok(Some(compiled)),
+        Err(err) => {
+            let err = err.downcast::<CompileError>()?;
+            print_diagnostics(&err.diagnostics);
+            eprintln!("{}", err);
+            Ok(None)
+        }
+    }
+}
+
+fn export(
+    input: &InputArgs,
+    output: &OutputArgs,
+    mut options: ExportOptions,
+    config: Option<&Path>
+) -> Result<ExitCode> {
+    let (project, main) = resolve_input(input)?;
+    let config = load_config(config, &project)?;
+    let Some(compiled) = compile(&project, &main)? else {
+        return Ok(ExitCode::FAILURE);
+    };
+    print_diagnostics(&compiled.warnings);
+
+    options.pages = output.pages.clone();
+    options.filename_template = output.name.clone().or_else(|| project.settings.output_filename.clone());
+    let exporter = match &output.output {
+        Some(directory) => Exporter::new(directory),
+        None => Exporter::for_project(&project),
+    };
+
+    for path in exporter.export(&compiled, &main, &options)? {
+        println!("{}", path.display());
+    }
+    if config.compiler.show_compilation_output {
+        eprintln!("Compiled {} in {:.2?}", main.display(), compiled.duration);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn check(input: &InputArgs, format: ReportFormat, config: Option<&Path>) -> Result<ExitCode> {
+    let (project, main) = resolve_input(input)?;
+    let config = load_config(config, &project)?;
+    let report = match compiler(&project)?.compile_blocking(&project.root, &main) {
+        Ok(compiled) => CheckReport {
+            success: true,
+            file: main,
+            duration_ms: Some(compiled.duration.as_millis()),
+            diagnostics: compiled.warnings,
+        },
+        Err(err) => CheckReport {
+            success: false,
+            file: main,
+            duration_ms: None,
+            diagnostics: err.downcast::<CompileError>()?.diagnostics,
+        },
+    };
+
+    match format {
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        ReportFormat::Human => {
+            print_diagnostics(&report.diagnostics);
+            let shown = report.duration_ms.filter(|_| config.compiler.show_compilation_output);
+            if let Some(duration_ms) = shown {
+                eprintln!("Compiled {} in {}ms", report.file.display(), duration_ms);
+            }
+        }
+    }
+    Ok(if report.success { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+fn print_diagnostics(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+}
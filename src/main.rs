@@ -1,12 +1,18 @@
+mod cli;
+
 use anyhow::Result;
+use clap::Parser;
+use cli::Cli;
 use gpui::*;
+use std::process::ExitCode;
 use tracing_subscriber;
 use ui::TypstEditorApp;
 
-fn main() -> Result<()> {
-    // Initialize logging
+fn main() -> Result<ExitCode> {
+    // Initialize logging; stdout is reserved for command output
     tracing_subscriber
         ::fmt()
+        .with_writer(std::io::stderr)
         .with_env_filter(
             tracing_subscriber::EnvFilter
                 ::from_default_env()
@@ -14,6 +20,11 @@ fn main() -> Result<()> {
         )
         .init();
 
+    let args = Cli::parse();
+    if let Some(command) = args.command {
+        return cli::run(command, args.config.as_deref());
+    }
+
     tracing::info!("Starting Typst Studio");
 
     // Initialize GPUI application
@@ -23,5 +34,5 @@ fn main() -> Result<()> {
         app.open_main_window(cx);
     });
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }